@@ -0,0 +1,65 @@
+use crate::config::AdaptiveTpsConfig;
+use std::time::{Duration, Instant};
+
+// 429s from one burst arrive together; treat them as a single signal.
+const CUT_COOLDOWN: Duration = Duration::from_secs(1);
+// Only probe upward while demand actually presses against the learned limit.
+const BUSY_RATIO: f64 = 0.8;
+
+/// AIMD rate limit learned from upstream 429s: multiplicative cut on a 429,
+/// additive step up after each clean interval.
+#[derive(Debug)]
+pub struct AdaptiveTps {
+    pub cfg: AdaptiveTpsConfig,
+    limit: Option<f64>, // None => unlimited until the first 429
+    window_start: Instant,
+    window_calls: u32,
+    observed_tps: f64,  // call rate over the last full second
+    last_change: Instant,
+}
+
+impl AdaptiveTps {
+    pub fn new(cfg: AdaptiveTpsConfig, start_tps: u32) -> Self {
+        let now = Instant::now();
+        let limit = (start_tps > 0).then(|| cfg.clamp(start_tps as f64));
+        Self { cfg, limit, window_start: now, window_calls: 0, observed_tps: 0.0, last_change: now }
+    }
+
+    pub fn limit(&self) -> Option<f64> { self.limit }
+
+    /// Count a call admitted by the bucket; returns a raised limit when one is due.
+    pub fn on_call(&mut self) -> Option<f64> {
+        self.window_calls += 1;
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            self.observed_tps = self.window_calls as f64 / elapsed.as_secs_f64();
+            self.window_calls = 0;
+            self.window_start = Instant::now();
+        }
+
+        let limit = self.limit?;
+        let interval = Duration::from_secs(self.cfg.increase_interval_s.max(1));
+        if self.last_change.elapsed() < interval || self.observed_tps < limit * BUSY_RATIO {
+            return None;
+        }
+        let raised = self.cfg.clamp(limit + self.cfg.increase_tps);
+        self.last_change = Instant::now();
+        (raised > limit).then(|| {
+            self.limit = Some(raised);
+            raised
+        })
+    }
+
+    /// Upstream answered 429; returns the lowered limit, or None inside the cooldown.
+    pub fn on_rate_limited(&mut self) -> Option<f64> {
+        if self.limit.is_some() && self.last_change.elapsed() < CUT_COOLDOWN {
+            return None;
+        }
+        let current = self.window_calls as f64 / self.window_start.elapsed().as_secs_f64().max(0.001);
+        let base = self.limit.unwrap_or_else(|| self.observed_tps.max(current));
+        let cut = self.cfg.clamp(base * self.cfg.decrease_factor.clamp(0.05, 0.95));
+        self.limit = Some(cut);
+        self.last_change = Instant::now();
+        Some(cut)
+    }
+}
@@ -0,0 +1,86 @@
+use crate::circuit_breaker::{BanSource, ManualBan};
+use crate::relay::HttpState;
+use crate::state::ProviderState;
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+// Operator actions. Mounted behind `auth::require_auth` with /status.
+
+#[derive(Deserialize)]
+pub struct DrainRequest {
+    pub provider: String, // id or name
+    #[serde(default = "default_drained")]
+    pub drained: bool,
+}
+fn default_drained() -> bool { true }
+
+/// POST /admin/drain {"provider": "...", "drained": true|false}
+pub async fn drain(State(state): State<HttpState>, Json(req): Json<DrainRequest>) -> (StatusCode, Json<Value>) {
+    let Some(p) = state.app.snapshot().registry.find(&req.provider) else {
+        return unknown_provider(&req.provider);
+    };
+    p.set_drained(req.drained);
+    (StatusCode::OK, Json(json!({ "name": p.name, "label": p.label(), "drained": p.is_drained() })))
+}
+
+#[derive(Deserialize)]
+pub struct BanRequest {
+    pub provider: String,
+    pub reason: String,
+    #[serde(default)]
+    pub duration_s: Option<u64>, // absent => until /admin/unban
+}
+
+/// POST /admin/ban {"provider": "...", "reason": "...", "duration_s": 3600}
+pub async fn ban(State(state): State<HttpState>, Json(req): Json<BanRequest>) -> (StatusCode, Json<Value>) {
+    let Some(p) = state.app.snapshot().registry.find(&req.provider) else {
+        return unknown_provider(&req.provider);
+    };
+    let until = req.duration_s.and_then(ManualBan::deadline_in);
+    p.set_manual_ban(Some(ManualBan { reason: req.reason, until, source: BanSource::Admin }));
+    (StatusCode::OK, Json(json!({ "name": p.name, "label": p.label(), "ban": manual_ban_json(&p) })))
+}
+
+#[derive(Deserialize)]
+pub struct UnbanRequest {
+    pub provider: String,
+}
+
+/// POST /admin/unban {"provider": "..."} — lifts a manual ban and the misconfigured state
+/// (breaker bans run their course).
+pub async fn unban(State(state): State<HttpState>, Json(req): Json<UnbanRequest>) -> (StatusCode, Json<Value>) {
+    let Some(p) = state.app.snapshot().registry.find(&req.provider) else {
+        return unknown_provider(&req.provider);
+    };
+    p.set_manual_ban(None);
+    p.clear_misconfigured("admin unban");
+    (StatusCode::OK, Json(json!({ "name": p.name, "label": p.label(), "ban": Value::Null })))
+}
+
+/// POST /admin/snapshot — write the dashboard snapshot files to ui.snapshot_dir.
+pub async fn snapshot(State(state): State<HttpState>) -> (StatusCode, Json<Value>) {
+    let dir = state.app.snapshot().cfg.ui.snapshot_dir.clone();
+    match crate::ui::export_snapshot(&state.app, &dir) {
+        Ok(files) => {
+            let files: Vec<String> = files.iter().map(|f| f.display().to_string()).collect();
+            (StatusCode::OK, Json(json!({ "files": files })))
+        }
+        Err(e) => {
+            let error = format!("writing snapshot to {}: {}", dir.display(), e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": error })))
+        }
+    }
+}
+
+/// `null`, or the active manual ban as shown in /status.
+pub fn manual_ban_json(p: &ProviderState) -> Value {
+    match p.manual_ban() {
+        Some(b) => json!({ "reason": b.reason, "until": b.until_epoch(), "source": b.source.as_str() }),
+        None => Value::Null,
+    }
+}
+
+fn unknown_provider(key: &str) -> (StatusCode, Json<Value>) {
+    (StatusCode::NOT_FOUND, Json(json!({ "error": format!("unknown provider {:?}", key) })))
+}
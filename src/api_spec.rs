@@ -0,0 +1,224 @@
+use axum::{
+    http::header,
+    routing::{get, MethodRouter},
+    Router,
+};
+use serde_json::{json, Map, Value};
+
+/// One operation on a path, as GET /api-spec describes it.
+pub struct Op {
+    pub method: &'static str, // lowercase, as OpenAPI keys it
+    pub summary: &'static str,
+    pub query: &'static [Field],
+    pub body: &'static [Field], // members of a JSON object body; empty => no body
+    pub response: &'static str, // content type of a successful answer
+}
+
+pub struct Field {
+    pub name: &'static str,
+    pub ty: &'static str, // OpenAPI type: string, integer, boolean, object, array
+    pub required: bool,
+    pub description: &'static str,
+}
+
+const fn field(name: &'static str, ty: &'static str, required: bool, description: &'static str) -> Field {
+    Field { name, ty, required, description }
+}
+
+const JSON: &str = "application/json";
+
+pub const HEALTH: &[Op] = &[
+    Op { method: "get", summary: "Liveness", query: &[], body: &[], response: JSON },
+    Op { method: "post", summary: "JSON-RPC 2.0 call, relayed to a provider", query: &[], body: &[], response: JSON },
+];
+pub const READYZ: &[Op] =
+    &[Op { method: "get", summary: "200 while at least one provider is selectable, else 503", query: &[], body: &[], response: JSON }];
+pub const GRAPHQL: &[Op] = &[Op {
+    method: "post",
+    summary: "GraphQL passthrough to providers flagged supports_graphql",
+    query: &[],
+    body: &[field("query", "string", true, "GraphQL document"), field("variables", "object", false, "")],
+    response: JSON,
+}];
+#[cfg(feature = "grpc")]
+pub const GRPC: &[Op] = &[Op {
+    method: "post",
+    summary: "gRPC-Web unary Relay/Call wrapping one JSON-RPC call (protobuf body, see how_to_run.txt)",
+    query: &[],
+    body: &[],
+    response: "application/grpc-web+proto",
+}];
+pub const BACKFILL: &[Op] = &[Op {
+    method: "post",
+    summary: "Run one call per block across every healthy provider; one NDJSON line per block",
+    query: &[],
+    body: &[
+        field("method", "string", true, "JSON-RPC method"),
+        field("params", "array", false, "params with \"$block\" where the block number goes"),
+        field("from", "integer", true, "first block (integer or 0x-hex)"),
+        field("to", "integer", true, "last block, inclusive"),
+    ],
+    response: "application/x-ndjson",
+}];
+pub const REST_BLOCK: &[Op] = &[Op {
+    method: "get",
+    summary: "Block by number or tag",
+    query: &[field("full", "boolean", false, "include full transaction objects")],
+    body: &[],
+    response: JSON,
+}];
+pub const REST_BALANCE: &[Op] = &[Op {
+    method: "get",
+    summary: "Balance of an address",
+    query: &[field("block", "string", false, "tag, decimal or 0x-hex; default latest")],
+    body: &[],
+    response: JSON,
+}];
+pub const REST_TX: &[Op] = &[Op { method: "get", summary: "Transaction by hash", query: &[], body: &[], response: JSON }];
+pub const REST_RECEIPT: &[Op] = &[Op { method: "get", summary: "Transaction receipt by hash", query: &[], body: &[], response: JSON }];
+pub const STATUS: &[Op] = &[Op {
+    method: "get",
+    summary: "Relay and provider state",
+    query: &[
+        field("include", "string", false, "comma-separated extras: retired"),
+        field("filter", "string", false, "comma-separated: healthy, unhealthy, banned, drained, off_schedule, near_quota, selectable"),
+        field("fields", "string", false, "comma-separated provider fields to keep"),
+        field("sort", "string", false, "provider field to sort by; \"-\" prefix for descending"),
+        field("format", "string", false, "json (default), table or prometheus"),
+        field("watch", "boolean", false, "stream a document per state change as NDJSON"),
+    ],
+    body: &[],
+    response: JSON,
+}];
+pub const TX_TRACK: &[Op] = &[Op {
+    method: "get",
+    summary: "A transaction this relay broadcast: sender, nonce, fees and whether it was replaced or cancelled",
+    query: &[],
+    body: &[],
+    response: JSON,
+}];
+pub const STATS_ORIGINS: &[Op] =
+    &[Op { method: "get", summary: "Call counters per X-RLY-Origin (origin_stats)", query: &[], body: &[], response: JSON }];
+pub const ADMIN_DRAIN: &[Op] = &[Op {
+    method: "post",
+    summary: "Drain or undrain a provider",
+    query: &[],
+    body: &[field("provider", "string", true, "id or name"), field("drained", "boolean", false, "default true")],
+    response: JSON,
+}];
+pub const ADMIN_BAN: &[Op] = &[Op {
+    method: "post",
+    summary: "Ban a provider by hand",
+    query: &[],
+    body: &[
+        field("provider", "string", true, "id or name"),
+        field("reason", "string", true, ""),
+        field("duration_s", "integer", false, "absent => until /admin/unban"),
+    ],
+    response: JSON,
+}];
+pub const ADMIN_UNBAN: &[Op] = &[Op {
+    method: "post",
+    summary: "Lift a manual ban and the misconfigured state",
+    query: &[],
+    body: &[field("provider", "string", true, "id or name")],
+    response: JSON,
+}];
+pub const ADMIN_SNAPSHOT: &[Op] =
+    &[Op { method: "post", summary: "Write the dashboard snapshot files to ui.snapshot_dir", query: &[], body: &[], response: JSON }];
+const API_SPEC: &[Op] = &[Op { method: "get", summary: "This document", query: &[], body: &[], response: JSON }];
+
+/// A router that keeps a description of each route it is given, so /api-spec is built
+/// from the same calls that register the routes.
+pub struct Documented<S> {
+    router: Router<S>,
+    auth: bool, // behind server.auth
+    paths: Vec<(String, bool, &'static [Op])>,
+}
+
+impl<S: Clone + Send + Sync + 'static> Documented<S> {
+    pub fn new(auth: bool) -> Self { Self { router: Router::new(), auth, paths: Vec::new() } }
+
+    pub fn route(mut self, path: &str, ops: &'static [Op], handler: MethodRouter<S>) -> Self {
+        self.router = self.router.route(path, handler);
+        self.paths.push((path.to_string(), self.auth, ops));
+        self
+    }
+
+    /// Apply `f` to the routes so far (e.g. a route_layer); their descriptions are kept.
+    pub fn map_router(mut self, f: impl FnOnce(Router<S>) -> Router<S>) -> Self {
+        self.router = f(self.router);
+        self
+    }
+
+    pub fn merge(mut self, other: Documented<S>) -> Self {
+        self.router = self.router.merge(other.router);
+        self.paths.extend(other.paths);
+        self
+    }
+
+    /// The router, plus GET /api-spec serving the OpenAPI description of every route.
+    pub fn finish(self) -> Router<S> {
+        let mut paths = self.paths;
+        paths.push(("/api-spec".to_string(), false, API_SPEC));
+        let spec = openapi(&paths).to_string();
+        let serve = move || {
+            let spec = spec.clone();
+            async move { ([(header::CONTENT_TYPE, JSON)], spec) }
+        };
+        self.router.route("/api-spec", get(serve))
+    }
+}
+
+fn openapi(paths: &[(String, bool, &'static [Op])]) -> Value {
+    let mut out = Map::new();
+    for (path, auth, ops) in paths {
+        // axum's ":name" segments are OpenAPI's "{name}"
+        let segments: Vec<String> =
+            path.split('/').map(|s| s.strip_prefix(':').map_or_else(|| s.to_string(), |p| format!("{{{}}}", p))).collect();
+        let path_params: Vec<Value> = path
+            .split('/')
+            .filter_map(|s| s.strip_prefix(':'))
+            .map(|p| json!({ "name": p, "in": "path", "required": true, "schema": { "type": "string" } }))
+            .collect();
+        let item: Map<String, Value> = ops.iter().map(|op| (op.method.to_string(), operation(op, &path_params, *auth))).collect();
+        out.insert(segments.join("/"), Value::Object(item));
+    }
+    json!({
+        "openapi": "3.0.3",
+        "info": { "title": "RPC relay", "version": env!("CARGO_PKG_VERSION") },
+        "components": {
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer" },
+                "basic": { "type": "http", "scheme": "basic" },
+            },
+        },
+        "paths": out,
+    })
+}
+
+fn operation(op: &Op, path_params: &[Value], auth: bool) -> Value {
+    let mut params = path_params.to_vec();
+    params.extend(op.query.iter().map(|f| {
+        json!({ "name": f.name, "in": "query", "required": f.required, "description": f.description, "schema": { "type": f.ty } })
+    }));
+    let mut o = json!({
+        "summary": op.summary,
+        "parameters": params,
+        "responses": { "200": { "description": "OK", "content": { op.response: {} } } },
+    });
+    if !op.body.is_empty() {
+        let props: Map<String, Value> =
+            op.body.iter().map(|f| (f.name.to_string(), json!({ "type": f.ty, "description": f.description }))).collect();
+        let required: Vec<&str> = op.body.iter().filter(|f| f.required).map(|f| f.name).collect();
+        o["requestBody"] = json!({
+            "required": true,
+            "content": { JSON: { "schema": { "type": "object", "properties": props, "required": required } } },
+        });
+    }
+    if auth {
+        o["security"] = json!([{ "bearer": [] }, { "basic": [] }]);
+        o["responses"]["401"] = json!({ "description": "server.auth is set and the request has no valid credentials" });
+    }
+    o
+}
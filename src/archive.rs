@@ -0,0 +1,60 @@
+use crate::config::{ArchiveProbeConfig, ProbeTokens};
+use crate::jsonrpc::JsonRpcRequest;
+use crate::state::{AppState, HistoryDepth, ProviderState};
+use crate::upstream;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+use tracing::debug;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+const ZERO_ADDRESS: &str = "0x0000000000000000000000000000000000000000";
+
+/// Learns each provider's history depth (health_monitor.archive_probe) so historical state
+/// reads can skip providers that would answer "missing trie node". Idle unless configured.
+pub async fn archive_probe_loop(app: Arc<AppState>) {
+    loop {
+        let snap = app.snapshot();
+        let Some(ac) = snap.cfg.health_monitor.archive_probe.clone() else {
+            drop(snap);
+            sleep(Duration::from_secs(5)).await;
+            continue;
+        };
+        let (tokens, max_bytes) = (snap.cfg.health_monitor.probe_tokens, snap.cfg.relay.response_limit());
+        let interval = Duration::from_secs(ac.interval_s.max(1));
+        let due: Vec<_> = snap
+            .registry
+            .all()
+            .into_iter()
+            .filter(|p| p.is_healthy() && p.get_latest_block() > 0 && p.history_probe_due(interval))
+            .filter(|p| p.try_probe_budget())
+            .collect();
+        drop(snap);
+        futures::future::join_all(due.iter().map(|p| probe_depth(p, &ac, tokens, max_bytes))).await;
+        sleep(Duration::from_secs(5)).await;
+    }
+}
+
+// Deepest first: block 1 (full archive), then each configured depth. A transport failure
+// ends the round without a verdict; only an error reply counts as "state not there".
+async fn probe_depth(p: &ProviderState, ac: &ArchiveProbeConfig, tokens: ProbeTokens, max_bytes: usize) {
+    let head = p.get_latest_block();
+    let mut depths: Vec<u64> = ac.depths.iter().copied().filter(|&d| d < head.saturating_sub(1)).collect();
+    depths.sort_unstable_by(|a, b| b.cmp(a));
+    let rungs = std::iter::once((1, HistoryDepth::Archive)).chain(depths.into_iter().map(|d| (head - d, HistoryDepth::Blocks(d))));
+    for (block, depth) in rungs {
+        let payload = JsonRpcRequest::new("eth_getBalance", json!([ZERO_ADDRESS, format!("0x{:x}", block)])).to_value();
+        if tokens == ProbeTokens::Accounted {
+            p.charge_token();
+        }
+        match upstream::call(p, &payload, PROBE_TIMEOUT, max_bytes).await {
+            Ok(v) if v.get("error").is_none() => return p.set_history_depth(depth),
+            Ok(v) => debug!(provider = %p.label(), block, error = %v["error"], "no state at probed block"),
+            Err(e) => {
+                debug!(provider = %p.label(), block, reason = e.reason().as_str(), error = %e, "archive probe failed");
+                return;
+            }
+        }
+    }
+    p.set_history_depth(HistoryDepth::Blocks(0));
+}
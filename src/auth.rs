@@ -0,0 +1,52 @@
+use crate::relay::HttpState;
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::json;
+
+/// Guard for management surfaces (/status, admin). Relay traffic on `/` stays open.
+/// Re-reads `server.auth` per request so hot reload can rotate credentials.
+pub async fn require_auth(State(state): State<HttpState>, req: Request, next: Next) -> Response {
+    let auth = state.app.snapshot().cfg.server.auth.clone();
+    let Some(auth) = auth else { return next.run(req).await };
+
+    if authorized(&auth, req.headers()) {
+        return next.run(req).await;
+    }
+    let mut resp = (StatusCode::UNAUTHORIZED, Json(json!({"error": "unauthorized"}))).into_response();
+    if auth.basic_username.is_some() {
+        resp.headers_mut().insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_static("Basic realm=\"rpc-relay\""));
+    }
+    resp
+}
+
+fn authorized(auth: &crate::config::AuthConfig, headers: &HeaderMap) -> bool {
+    let Some(given) = headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()) else { return false };
+
+    if let (Some(token), Some(bearer)) = (auth.bearer_token.as_deref(), given.strip_prefix("Bearer ")) {
+        if ct_eq(token.as_bytes(), bearer.trim().as_bytes()) { return true; }
+    }
+    if let (Some(user), Some(basic)) = (auth.basic_username.as_deref(), given.strip_prefix("Basic ")) {
+        let expected = STANDARD.encode(format!("{}:{}", user, auth.basic_password.as_deref().unwrap_or("")));
+        if ct_eq(expected.as_bytes(), basic.trim().as_bytes()) { return true; }
+    }
+    false
+}
+
+/// Whether the request's X-RLY-Key is one of `keys`; false without one or when `keys` is empty.
+/// Every key is compared, in constant time, so timing doesn't tell which one nearly matched.
+pub fn has_trusted_key(keys: &[String], headers: &HeaderMap) -> bool {
+    let Some(given) = headers.get("x-rly-key").and_then(|v| v.to_str().ok()).map(str::trim) else { return false };
+    keys.iter().fold(false, |found, k| found | ct_eq(k.as_bytes(), given.as_bytes()))
+}
+
+// Constant-time comparison so the check doesn't leak how many leading bytes matched.
+fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() { return false; }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,219 @@
+use crate::config::BackfillConfig;
+use crate::jsonrpc::JsonRpcRequest;
+use crate::relay::{self, HttpState, Leg};
+use crate::relay_error::RelayError;
+use crate::state::{ProviderState, Snapshot};
+use crate::token_bucket::TokenBucket;
+use crate::upstream;
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Placeholder in `params` replaced by each block number (hex).
+const BLOCK: &str = "$block";
+
+#[derive(Deserialize)]
+pub struct BackfillRequest {
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+    pub from: BlockNumber,
+    pub to: BlockNumber,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum BlockNumber {
+    Number(u64),
+    Hex(String),
+}
+
+impl BlockNumber {
+    fn get(&self) -> Option<u64> {
+        match self {
+            BlockNumber::Number(n) => Some(*n),
+            BlockNumber::Hex(s) => s.strip_prefix("0x").and_then(|h| u64::from_str_radix(h, 16).ok()),
+        }
+    }
+}
+
+/// POST /backfill: run `method` once per block in from..=to across every healthy provider,
+/// each within its own token bucket, and stream one NDJSON line per block as it completes.
+pub async fn backfill(State(state): State<HttpState>, Json(req): Json<BackfillRequest>) -> Response {
+    let snap = state.app.snapshot();
+    let Some(bc) = snap.cfg.backfill.clone() else {
+        return (StatusCode::NOT_FOUND, Json(json!({ "error": "backfill is not enabled" }))).into_response();
+    };
+    let (Some(from), Some(to)) = (req.from.get(), req.to.get()) else {
+        return bad_request("from and to must be block numbers (integer or 0x-hex)".to_string());
+    };
+    if to < from || to - from >= bc.max_blocks {
+        return bad_request(format!("range must be non-empty and at most {} blocks", bc.max_blocks));
+    }
+    if !mentions_block(&req.params) {
+        return bad_request(format!("params must contain {:?} where the block number goes", BLOCK));
+    }
+    let providers = relay::healthy_candidates(&snap.registry, snap.cfg.relay.weight_decay.as_ref()).providers().to_vec();
+    if providers.is_empty() {
+        let (status, reply) = RelayError::NoHealthyProviders.into_response(Value::Null);
+        return (status, reply).into_response();
+    }
+
+    // follow reloads without refilling the budget on every request
+    let budget = state.relay.backfill_budget.clone();
+    let current = budget.snapshot().map_or(0, |(rate, _, _)| rate.round() as u32);
+    if current != bc.max_calls_per_s {
+        budget.reset(bc.max_calls_per_s);
+    }
+
+    let (tx, mut rx) = mpsc::channel::<String>(256);
+    let job = Arc::new(Job {
+        queue: Mutex::new((from..=to).map(|b| (b, 0)).collect()),
+        pending: AtomicU64::new(to - from + 1),
+        failed: AtomicU64::new(0),
+        stop: AtomicBool::new(false),
+        method: req.method,
+        params: req.params,
+    });
+    tokio::spawn(run(job, providers, snap, bc, budget, tx));
+    let lines = futures::stream::poll_fn(move |cx| rx.poll_recv(cx).map(|l| l.map(Ok::<_, Infallible>)));
+    ([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(lines)).into_response()
+}
+
+struct Job {
+    queue: Mutex<VecDeque<(u64, u32)>>, // (block, failed tries so far)
+    pending: AtomicU64,                 // blocks without a line yet
+    failed: AtomicU64,
+    stop: AtomicBool, // the client went away
+    method: String,
+    params: Value,
+}
+
+async fn run(
+    job: Arc<Job>,
+    providers: Vec<Arc<ProviderState>>,
+    snap: Arc<Snapshot>,
+    bc: BackfillConfig,
+    budget: Arc<TokenBucket>,
+    tx: mpsc::Sender<String>,
+) {
+    let total = job.pending.load(Ordering::Relaxed);
+    let workers: Vec<_> = providers
+        .iter()
+        .flat_map(|p| std::iter::repeat_n(p, bc.per_provider_concurrency.max(1)))
+        .map(|p| tokio::spawn(worker(job.clone(), p.clone(), snap.clone(), budget.clone(), tx.clone())))
+        .collect();
+    let _ = futures::future::join_all(workers).await;
+
+    // Every worker's provider dropped out; whatever is left can't be served.
+    let left: Vec<_> = job.queue.lock().drain(..).collect();
+    for (block, _) in left {
+        let (_, Json(reply)) = RelayError::NoHealthyProviders.into_response(Value::Null);
+        emit(&job, &tx, block, Err(reply["error"].clone())).await;
+    }
+    let failed = job.failed.load(Ordering::Relaxed);
+    let summary = json!({ "done": true, "blocks": total, "ok": total - failed, "failed": failed });
+    let _ = tx.send(format!("{}\n", summary)).await;
+}
+
+async fn worker(job: Arc<Job>, prov: Arc<ProviderState>, snap: Arc<Snapshot>, budget: Arc<TokenBucket>, tx: mpsc::Sender<String>) {
+    let rc = &snap.cfg.relay;
+    while !job.stop.load(Ordering::Relaxed) && job.pending.load(Ordering::Relaxed) > 0 {
+        if !prov.is_healthy() || prov.breaker_is_banned() || prov.is_drained() || prov.is_off_schedule() {
+            return;
+        }
+        let Some((block, tries)) = job.queue.lock().pop_front() else {
+            // the rest is in flight elsewhere; a failed block may still come back
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            continue;
+        };
+        if !budget.try_take(1.0) {
+            job.queue.lock().push_front((block, tries));
+            tokio::time::sleep(token_wait(budget.snapshot())).await;
+            continue;
+        }
+        if !prov.try_consume_token() {
+            job.queue.lock().push_front((block, tries));
+            tokio::time::sleep(token_wait(prov.bucket_snapshot())).await;
+            continue;
+        }
+        prov.call_count.fetch_add(1, Ordering::Relaxed);
+        let payload = JsonRpcRequest::new(&job.method, with_block(&job.params, block)).to_value();
+        let res = upstream::call(&prov, &payload, rc.upstream_timeout(), rc.response_limit()).await;
+        let give_up = tries + 1 >= rc.max_provider_tries.max(1);
+        let outcome = match (relay::account_leg(&prov, &res, &snap, &job.method, false), res) {
+            (Leg::Success | Leg::Benign, Ok(mut v)) => Ok(v.get_mut("result").map(Value::take).unwrap_or(Value::Null)),
+            (Leg::Terminal, Ok(v)) => Err(v["error"].clone()),
+            (Leg::NonBreaking(..), Ok(v)) if give_up => Err(v["error"].clone()),
+            (Leg::Failed(_, detail), _) if give_up => {
+                let summary = format!("All {} attempts failed: {}", tries + 1, detail);
+                let (_, Json(reply)) = RelayError::AllAttemptsFailed { summary, attempts: Vec::new() }.into_response(Value::Null);
+                Err(reply["error"].clone())
+            }
+            _ => {
+                // another worker, likely on another provider, picks it up
+                job.queue.lock().push_back((block, tries + 1));
+                continue;
+            }
+        };
+        emit(&job, &tx, block, outcome).await;
+    }
+}
+
+async fn emit(job: &Job, tx: &mpsc::Sender<String>, block: u64, outcome: Result<Value, Value>) {
+    let line = match outcome {
+        Ok(result) => json!({ "block": block, "result": result }),
+        Err(error) => {
+            job.failed.fetch_add(1, Ordering::Relaxed);
+            json!({ "block": block, "error": error })
+        }
+    };
+    job.pending.fetch_sub(1, Ordering::Relaxed);
+    if tx.send(format!("{}\n", line)).await.is_err() {
+        job.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+// Until a bucket, given by its snapshot, has a whole token again.
+fn token_wait(bucket: Option<(f64, f64, f64)>) -> Duration {
+    let secs = match bucket {
+        Some((rate, tokens, _)) if rate > 0.0 => (1.0 - tokens).max(0.0) / rate,
+        _ => 0.0,
+    };
+    Duration::from_secs_f64(secs.clamp(0.005, 1.0))
+}
+
+fn mentions_block(v: &Value) -> bool {
+    match v {
+        Value::String(s) => s == BLOCK,
+        Value::Array(items) => items.iter().any(mentions_block),
+        Value::Object(obj) => obj.values().any(mentions_block),
+        _ => false,
+    }
+}
+
+fn with_block(v: &Value, block: u64) -> Value {
+    match v {
+        Value::String(s) if s == BLOCK => Value::String(format!("0x{:x}", block)),
+        Value::Array(items) => Value::Array(items.iter().map(|i| with_block(i, block)).collect()),
+        Value::Object(obj) => Value::Object(obj.iter().map(|(k, i)| (k.clone(), with_block(i, block))).collect()),
+        other => other.clone(),
+    }
+}
+
+fn bad_request(msg: String) -> Response {
+    (StatusCode::BAD_REQUEST, Json(json!({ "error": msg }))).into_response()
+}
@@ -0,0 +1,107 @@
+//! `bench`: micro-benchmarks for hot-path primitives, run as a subcommand of the release
+//! binary (`cargo run --release -- bench [threads...] [--secs N]`).
+
+use crate::token_bucket::TokenBucket;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Barrier,
+};
+use std::time::{Duration, Instant};
+
+const DEFAULT_THREADS: [usize; 5] = [1, 4, 16, 64, 256];
+
+/// Token-bucket throughput at rising thread counts, lock-free bucket vs the mutex-guarded
+/// one it replaced. The rate is high enough that the takes succeed, as on a busy provider
+/// with headroom. Returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let mut threads = Vec::new();
+    let mut secs = 1.0;
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        match (a.as_str(), a.parse::<usize>()) {
+            ("--secs", _) => match it.next().and_then(|v| v.parse::<f64>().ok()) {
+                Some(s) if s > 0.0 => secs = s,
+                _ => return usage(),
+            },
+            (_, Ok(n)) if n > 0 => threads.push(n),
+            _ => return usage(),
+        }
+    }
+    if threads.is_empty() {
+        threads = DEFAULT_THREADS.to_vec();
+    }
+    let run_for = Duration::from_secs_f64(secs);
+    println!("token bucket try_take, {:.1}s per run", secs);
+    println!("{:>8} {:>16} {:>16} {:>8}", "threads", "mutex ops/s", "atomic ops/s", "speedup");
+    for n in threads {
+        let locked = Arc::new(parking_lot::Mutex::new(MutexBucket::new(1e9)));
+        let mutex_rate = measure(n, run_for, move || locked.lock().try_take(1.0));
+        let bucket = Arc::new(TokenBucket::new(1_000_000_000));
+        let atomic_rate = measure(n, run_for, move || bucket.try_take(1.0));
+        println!("{:>8} {:>16.0} {:>16.0} {:>7.1}x", n, mutex_rate, atomic_rate, atomic_rate / mutex_rate.max(1.0));
+    }
+    0
+}
+
+fn usage() -> i32 {
+    eprintln!("usage: bench [threads...] [--secs N]");
+    2
+}
+
+// Calls of `op` per second across `threads` threads started together.
+fn measure(threads: usize, run_for: Duration, op: impl Fn() -> bool + Clone + Send + 'static) -> f64 {
+    let stop = Arc::new(AtomicBool::new(false));
+    let total = Arc::new(AtomicU64::new(0));
+    let start = Arc::new(Barrier::new(threads + 1));
+    let handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let (op, stop, total, start) = (op.clone(), stop.clone(), total.clone(), start.clone());
+            std::thread::spawn(move || {
+                start.wait();
+                let mut n = 0u64;
+                while !stop.load(Ordering::Relaxed) {
+                    std::hint::black_box(op());
+                    n += 1;
+                }
+                total.fetch_add(n, Ordering::Relaxed);
+            })
+        })
+        .collect();
+    start.wait();
+    let began = Instant::now();
+    std::thread::sleep(run_for);
+    stop.store(true, Ordering::Relaxed);
+    for h in handles {
+        let _ = h.join();
+    }
+    total.load(Ordering::Relaxed) as f64 / began.elapsed().as_secs_f64()
+}
+
+// The previous bucket, kept here as the baseline.
+struct MutexBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last: Instant,
+}
+
+impl MutexBucket {
+    fn new(tps: f64) -> Self {
+        Self { capacity: tps, tokens: tps, refill_per_sec: tps, last: Instant::now() }
+    }
+
+    fn try_take(&mut self, n: f64) -> bool {
+        let now = Instant::now();
+        let dt = now.duration_since(self.last).as_secs_f64();
+        if dt > 0.0 {
+            self.tokens = (self.tokens + dt * self.refill_per_sec).min(self.capacity);
+            self.last = now;
+        }
+        if self.tokens >= n {
+            self.tokens -= n;
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use serde_json::{Map, Value};
+
+// Positional params that are QUANTITY or block tags, per method.
+fn quantity_positions(method: &str) -> &'static [usize] {
+    match method {
+        "eth_getBlockByNumber"
+        | "eth_getBlockTransactionCountByNumber"
+        | "eth_getUncleCountByBlockNumber" => &[0],
+        "eth_getTransactionByBlockNumberAndIndex" | "eth_getUncleByBlockNumberAndIndex" => &[0, 1],
+        "eth_getTransactionByBlockHashAndIndex" | "eth_getUncleByBlockHashAndIndex" => &[1],
+        "eth_getBalance" | "eth_getCode" | "eth_getTransactionCount" | "eth_call" | "eth_estimateGas" => &[1],
+        "eth_getStorageAt" => &[1, 2],
+        "eth_getProof" => &[2],
+        "eth_feeHistory" => &[0, 1],
+        _ => &[],
+    }
+}
+
+/// Block a state read is evaluated at, when it names one explicitly.
+pub enum BlockRef {
+    Number(u64),
+    Hash,
+}
+
+// Position of the trailing block parameter of state reads.
+fn block_position(method: &str) -> Option<usize> {
+    match method {
+        "eth_call" | "eth_getBalance" | "eth_getCode" | "eth_getTransactionCount" | "eth_estimateGas" => Some(1),
+        "eth_getStorageAt" | "eth_getProof" => Some(2),
+        _ => None,
+    }
+}
+
+/// Explicit block of a state read: a hex number, or an EIP-1898 {blockHash}/{blockNumber}
+/// object. Tags (latest, pending, ...) and omitted blocks give None.
+pub fn explicit_block(method: &str, params: &Value) -> Option<BlockRef> {
+    let block = params.get(block_position(method)?)?;
+    let number = |v: &Value| v.as_str()?.strip_prefix("0x").and_then(|h| u64::from_str_radix(h, 16).ok());
+    match block {
+        Value::String(_) => number(block).map(BlockRef::Number),
+        Value::Object(o) if o.contains_key("blockHash") => Some(BlockRef::Hash),
+        Value::Object(o) => o.get("blockNumber").and_then(number).map(BlockRef::Number),
+        _ => None,
+    }
+}
+
+// Object fields that are QUANTITY (call objects, log filters).
+const QUANTITY_KEYS: &[&str] = &[
+    "fromBlock", "toBlock", "blockNumber", "gas", "gasPrice", "maxFeePerGas",
+    "maxPriorityFeePerGas", "value", "nonce", "chainId", "type",
+];
+
+/// Params encoded so that semantically identical requests produce the same
+/// string: hex is lowercased, QUANTITY values lose leading zeros and object
+/// keys are sorted.
+pub fn canonical_params(method: &str, params: &Value) -> String {
+    let normalized = match params {
+        Value::Array(items) => {
+            let positions = quantity_positions(method);
+            Value::Array(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| normalize(v, positions.contains(&i)))
+                    .collect(),
+            )
+        }
+        other => normalize(other, false),
+    };
+    normalized.to_string()
+}
+
+fn normalize(v: &Value, quantity: bool) -> Value {
+    match v {
+        Value::String(s) => Value::String(normalize_str(s, quantity)),
+        Value::Array(items) => Value::Array(items.iter().map(|i| normalize(i, false)).collect()),
+        Value::Object(obj) => {
+            let mut keys: Vec<&String> = obj.keys().collect();
+            keys.sort();
+            let mut out = Map::new();
+            for k in keys {
+                out.insert(k.clone(), normalize(&obj[k], QUANTITY_KEYS.contains(&k.as_str())));
+            }
+            Value::Object(out)
+        }
+        other => other.clone(),
+    }
+}
+
+fn normalize_str(s: &str, quantity: bool) -> String {
+    let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) else { return s.to_string() };
+    if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return s.to_string();
+    }
+    let hex = hex.to_ascii_lowercase();
+    if quantity {
+        let trimmed = hex.trim_start_matches('0');
+        return format!("0x{}", if trimmed.is_empty() { "0" } else { trimmed });
+    }
+    format!("0x{}", hex)
+}
@@ -0,0 +1,87 @@
+use crate::config::CacheShadowConfig;
+use crate::jsonrpc::JsonRpcRequest;
+use crate::relay::{self, HttpState};
+use crate::upstream;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::{debug, warn};
+
+/// Cache hits re-fetched upstream (cache_shadow) and how many live answers differed.
+#[derive(Default)]
+pub struct CacheShadow {
+    hits_seen: AtomicU64,
+    failed: AtomicU64, // sampled, but no provider had a token or none gave a usable answer
+    per_method: parking_lot::Mutex<HashMap<String, (u64, u64)>>, // (checked, diverged)
+}
+
+impl CacheShadow {
+    // Spreads `rate` evenly over hits (every 1/rate-th one), so no RNG is needed.
+    fn sample(&self, rate: f64) -> bool {
+        let rate = rate.clamp(0.0, 1.0);
+        let n = self.hits_seen.fetch_add(1, Ordering::Relaxed) as f64;
+        ((n + 1.0) * rate).floor() > (n * rate).floor()
+    }
+
+    fn record(&self, method: &str, diverged: bool) {
+        let mut per_method = self.per_method.lock();
+        let counts = per_method.entry(method.to_string()).or_default();
+        counts.0 += 1;
+        if diverged { counts.1 += 1 }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let per_method = self.per_method.lock();
+        let (checked, diverged) = per_method.values().fold((0, 0), |(c, d), &(mc, md)| (c + mc, d + md));
+        let methods: Map<String, Value> = per_method
+            .iter()
+            .map(|(m, &(c, d))| (m.clone(), json!({ "checked": c, "diverged": d })))
+            .collect();
+        json!({
+            "checked": checked,
+            "diverged": diverged,
+            "failed": self.failed.load(Ordering::Relaxed),
+            "methods": methods,
+        })
+    }
+}
+
+/// Called on every cache hit: for a sample_rate share of them, ask the fastest healthy
+/// provider in the background and compare its result with the `cached` reply.
+/// The client is answered from the cache either way.
+pub fn on_hit(state: &HttpState, cfg: &CacheShadowConfig, method: &str, params: &Value, cached: &Value) {
+    let app = state.app.clone();
+    if !app.cache_shadow.sample(cfg.sample_rate) {
+        return;
+    }
+    let snap = app.snapshot();
+    let cands = relay::healthy_candidates(&snap.registry, snap.cfg.relay.weight_decay.as_ref());
+    let Some(prov) = relay::fastest_with_tokens(&cands, 1).pop() else {
+        app.cache_shadow.failed.fetch_add(1, Ordering::Relaxed);
+        return;
+    };
+    let payload = JsonRpcRequest::new(method, params.clone()).to_value();
+    let (method, params, cached) = (method.to_string(), params.clone(), cached.get("result").cloned());
+    tokio::spawn(async move {
+        let rc = &snap.cfg.relay;
+        // Kept out of the provider's call and error counters, like health probes.
+        let live = match upstream::call(&prov, &payload, rc.upstream_timeout(), rc.response_limit()).await {
+            Ok(v) if v.get("error").is_none() => v,
+            Ok(v) => {
+                debug!(provider = %prov.label(), method, error = %v["error"], "cache shadow check got an error reply");
+                app.cache_shadow.failed.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            Err(e) => {
+                debug!(provider = %prov.label(), method, error = %e, "cache shadow check failed");
+                app.cache_shadow.failed.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
+        let diverged = live.get("result") != cached.as_ref();
+        app.cache_shadow.record(&method, diverged);
+        if diverged {
+            warn!(provider = %prov.label(), method, params = %params, "cached reply differs from the live upstream answer");
+        }
+    });
+}
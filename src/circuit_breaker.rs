@@ -1,41 +1,190 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use crate::timing::epoch_secs;
+use std::time::{Duration, Instant};
 
 pub struct BreakerConfig {
     pub ban_error_threshold: u32,
     pub ban_seconds: u64,
+    pub auth_failure_threshold: u32, // consecutive auth failures before "misconfigured"; 0 = never
+    pub auth_reprobe_s: u64,         // how often a misconfigured provider's probe may clear it; 0 = never
+    pub failover_statuses: Vec<u16>,  // HTTP statuses that never count toward a ban
+    pub ban_statuses: Vec<u16>,       // HTTP statuses that ban on the spot
 }
 
-#[derive(Debug)]
+impl From<&crate::config::RelayConfig> for BreakerConfig {
+    fn from(rc: &crate::config::RelayConfig) -> Self {
+        Self {
+            ban_error_threshold: rc.ban_error_threshold,
+            ban_seconds: rc.ban_seconds,
+            auth_failure_threshold: rc.auth_failure_threshold,
+            auth_reprobe_s: rc.auth_reprobe_s,
+            failover_statuses: rc.failover_statuses.clone(),
+            ban_statuses: rc.ban_statuses.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct CircuitBreaker {
     fail_streak: u32,
-    banned_until_epoch: u64, // seconds since epoch
+    banned_until: Option<Instant>, // monotonic, so wall-clock steps neither stretch nor cut a ban
+    manual: Option<ManualBan>,
+    auth_streak: u32,
+    misconfigured_since: Option<u64>, // epoch seconds; excluded until cleared by reload, unban or a re-probe
+    reprobe_at: Option<Instant>,       // next health probe allowed to clear misconfigured_since
+}
+
+/// Operator-initiated ban; independent of (and overriding) the automatic one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManualBan {
+    pub reason: String,
+    pub until: Option<Instant>, // None => until lifted; monotonic like banned_until
+    pub source: BanSource,
+}
+
+impl ManualBan {
+    /// Deadline `secs` from now; None (until lifted) past what Instant can represent.
+    pub fn deadline_in(secs: u64) -> Option<Instant> { Instant::now().checked_add(Duration::from_secs(secs)) }
+
+    /// Deadline for a wall-clock end time such as ban.until in the config; a past one has expired.
+    pub fn deadline_at_epoch(epoch: u64) -> Option<Instant> { Self::deadline_in(epoch.saturating_sub(epoch_secs())) }
+
+    /// Approximate wall-clock end in epoch seconds, for display.
+    pub fn until_epoch(&self) -> Option<u64> { self.until.map(display_epoch) }
 }
 
-impl Default for CircuitBreaker {
-    fn default() -> Self {
-        Self { fail_streak: 0, banned_until_epoch: 0 }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BanSource {
+    Config,
+    Admin,
+}
+
+impl BanSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BanSource::Config => "config",
+            BanSource::Admin => "admin",
+        }
     }
 }
 
 impl CircuitBreaker {
     pub fn is_banned(&self) -> bool {
-        let now = now_epoch();
-        now < self.banned_until_epoch
+        self.auto_banned() || self.manual_ban().is_some() || self.misconfigured_since.is_some()
+    }
+
+    /// The active manual ban, if any (an expired one reads as none).
+    pub fn manual_ban(&self) -> Option<&ManualBan> {
+        let now = Instant::now();
+        self.manual.as_ref().filter(|b| b.until.is_none_or(|t| now < t))
+    }
+
+    pub fn set_manual_ban(&mut self, ban: Option<ManualBan>) { self.manual = ban; }
+
+    pub fn on_success(&mut self) {
+        self.fail_streak = 0;
+        self.auth_streak = 0;
+    }
+
+    /// Returns true when this auth failure marked the provider misconfigured.
+    pub fn on_auth_failure(&mut self, cfg: &BreakerConfig) -> bool {
+        self.auth_streak = self.auth_streak.saturating_add(1);
+        if cfg.auth_failure_threshold == 0 || self.auth_streak < cfg.auth_failure_threshold || self.misconfigured_since.is_some() {
+            return false;
+        }
+        self.misconfigured_since = Some(epoch_secs());
+        self.reprobe_at = Some(Instant::now() + Duration::from_secs(cfg.auth_reprobe_s));
+        true
+    }
+
+    /// Whether the next health probe gets to clear the misconfigured state; each
+    /// re-probe pushes the following one auth_reprobe_s out.
+    pub fn reprobe_due(&mut self, cfg: &BreakerConfig) -> bool {
+        let now = Instant::now();
+        if self.misconfigured_since.is_none() || cfg.auth_reprobe_s == 0 || self.reprobe_at.is_some_and(|t| now < t) {
+            return false;
+        }
+        self.reprobe_at = Some(now + Duration::from_secs(cfg.auth_reprobe_s));
+        true
     }
 
-    pub fn on_success(&mut self) { self.fail_streak = 0; }
+    pub fn misconfigured_since(&self) -> Option<u64> { self.misconfigured_since }
 
-    pub fn on_failure(&mut self, cfg: &BreakerConfig) {
+    /// Returns true if the provider was misconfigured.
+    pub fn clear_misconfigured(&mut self) -> bool {
+        self.auth_streak = 0;
+        self.reprobe_at = None;
+        self.misconfigured_since.take().is_some()
+    }
+
+    /// Returns true when this failure started a new ban.
+    pub fn on_failure(&mut self, cfg: &BreakerConfig) -> bool {
         self.fail_streak = self.fail_streak.saturating_add(1);
         if self.fail_streak >= cfg.ban_error_threshold {
-            self.banned_until_epoch = now_epoch().saturating_add(cfg.ban_seconds);
+            self.banned_until = Some(Instant::now() + Duration::from_secs(cfg.ban_seconds));
             self.fail_streak = 0;
+            return true;
         }
+        false
     }
 
-    pub fn banned_until(&self) -> u64 { self.banned_until_epoch }
+    /// Ban for ban_seconds regardless of the streak; returns true unless already banned.
+    pub fn ban_now(&mut self, cfg: &BreakerConfig) -> bool {
+        let fresh = !self.auto_banned();
+        let until = Instant::now() + Duration::from_secs(cfg.ban_seconds);
+        self.banned_until = self.banned_until.max(Some(until));
+        self.fail_streak = 0;
+        fresh
+    }
+
+    fn auto_banned(&self) -> bool { self.banned_until.is_some_and(|t| Instant::now() < t) }
+
+    /// Approximate wall-clock end of the automatic ban in epoch seconds, for display; 0 when not banned.
+    pub fn banned_until(&self) -> u64 {
+        self.banned_until.filter(|&t| Instant::now() < t).map_or(0, display_epoch)
+    }
+}
+
+// Wall-clock reading of a monotonic deadline, rounded up to the second.
+fn display_epoch(t: Instant) -> u64 {
+    epoch_secs().saturating_add(t.saturating_duration_since(Instant::now()).as_secs_f64().ceil() as u64)
 }
 
-fn now_epoch() -> u64 {
-    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg(auth_reprobe_s: u64) -> BreakerConfig {
+        BreakerConfig { ban_error_threshold: 3, ban_seconds: 60, auth_failure_threshold: 2, auth_reprobe_s, failover_statuses: vec![], ban_statuses: vec![] }
+    }
+
+    #[test]
+    fn manual_ban_runs_on_a_monotonic_deadline() {
+        let mut b = CircuitBreaker::default();
+        let ban = |until| ManualBan { reason: "test".into(), until, source: BanSource::Admin };
+        b.set_manual_ban(Some(ban(ManualBan::deadline_in(60))));
+        let shown = b.manual_ban().and_then(ManualBan::until_epoch).unwrap();
+        assert!((epoch_secs() + 59..=epoch_secs() + 61).contains(&shown));
+        b.set_manual_ban(Some(ban(ManualBan::deadline_at_epoch(epoch_secs().saturating_sub(10)))));
+        assert!(b.manual_ban().is_none(), "a past end time has expired");
+        assert_eq!(ManualBan::deadline_in(u64::MAX), None);
+        b.set_manual_ban(Some(ban(None)));
+        assert!(b.is_banned());
+    }
+
+    #[test]
+    fn reprobe_waits_for_the_interval() {
+        let mut b = CircuitBreaker::default();
+        assert!(!b.reprobe_due(&cfg(0)));
+        assert!(!b.on_auth_failure(&cfg(600)));
+        assert!(b.on_auth_failure(&cfg(600)));
+        assert!(b.is_banned());
+        assert!(!b.reprobe_due(&cfg(600)));
+        // 0 turns re-probing off even once the deadline has passed
+        b.reprobe_at = Some(Instant::now());
+        assert!(!b.reprobe_due(&cfg(0)));
+        assert!(b.reprobe_due(&cfg(600)));
+        assert!(!b.reprobe_due(&cfg(600)), "the next one is pushed out");
+        assert!(b.clear_misconfigured());
+        assert!(!b.reprobe_due(&cfg(600)));
+    }
 }
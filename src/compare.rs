@@ -0,0 +1,249 @@
+use crate::config::Config;
+use crate::jsonrpc::{self, JsonRpcRequest};
+use crate::state::{build_registry, ProviderState};
+use crate::upstream;
+use futures::future::join_all;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// `relay compare [--json] [--rounds N]`: run a fixed suite of calls against every
+/// configured provider and print latency, agreement with the majority answer and
+/// method support, as markdown (default) or JSON.
+pub async fn run(cfg: &Config, args: &[String]) -> i32 {
+    let json_out = args.iter().any(|a| a == "--json");
+    let rounds = args
+        .iter()
+        .position(|a| a == "--rounds")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|n| n.parse::<usize>().ok())
+        .unwrap_or(5)
+        .max(1);
+
+    let reg = build_registry(&cfg.rpc_endpoints, &cfg.http_client, &cfg.relay.default_headers);
+    let providers = reg.all();
+    if providers.is_empty() {
+        eprintln!("compare: no providers configured");
+        return 1;
+    }
+    let limits = (cfg.relay.upstream_timeout(), cfg.relay.response_limit());
+    let mut reports: Vec<Report> = providers.iter().map(|p| Report::new(p.label())).collect();
+
+    // Latency and head: repeated eth_blockNumber
+    for _ in 0..rounds {
+        let res = call_all(&providers, "eth_blockNumber", json!([]), limits).await;
+        for (r, (out, ms)) in reports.iter_mut().zip(res) {
+            if let Some(head) = out.ok().as_ref().and_then(Value::as_str).and_then(jsonrpc::parse_quantity) {
+                r.latencies.push(ms);
+                r.head = r.head.max(head);
+            }
+        }
+    }
+    let best_head = reports.iter().map(|r| r.head).max().unwrap_or(0);
+    // A block every reachable provider should have, so answers are comparable.
+    let common = reports.iter().map(|r| r.head).filter(|&h| h > 0).min().unwrap_or(0).saturating_sub(5);
+    let at = format!("0x{:x}", common);
+    let zero = "0x0000000000000000000000000000000000000000";
+
+    let checks: Vec<(&str, &str, Value)> = vec![
+        ("chainId", "eth_chainId", json!([])),
+        ("block", "eth_getBlockByNumber", json!([at, false])),
+        ("balance", "eth_getBalance", json!([zero, at])),
+        ("call", "eth_call", json!([{ "to": zero, "data": "0x" }, at])),
+        ("logs_1", "eth_getLogs", logs_range(common, 1)),
+        ("logs_100", "eth_getLogs", logs_range(common, 100)),
+        ("logs_1000", "eth_getLogs", logs_range(common, 1000)),
+    ];
+    let mut check_names = Vec::new();
+    for (name, method, params) in checks {
+        check_names.push(name);
+        let res = call_all(&providers, method, params, limits).await;
+        // Majority answer among providers that answered at all
+        let mut votes: HashMap<String, usize> = HashMap::new();
+        for (out, _) in &res {
+            if let Ok(v) = out { *votes.entry(v.to_string()).or_default() += 1; }
+        }
+        let majority = votes.into_iter().max_by_key(|(_, n)| *n).map(|(v, _)| v);
+        for (r, (out, ms)) in reports.iter_mut().zip(res) {
+            let outcome = match out {
+                Ok(v) if Some(v.to_string()) == majority => CheckOutcome::Agrees(ms),
+                Ok(_) => CheckOutcome::Differs(ms),
+                Err(e) => CheckOutcome::Failed(e),
+            };
+            r.checks.push((name, outcome));
+        }
+    }
+
+    // Method support: anything but "method not found"/unsupported counts as supported
+    let probes: Vec<(&str, Value)> = vec![
+        ("eth_feeHistory", json!(["0x4", "latest", [25, 75]])),
+        ("eth_maxPriorityFeePerGas", json!([])),
+        ("eth_getProof", json!([zero, [], at])),
+        ("eth_getBlockReceipts", json!([at])),
+        ("debug_traceBlockByNumber", json!([at, { "tracer": "callTracer" }])),
+        ("trace_block", json!([at])),
+        ("txpool_status", json!([])),
+        ("web3_clientVersion", json!([])),
+    ];
+    let mut method_names = Vec::new();
+    for (method, params) in probes {
+        method_names.push(method);
+        let res = call_all(&providers, method, params, limits).await;
+        for (r, (out, _)) in reports.iter_mut().zip(res) {
+            let supported = match &out {
+                Ok(_) => true,
+                Err(e) => !is_unsupported(e),
+            };
+            r.methods.push((method, supported));
+        }
+    }
+
+    let out = if json_out {
+        serde_json::to_string_pretty(&to_json(&reports, best_head)).unwrap_or_default()
+    } else {
+        to_markdown(&reports, best_head, &check_names, &method_names, rounds)
+    };
+    println!("{}", out);
+    0
+}
+
+struct Report {
+    label: String,
+    latencies: Vec<u64>,
+    head: u64,
+    checks: Vec<(&'static str, CheckOutcome)>,
+    methods: Vec<(&'static str, bool)>,
+}
+
+enum CheckOutcome {
+    Agrees(u64),
+    Differs(u64),
+    Failed(String),
+}
+
+impl Report {
+    fn new(label: String) -> Self {
+        Self { label, latencies: Vec::new(), head: 0, checks: Vec::new(), methods: Vec::new() }
+    }
+
+    fn latency(&self) -> Option<(u64, u64)> {
+        let mut l = self.latencies.clone();
+        l.sort_unstable();
+        Some((*l.get(l.len() / 2)?, *l.last()?))
+    }
+
+    fn agreement(&self) -> (usize, usize) {
+        let agrees = self.checks.iter().filter(|(_, c)| matches!(c, CheckOutcome::Agrees(_))).count();
+        (agrees, self.checks.len())
+    }
+}
+
+/// One call per provider, concurrently; `(result or error text, latency ms)` in provider order.
+async fn call_all(
+    providers: &[Arc<ProviderState>],
+    method: &str,
+    params: Value,
+    (timeout, max_bytes): (Duration, usize),
+) -> Vec<(Result<Value, String>, u64)> {
+    let payload = JsonRpcRequest::new(method, params).to_value();
+    join_all(providers.iter().map(|p| {
+        let payload = &payload;
+        async move {
+            let started = Instant::now();
+            let res = match upstream::call(p, payload, timeout, max_bytes).await {
+                Ok(mut v) => match v.get("error") {
+                    Some(e) => Err(e.to_string()),
+                    None => Ok(v.get_mut("result").map(Value::take).unwrap_or(Value::Null)),
+                },
+                Err(e) => Err(e.to_string()),
+            };
+            (res, started.elapsed().as_millis() as u64)
+        }
+    }))
+    .await
+}
+
+fn logs_range(to: u64, span: u64) -> Value {
+    json!([{ "fromBlock": format!("0x{:x}", to.saturating_sub(span - 1)), "toBlock": format!("0x{:x}", to) }])
+}
+
+fn is_unsupported(err: &str) -> bool {
+    let e = err.to_ascii_lowercase();
+    e.contains("-32601") || e.contains("not found") || e.contains("not supported") || e.contains("does not exist") || e.contains("not available")
+}
+
+fn to_json(reports: &[Report], best_head: u64) -> Value {
+    let providers: Vec<Value> = reports
+        .iter()
+        .map(|r| {
+            let checks: serde_json::Map<String, Value> = r
+                .checks
+                .iter()
+                .map(|(name, c)| {
+                    let v = match c {
+                        CheckOutcome::Agrees(ms) => json!({ "ok": true, "agrees": true, "latency_ms": ms }),
+                        CheckOutcome::Differs(ms) => json!({ "ok": true, "agrees": false, "latency_ms": ms }),
+                        CheckOutcome::Failed(e) => json!({ "ok": false, "error": e }),
+                    };
+                    (name.to_string(), v)
+                })
+                .collect();
+            let methods: serde_json::Map<String, Value> =
+                r.methods.iter().map(|(m, ok)| (m.to_string(), Value::Bool(*ok))).collect();
+            let (agrees, total) = r.agreement();
+            json!({
+                "provider": r.label,
+                "latency_ms": r.latency().map(|(p50, max)| json!({ "p50": p50, "max": max })),
+                "head": r.head,
+                "behind": best_head.saturating_sub(r.head),
+                "agreement": format!("{}/{}", agrees, total),
+                "checks": checks,
+                "methods": methods,
+            })
+        })
+        .collect();
+    json!({ "best_head": best_head, "providers": providers })
+}
+
+fn to_markdown(reports: &[Report], best_head: u64, checks: &[&str], methods: &[&str], rounds: usize) -> String {
+    let mut f = String::new();
+    let _ = writeln!(f, "# Provider comparison\n");
+    let _ = writeln!(f, "Best head: {} · eth_blockNumber rounds: {}\n", best_head, rounds);
+    let _ = writeln!(f, "| provider | p50 ms | max ms | behind | agreement | unsupported |");
+    let _ = writeln!(f, "|---|---:|---:|---:|---:|---|");
+    for r in reports {
+        let (p50, max) = r.latency().map_or(("–".to_string(), "–".to_string()), |(a, b)| (a.to_string(), b.to_string()));
+        let behind = if r.head == 0 { "–".to_string() } else { best_head.saturating_sub(r.head).to_string() };
+        let (agrees, total) = r.agreement();
+        let unsupported: Vec<&str> = r.methods.iter().filter(|(_, ok)| !ok).map(|(m, _)| *m).collect();
+        let unsupported = if unsupported.is_empty() { "–".to_string() } else { unsupported.join(", ") };
+        let _ = writeln!(f, "| {} | {} | {} | {} | {}/{} | {} |", r.label, p50, max, behind, agrees, total, unsupported);
+    }
+
+    let _ = writeln!(f, "\n## Checks (✔ agrees with majority, ≠ differs, ✘ failed; ms)\n");
+    let _ = writeln!(f, "| provider | {} |", checks.join(" | "));
+    let _ = writeln!(f, "|---|{}", "---|".repeat(checks.len()));
+    for r in reports {
+        let cells: Vec<String> = r
+            .checks
+            .iter()
+            .map(|(_, c)| match c {
+                CheckOutcome::Agrees(ms) => format!("✔ {}", ms),
+                CheckOutcome::Differs(ms) => format!("≠ {}", ms),
+                CheckOutcome::Failed(e) => format!("✘ {}", e.chars().take(40).collect::<String>().replace('|', "/")),
+            })
+            .collect();
+        let _ = writeln!(f, "| {} | {} |", r.label, cells.join(" | "));
+    }
+
+    let _ = writeln!(f, "\n## Method support\n");
+    let _ = writeln!(f, "| provider | {} |", methods.join(" | "));
+    let _ = writeln!(f, "|---|{}", "---|".repeat(methods.len()));
+    for r in reports {
+        let cells: Vec<&str> = r.methods.iter().map(|(_, ok)| if *ok { "yes" } else { "no" }).collect();
+        let _ = writeln!(f, "| {} | {} |", r.label, cells.join(" | "));
+    }
+    f
+}
@@ -1,5 +1,12 @@
+use crate::schedule::ScheduleConfig;
+use regex_automata::{meta::Regex, util::syntax};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, env, fs, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Config {
@@ -9,7 +16,43 @@ pub struct Config {
     #[serde(default)]
     pub health_monitor: HealthMonitorConfig,
     #[serde(default)]
-    pub cache_ttl: HashMap<String, u64>, // per-method TTL in milliseconds
+    pub cache_ttl: HashMap<String, u64>, // method, or "method_glob[:params_glob]" -> TTL in milliseconds
+    #[serde(default)]
+    pub cache_ttl_default: u64,          // methods no cache_ttl key matches (never broadcast methods)
+    #[serde(default)]
+    pub cache_error_ttl: HashMap<String, u64>, // same keys as cache_ttl -> TTL for provider error replies
+    #[serde(default)]
+    pub negative_cache: Option<NegativeCacheConfig>,
+    #[serde(default)]
+    pub pinned_cache: Option<PinnedCacheConfig>,
+    #[serde(default)]
+    pub broadcast_preflight: Option<BroadcastPreflightConfig>, // check raw transactions locally before broadcasting
+    #[serde(default)]
+    pub tx_tracking: Option<TxTrackingConfig>, // remember broadcasts and link same-nonce replacements
+    #[serde(default)]
+    pub origin_stats: Option<OriginStatsConfig>, // per-caller counters from X-RLY-Origin
+    #[serde(default)]
+    pub call_overrides: Option<CallOverridesConfig>, // X-RLY-Max-Tries / X-RLY-Timeout-Ms per request
+    #[serde(default)]
+    pub cache_ttl_scaling: Option<CacheTtlScalingConfig>, // longer TTLs while the provider pool is strained
+    #[serde(default)]
+    pub cache_shadow: Option<CacheShadowConfig>,
+    #[serde(default)]
+    pub logs_pagination: Option<LogsPaginationConfig>, // enables rly_getLogsPaged
+    #[serde(default)]
+    pub backfill: Option<BackfillConfig>, // enables POST /backfill
+    #[serde(default)]
+    pub memory_guard: Option<MemoryGuardConfig>, // shed load and shrink the cache near an RSS limit
+    #[serde(default)]
+    pub http_client: HttpClientConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub error_reporting: ErrorReportingConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub ui: UiConfig,
     pub rpc_endpoints: RpcEndpoints,
 }
 
@@ -19,19 +62,377 @@ pub struct ServerConfig {
     pub port: u16,         // e.g., 5588
     #[serde(default = "default_request_timeout_ms")]
     pub request_timeout_ms: u64,
+    #[serde(default)]
+    pub auth: Option<AuthConfig>, // protects /status and admin routes when set
 }
 fn default_request_timeout_ms() -> u64 { 30_000 }
 
+/// Either (or both) credentials may be configured; any match is accepted.
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub struct AuthConfig {
+    #[serde(default)]
+    pub bearer_token: Option<String>,
+    #[serde(default)]
+    pub basic_username: Option<String>,
+    #[serde(default)]
+    pub basic_password: Option<String>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub struct HealthMonitorConfig {
     #[serde(default = "default_max_blocks_behind")]
     pub max_blocks_behind: u64,
     #[serde(default = "default_monitor_interval_s")]
     pub monitor_interval_s: u64,
+    #[serde(default)]
+    pub degradation: Option<DegradationConfig>, // None => thresholds never loosen
+    #[serde(default)]
+    pub lag_weighting: bool, // scale selection weight from 1 at 0 behind down to 0 at max_blocks_behind
+    #[serde(default)]
+    pub probe_tokens: ProbeTokens,
+    #[serde(default)]
+    pub archive_probe: Option<ArchiveProbeConfig>, // None => no depth probing, no historical routing
+    #[serde(default)]
+    pub retired_retention_s: Option<u64>, // keep providers dropped by a reload this long; None => 3600
 }
 fn default_max_blocks_behind() -> u64 { 6 }
+
+impl HealthMonitorConfig {
+    pub fn retired_retention(&self) -> Duration { Duration::from_secs(self.retired_retention_s.unwrap_or(3_600)) }
+}
+
+/// Periodic eth_getBalance probes at old blocks to learn how much state each provider keeps.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArchiveProbeConfig {
+    #[serde(default = "default_archive_probe_interval_s")]
+    pub interval_s: u64,
+    #[serde(default = "default_archive_probe_depths")]
+    pub depths: Vec<u64>, // blocks behind the head to try after block 1 fails, deepest first
+}
+fn default_archive_probe_interval_s() -> u64 { 3_600 }
+fn default_archive_probe_depths() -> Vec<u64> { vec![1_000_000, 100_000, 10_000, 1_024, 128] }
+
+/// Whether health probes draw on the provider's max_tps budget.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeTokens {
+    #[default]
+    Exempt,    // probes are free
+    Accounted, // each probe is charged a token, even if that leaves the bucket in debt
+}
 fn default_monitor_interval_s() -> u64 { 5 }
 
+/// Looser thresholds used while too many providers fail the normal ones.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DegradationConfig {
+    #[serde(default = "default_degraded_unhealthy_pct")]
+    pub unhealthy_pct: f64,     // enter degraded mode when more than this share is unhealthy
+    #[serde(default = "default_degraded_max_blocks_behind")]
+    pub max_blocks_behind: u64, // replaces health_monitor.max_blocks_behind while degraded
+    #[serde(default)]
+    pub latency_threshold_ms: Option<u64>, // replaces relay.latency_threshold_ms; None => no latency filter
+}
+fn default_degraded_unhealthy_pct() -> f64 { 50.0 }
+fn default_degraded_max_blocks_behind() -> u64 { 50 }
+
+/// Where and how tracing output is written. Read once at startup.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    #[serde(default)]
+    pub target: LogTarget,
+    #[serde(default = "default_log_path")]
+    pub path: String,
+    #[serde(default)]
+    pub rotation: LogRotation,
+    #[serde(default = "default_log_max_size_mb")]
+    pub max_size_mb: u64,
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+    #[serde(default)]
+    pub format: LogFormat,
+    #[serde(default)]
+    pub slow_request_ms: Option<u64>, // warn about relayed calls slower than this; re-read per call
+}
+fn default_log_path() -> String { "relay.log".to_string() }
+fn default_log_max_size_mb() -> u64 { 100 }
+fn default_log_max_files() -> usize { 5 }
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            target: LogTarget::default(),
+            path: default_log_path(),
+            rotation: LogRotation::default(),
+            max_size_mb: default_log_max_size_mb(),
+            max_files: default_log_max_files(),
+            format: LogFormat::default(),
+            slow_request_ms: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogTarget {
+    #[default]
+    Stdout,
+    File,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    #[default]
+    Never,
+    Size,
+    Daily,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Compact,
+    Json,
+}
+
+/// Terminal dashboard layout; re-read every frame.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UiConfig {
+    #[serde(default)]
+    pub columns: Vec<UiColumnSpec>, // empty => built-in set and order
+    #[serde(default = "default_ui_auto_fit")]
+    pub auto_fit: bool,             // stretch/shrink the URL column and drop trailing columns to fit the terminal
+    #[serde(default = "default_ui_latency_warn_ms")]
+    pub latency_warn_ms: u64,       // latency cell yellow at/above this, green below
+    #[serde(default = "default_ui_latency_crit_ms")]
+    pub latency_crit_ms: u64,       // ... red at/above this
+    #[serde(default)]
+    pub charset: UiCharset,         // frame and symbol glyphs
+    #[serde(default = "default_ui_snapshot_dir")]
+    pub snapshot_dir: PathBuf,      // where snapshot exports are written
+}
+fn default_ui_auto_fit() -> bool { true }
+fn default_ui_snapshot_dir() -> PathBuf { PathBuf::from(".") }
+
+/// Glyphs for the dashboard frame, truncation marks and sparklines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UiCharset {
+    #[default]
+    Auto,    // ascii on legacy Windows consoles and non-UTF-8 locales, else unicode
+    Unicode, // box drawing, '…', '∞', '–', block sparklines
+    Ascii,   // plain ASCII, same widths
+}
+fn default_ui_latency_warn_ms() -> u64 { 100 }
+fn default_ui_latency_crit_ms() -> u64 { 500 }
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            columns: Vec::new(),
+            auto_fit: default_ui_auto_fit(),
+            latency_warn_ms: default_ui_latency_warn_ms(),
+            latency_crit_ms: default_ui_latency_crit_ms(),
+            charset: UiCharset::default(),
+            snapshot_dir: default_ui_snapshot_dir(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UiColumn {
+    Url, Status, Weight, Block, Behind, Latency, Tps, Tpm, Rates, Errors, ErrorRates, OkPct, LastError, Calls, TpsTrend, LatencyTrend, Uptime, Bucket,
+}
+
+impl UiColumn {
+    pub const ALL: [UiColumn; 18] = [
+        UiColumn::Url, UiColumn::Status, UiColumn::Weight, UiColumn::Block, UiColumn::Behind,
+        UiColumn::Latency, UiColumn::Tps, UiColumn::Tpm, UiColumn::Rates, UiColumn::Errors, UiColumn::ErrorRates,
+        UiColumn::OkPct, UiColumn::LastError, UiColumn::Calls, UiColumn::TpsTrend, UiColumn::LatencyTrend,
+        UiColumn::Uptime, UiColumn::Bucket,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            UiColumn::Url => "url",
+            UiColumn::Status => "status",
+            UiColumn::Weight => "weight",
+            UiColumn::Block => "block",
+            UiColumn::Behind => "behind",
+            UiColumn::Latency => "latency",
+            UiColumn::Tps => "tps",
+            UiColumn::Tpm => "tpm",
+            UiColumn::Rates => "rates",
+            UiColumn::Errors => "errors",
+            UiColumn::ErrorRates => "error_rates",
+            UiColumn::OkPct => "ok_pct",
+            UiColumn::LastError => "last_error",
+            UiColumn::Calls => "calls",
+            UiColumn::TpsTrend => "tps_trend",
+            UiColumn::LatencyTrend => "latency_trend",
+            UiColumn::Uptime => "uptime",
+            UiColumn::Bucket => "bucket",
+        }
+    }
+}
+
+/// `name` or `name:width`, e.g. "url:30".
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct UiColumnSpec {
+    pub column: UiColumn,
+    pub width: Option<usize>,
+}
+
+impl TryFrom<String> for UiColumnSpec {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, String> {
+        let (name, width) = match s.split_once(':') {
+            Some((n, w)) => (n, Some(w.trim().parse::<usize>().map_err(|_| format!("bad width in ui column {:?}", s))?)),
+            None => (s.as_str(), None),
+        };
+        let name = name.trim();
+        let column = UiColumn::ALL
+            .into_iter()
+            .find(|c| c.name() == name)
+            .ok_or_else(|| format!("unknown ui column {:?}", name))?;
+        Ok(Self { column, width })
+    }
+}
+
+impl From<UiColumnSpec> for String {
+    fn from(c: UiColumnSpec) -> String {
+        match c.width {
+            Some(w) => format!("{}:{}", c.column.name(), w),
+            None => c.column.name().to_string(),
+        }
+    }
+}
+
+/// Optional push of critical conditions to a webhook and/or Sentry.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ErrorReportingConfig {
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    #[serde(default)]
+    pub sentry_dsn: Option<String>,
+    #[serde(default = "default_report_min_interval_s")]
+    pub min_interval_s: u64, // per event kind
+    #[serde(default = "default_reload_failure_threshold")]
+    pub reload_failure_threshold: u32,
+}
+fn default_report_min_interval_s() -> u64 { 300 }
+fn default_reload_failure_threshold() -> u32 { 3 }
+
+impl Default for ErrorReportingConfig {
+    fn default() -> Self {
+        Self {
+            webhook_url: None,
+            sentry_dsn: None,
+            min_interval_s: default_report_min_interval_s(),
+            reload_failure_threshold: default_reload_failure_threshold(),
+        }
+    }
+}
+
+/// Push-based metrics exporters. Each exporter is enabled by the presence of its section.
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub struct MetricsConfig {
+    #[serde(default)]
+    pub statsd: Option<StatsdConfig>,
+    #[serde(default)]
+    pub influx: Option<InfluxConfig>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StatsdConfig {
+    #[serde(default = "default_statsd_addr")]
+    pub addr: String,
+    #[serde(default = "default_metrics_prefix")]
+    pub prefix: String,
+    #[serde(default = "default_metrics_interval_s")]
+    pub interval_s: u64,
+    #[serde(default)]
+    pub dogstatsd: bool, // emit |#tag:value extensions
+    #[serde(default)]
+    pub tags: Vec<String>, // extra DogStatsD tags, e.g. "env:prod"
+}
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InfluxConfig {
+    pub url: String, // e.g. "http://localhost:8086"
+    #[serde(default)]
+    pub api: InfluxApi,
+    #[serde(default)]
+    pub database: Option<String>, // v1
+    #[serde(default)]
+    pub username: Option<String>, // v1
+    #[serde(default)]
+    pub password: Option<String>, // v1
+    #[serde(default)]
+    pub org: Option<String>,      // v2
+    #[serde(default)]
+    pub bucket: Option<String>,   // v2
+    #[serde(default)]
+    pub token: Option<String>,    // v2
+    #[serde(default = "default_metrics_prefix")]
+    pub measurement_prefix: String,
+    #[serde(default = "default_metrics_interval_s")]
+    pub interval_s: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InfluxApi {
+    #[default]
+    V2,
+    V1,
+}
+
+fn default_statsd_addr() -> String { "127.0.0.1:8125".to_string() }
+fn default_metrics_prefix() -> String { "rpc_relay".to_string() }
+fn default_metrics_interval_s() -> u64 { 10 }
+
+/// Upstream connection pool tuning, applied to every provider's client.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HttpClientConfig {
+    #[serde(default = "default_pool_max_idle_per_host")]
+    pub pool_max_idle_per_host: usize,
+    #[serde(default = "default_pool_idle_timeout_s")]
+    pub pool_idle_timeout_s: u64,
+    #[serde(default = "default_tcp_keepalive_s")]
+    pub tcp_keepalive_s: u64, // 0 => disabled
+    #[serde(default = "default_tcp_nodelay")]
+    pub tcp_nodelay: bool,
+    #[serde(default)]
+    pub http1_only: bool,
+    #[serde(default)]
+    pub connect_timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub connection_max_lifetime_s: u64, // 0 => pooled connections live until idle timeout
+    #[serde(default)]
+    pub dns_refresh_s: u64,             // 0 => never re-resolve provider hosts
+}
+fn default_pool_max_idle_per_host() -> usize { 32 }
+fn default_pool_idle_timeout_s() -> u64 { 90 }
+fn default_tcp_keepalive_s() -> u64 { 30 }
+fn default_tcp_nodelay() -> bool { true }
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            pool_max_idle_per_host: default_pool_max_idle_per_host(),
+            pool_idle_timeout_s: default_pool_idle_timeout_s(),
+            tcp_keepalive_s: default_tcp_keepalive_s(),
+            tcp_nodelay: default_tcp_nodelay(),
+            http1_only: false,
+            connect_timeout_ms: None,
+            connection_max_lifetime_s: 0,
+            dns_refresh_s: 0,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RelayConfig {
     #[serde(default)]
@@ -44,17 +445,434 @@ pub struct RelayConfig {
     pub broadcast_methods: Vec<String>,
     #[serde(default = "default_broadcast_redundancy")]
     pub broadcast_redundancy: usize,
+    #[serde(default)]
+    pub broadcast_targets: BroadcastTargets,
+    #[serde(default)]
+    pub race_methods: Vec<String>,        // read-only methods raced across the fastest providers
+    #[serde(default = "default_race_fanout")]
+    pub race_fanout: usize,
     #[serde(default = "default_ban_error_threshold")]
     pub ban_error_threshold: u32,
     #[serde(default = "default_ban_seconds")]
     pub ban_seconds: u64,
+    #[serde(default = "default_max_response_bytes")]
+    pub max_response_bytes: usize,
+    #[serde(default = "default_broadcast_benign_errors")]
+    pub broadcast_benign_errors: Vec<ErrorPattern>, // "tx already propagated" replies during broadcast
+    #[serde(default)]
+    pub broadcast_dedup_ms: u64,                    // repeat of a just-broadcast raw tx gets the cached reply; 0 = off (default)
+    #[serde(default)]
+    pub broadcast_finish_all: bool,                 // let the other legs complete after the first success
+    #[serde(default)]
+    pub receipt_fanout_ms: u64,                     // after a broadcast, look its tx up on several providers; 0 = off
+    #[serde(default = "default_receipt_fanout")]
+    pub receipt_fanout: usize,
+    #[serde(default = "default_non_breaking_errors")]
+    pub non_breaking_errors: Vec<ErrorPattern>,    // returned to the client, not held against the provider
+    #[serde(default = "default_terminal_errors")]
+    pub terminal_errors: Vec<ErrorPattern>,        // deterministic: no failover, returned as-is
+    #[serde(default)]
+    pub weight_decay: Option<WeightDecayConfig>,   // None => weights are used as configured
+    #[serde(default)]
+    pub pending_nonce: PendingNonceConfig,
+    #[serde(default = "default_auth_errors")]
+    pub auth_errors: Vec<ErrorPattern>,            // JSON-RPC errors meaning our API key was rejected
+    #[serde(default = "default_auth_failure_threshold")]
+    pub auth_failure_threshold: u32,               // consecutive auth failures => misconfigured; 0 = off
+    #[serde(default = "default_auth_reprobe_s")]
+    pub auth_reprobe_s: u64,                       // misconfigured: a passing health probe this often lets it back; 0 = never
+    #[serde(default)]
+    pub failover_statuses: Vec<u16>,               // upstream HTTP statuses failed over without breaker penalty
+    #[serde(default)]
+    pub ban_statuses: Vec<u16>,                    // upstream HTTP statuses that ban for ban_seconds at once
+    #[serde(default)]
+    pub routing: RoutingPolicy,
+    #[serde(default)]
+    pub default_headers: BTreeMap<String, String>, // sent with every upstream request (relay traffic and probes)
+}
+
+/// How the first attempt of a normal call picks among the selectable providers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoutingPolicy {
+    #[default]
+    Weighted, // weighted rotation over everything that passes the filters
+    Cost,     // the cheapest of those by cost_per_million; failover still uses all of them
+}
+
+/// Which providers get the broadcast_redundancy copies of a broadcast.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BroadcastTargets {
+    #[default]
+    Fastest, // the fastest of the selected tier
+    Spread,  // the fastest primary, then alternating tiers for propagation diversity
+}
+
+/// Who gets eth_getTransactionCount rewritten to the "pending" block tag.
+/// An API key entry wins over a sender address entry, which wins over `enabled`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PendingNonceConfig {
+    #[serde(default = "default_pending_nonce_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub api_keys: HashMap<String, bool>,  // X-RLY-Key header value -> rewrite?
+    #[serde(default)]
+    pub addresses: HashMap<String, bool>, // queried address (case-insensitive) -> rewrite?
+}
+fn default_pending_nonce_enabled() -> bool { true }
+
+impl Default for PendingNonceConfig {
+    fn default() -> Self {
+        Self { enabled: true, api_keys: HashMap::new(), addresses: HashMap::new() }
+    }
+}
+
+impl PendingNonceConfig {
+    pub fn applies(&self, api_key: Option<&str>, address: Option<&str>) -> bool {
+        if let Some(v) = api_key.and_then(|k| self.api_keys.get(k)) {
+            return *v;
+        }
+        let by_address = address.and_then(|a| self.addresses.iter().find(|(k, _)| k.eq_ignore_ascii_case(a)));
+        by_address.map_or(self.enabled, |(_, v)| *v)
+    }
+}
+
+/// Scale a provider's selection weight down while its recent error rate is high.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WeightDecayConfig {
+    #[serde(default = "default_decay_error_rate_threshold")]
+    pub error_rate_threshold: f64, // no decay at or below this rate
+    #[serde(default = "default_decay_min_factor")]
+    pub min_factor: f64,           // floor, so a decayed provider still sees some traffic to recover on
+    #[serde(default = "default_decay_half_life_s")]
+    pub half_life_s: u64,          // recent-error memory; also how fast an idle provider recovers
+}
+fn default_decay_error_rate_threshold() -> f64 { 0.05 }
+fn default_decay_min_factor() -> f64 { 0.1 }
+fn default_decay_half_life_s() -> u64 { 60 }
+
+impl WeightDecayConfig {
+    /// Weight multiplier for a given recent error rate.
+    pub fn factor(&self, error_rate: f64) -> f64 {
+        let min = self.min_factor.clamp(0.01, 1.0);
+        if error_rate <= self.error_rate_threshold { 1.0 } else { (self.error_rate_threshold.max(0.0) / error_rate).max(min) }
+    }
 }
 fn default_max_provider_tries() -> u32 { 3 }
 fn default_upstream_timeout_ms() -> u64 { 30_000 }
 fn default_broadcast_methods() -> Vec<String> { vec!["eth_sendRawTransaction".to_string()] }
 fn default_broadcast_redundancy() -> usize { 2 }
+fn default_race_fanout() -> usize { 2 }
+fn default_receipt_fanout() -> usize { 3 }
 fn default_ban_error_threshold() -> u32 { 3 }
 fn default_ban_seconds() -> u64 { 30 }
+fn default_max_response_bytes() -> usize { 32 * 1024 * 1024 }
+fn default_broadcast_benign_errors() -> Vec<ErrorPattern> {
+    patterns(&["already known", "already[ _]exists", "known transaction", "already imported", "nonce too low"])
+}
+fn default_non_breaking_errors() -> Vec<ErrorPattern> {
+    patterns(&["execution reverted", "insufficient funds", "nonce too (low|high)", "gas required exceeds", "intrinsic gas too low"])
+}
+fn default_auth_errors() -> Vec<ErrorPattern> {
+    patterns(&["invalid api[ _-]?key", "api[ _-]?key (is )?(invalid|expired|disabled|revoked)", "must be authenticated", "invalid project id", "unauthorized: (invalid|missing|bad) (api[ _-]?key|token|credentials)"])
+}
+fn default_auth_failure_threshold() -> u32 { 3 }
+fn default_auth_reprobe_s() -> u64 { 600 }
+fn default_terminal_errors() -> Vec<ErrorPattern> {
+    let mut list = patterns(&["execution reverted", "insufficient funds", "nonce too (low|high)", "intrinsic gas too low", "invalid sender"]);
+    // 3 = revert with data (geth), -32602 = invalid params
+    list.extend([3, -32602].map(|code| ErrorPattern::Code { code, message: None }));
+    list
+}
+/// Short-lived caching of `"result": null` ("not found yet") replies.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NegativeCacheConfig {
+    #[serde(default = "default_negative_ttl_ms")]
+    pub ttl_ms: u64,
+    #[serde(default = "default_negative_methods")]
+    pub methods: Vec<String>,
+}
+fn default_negative_ttl_ms() -> u64 { 1000 }
+fn default_negative_methods() -> Vec<String> {
+    ["eth_getTransactionReceipt", "eth_getTransactionByHash", "eth_getBlockByHash", "eth_getBlockByNumber"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Paginated eth_getLogs over large ranges (`rly_getLogsPaged`), for backfills.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogsPaginationConfig {
+    #[serde(default = "default_logs_chunk_blocks")]
+    pub chunk_blocks: u64, // blocks per upstream eth_getLogs; keep below provider range limits
+    #[serde(default = "default_logs_chunks_per_page")]
+    pub chunks_per_page: usize, // fetched concurrently for one page
+    #[serde(default = "default_pinned_min_confirmations")]
+    pub min_confirmations: u64, // chunks at least this deep are cached without a TTL
+    #[serde(default = "default_logs_cache_max_entries")]
+    pub cache_max_entries: usize,
+}
+fn default_logs_chunk_blocks() -> u64 { 2_000 }
+fn default_logs_chunks_per_page() -> usize { 5 }
+fn default_logs_cache_max_entries() -> usize { 10_000 }
+
+/// POST /backfill: one call per block of a range, spread over every healthy provider.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackfillConfig {
+    #[serde(default = "default_backfill_max_blocks")]
+    pub max_blocks: u64, // per request
+    #[serde(default = "default_backfill_per_provider_concurrency")]
+    pub per_provider_concurrency: usize, // in-flight calls per provider, within its max_tps
+    #[serde(default = "default_backfill_max_calls_per_s")]
+    pub max_calls_per_s: u32,            // shared by all backfill runs, on top of provider buckets; 0 = unlimited
+}
+fn default_backfill_max_blocks() -> u64 { 10_000 }
+fn default_backfill_max_calls_per_s() -> u32 { 100 }
+fn default_backfill_per_provider_concurrency() -> usize { 4 }
+
+/// Unexpiring, LRU-bounded cache for state reads at an explicit historical block.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PinnedCacheConfig {
+    #[serde(default = "default_pinned_max_entries")]
+    pub max_entries: usize,
+    #[serde(default = "default_pinned_min_confirmations")]
+    pub min_confirmations: u64, // blocks this close to the head may still reorg
+    #[serde(default = "default_pinned_methods")]
+    pub methods: Vec<String>,
+}
+fn default_pinned_max_entries() -> usize { 50_000 }
+fn default_pinned_min_confirmations() -> u64 { 64 }
+fn default_pinned_methods() -> Vec<String> {
+    ["eth_call", "eth_getBalance", "eth_getStorageAt", "eth_getCode", "eth_getProof"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Re-fetch a share of cache hits upstream and count answers that differ from the cached one.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CacheShadowConfig {
+    #[serde(default = "default_shadow_sample_rate")]
+    pub sample_rate: f64, // 0.0–1.0 of cache hits
+}
+fn default_shadow_sample_rate() -> f64 { 0.01 }
+
+/// Local checks on eth_sendRawTransaction before it is broadcast; a transaction failing
+/// them is answered with a relay error instead of being sent to any provider.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BroadcastPreflightConfig {
+    #[serde(default)]
+    pub chain_id: Option<u64>,      // the chain this relay serves; None => not checked
+    #[serde(default)]
+    pub require_chain_id: bool,     // reject legacy transactions without EIP-155 replay protection
+    #[serde(default = "default_preflight_min_gas_limit")]
+    pub min_gas_limit: u64,
+    #[serde(default)]
+    pub max_gas_limit: Option<u64>,
+    #[serde(default)]
+    pub min_fee_gwei: Option<f64>,  // floor for gasPrice / maxFeePerGas
+    #[serde(default)]
+    pub max_fee_gwei: Option<f64>,  // ceiling for gasPrice / maxFeePerGas (fat-finger guard)
+}
+fn default_preflight_min_gas_limit() -> u64 { 21_000 }
+
+/// Remember raw transactions the relay broadcast, for GET /tx/{hash}: which later
+/// broadcast (same sender and nonce, higher fee) replaced or cancelled each one.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TxTrackingConfig {
+    #[serde(default = "default_tx_retention_s")]
+    pub retention_s: u64,
+    #[serde(default = "default_tx_max_entries")]
+    pub max_entries: usize, // oldest dropped first beyond this
+}
+fn default_tx_retention_s() -> u64 { 3600 }
+fn default_tx_max_entries() -> usize { 10_000 }
+
+/// Count calls per X-RLY-Origin (the calling service's name) for GET /stats/origins.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OriginStatsConfig {
+    #[serde(default)]
+    pub trusted_keys: Vec<String>, // X-RLY-Key values allowed to tag; empty => any caller
+    #[serde(default = "default_max_origins")]
+    pub max_origins: usize,        // further origins are counted as "other"
+    #[serde(default = "default_origin_max_methods")]
+    pub max_methods: usize,        // per origin; further methods are counted as "other"
+}
+fn default_max_origins() -> usize { 100 }
+fn default_origin_max_methods() -> usize { 50 }
+
+/// Let callers set relay.max_provider_tries and relay.upstream_timeout_ms for one request
+/// with X-RLY-Max-Tries / X-RLY-Timeout-Ms, up to these ceilings.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CallOverridesConfig {
+    #[serde(default)]
+    pub trusted_keys: Vec<String>, // X-RLY-Key values allowed to override; empty => nobody
+    #[serde(default = "default_override_max_tries")]
+    pub max_tries: u32,
+    #[serde(default = "default_override_max_timeout_ms")]
+    pub max_timeout_ms: u64,
+}
+fn default_override_max_tries() -> u32 { 5 }
+fn default_override_max_timeout_ms() -> u64 { 60_000 }
+
+/// Longer cache TTLs while few or only slow providers are selectable, or in degraded mode;
+/// back to cache_ttl once the pool recovers. Uncached methods stay uncached.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CacheTtlScalingConfig {
+    #[serde(default = "default_ttl_small_pool")]
+    pub small_pool: usize,               // this many selectable providers or fewer => strained
+    #[serde(default)]
+    pub slow_latency_ms: Option<u64>,    // median selectable latency at or above this => strained
+    #[serde(default = "default_ttl_strained_scale")]
+    pub strained_scale: f64,
+    #[serde(default = "default_ttl_degraded_scale")]
+    pub degraded_scale: f64,             // health_monitor.degradation in effect
+    #[serde(default)]
+    pub min_ttl_ms: u64,                 // scaled TTLs are at least this
+    #[serde(default = "default_ttl_max_ms")]
+    pub max_ttl_ms: u64,                 // and at most this (never below the configured TTL)
+}
+fn default_ttl_small_pool() -> usize { 1 }
+fn default_ttl_strained_scale() -> f64 { 2.0 }
+fn default_ttl_degraded_scale() -> f64 { 4.0 }
+fn default_ttl_max_ms() -> u64 { 60_000 }
+
+impl CacheTtlScalingConfig {
+    /// `ttl_ms` under `scale`; 0 (not cached) stays 0 and a scale of 1 or less changes nothing.
+    pub fn apply(&self, ttl_ms: u64, scale: f64) -> u64 {
+        if ttl_ms == 0 || scale <= 1.0 {
+            return ttl_ms;
+        }
+        ((ttl_ms as f64 * scale) as u64).max(self.min_ttl_ms).min(self.max_ttl_ms.max(ttl_ms))
+    }
+}
+
+/// Self-protection against being OOM-killed: above max_rss_mb, calls to shed_methods
+/// get 503 with Retry-After and the response cache is shrunk.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MemoryGuardConfig {
+    pub max_rss_mb: u64,
+    #[serde(default = "default_memory_resume_pct")]
+    pub resume_pct: f64, // pressure ends once RSS is back under this share of max_rss_mb
+    #[serde(default = "default_memory_shed_methods")]
+    pub shed_methods: Vec<String>, // method names; `*` wildcards allowed
+    #[serde(default = "default_memory_retry_after_s")]
+    pub retry_after_s: u64,
+}
+fn default_memory_resume_pct() -> f64 { 90.0 }
+fn default_memory_shed_methods() -> Vec<String> {
+    ["eth_getLogs", "rly_getLogsPaged", "debug_*", "trace_*", "eth_getBlockReceipts"].iter().map(|s| s.to_string()).collect()
+}
+fn default_memory_retry_after_s() -> u64 { 5 }
+
+impl MemoryGuardConfig {
+    pub fn sheds(&self, method: &str) -> bool {
+        self.shed_methods.iter().any(|p| wildcard_match(p, method))
+    }
+}
+
+/// `*` matches any run of characters; everything else is literal.
+fn wildcard_match(pattern: &str, s: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = s.strip_prefix(first) else { return false };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else { return rest.is_empty() };
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+fn patterns(srcs: &[&str]) -> Vec<ErrorPattern> {
+    srcs.iter()
+        .map(|s| ErrorPattern::Message(MessagePattern::try_from(s.to_string()).expect("built-in pattern")))
+        .collect()
+}
+
+impl RelayConfig {
+    pub fn upstream_timeout(&self) -> Duration { Duration::from_millis(self.upstream_timeout_ms.max(1000)) }
+    pub fn response_limit(&self) -> usize { self.max_response_bytes.max(1) }
+}
+
+/// Matches a JSON-RPC error object. A bare string is a case-insensitive regex
+/// searched in the message; the map form pins the code and optionally the message.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged, try_from = "RawErrorPattern")]
+pub enum ErrorPattern {
+    Message(MessagePattern),
+    Code {
+        code: i64,
+        #[serde(default)]
+        message: Option<MessagePattern>,
+    },
+}
+
+impl ErrorPattern {
+    pub fn matches(&self, error: &serde_json::Value) -> bool {
+        let msg = error.get("message").and_then(|m| m.as_str()).unwrap_or_default();
+        let code = error.get("code").and_then(|c| c.as_i64());
+        match self {
+            ErrorPattern::Message(m) => m.is_match(msg),
+            ErrorPattern::Code { code: c, message } => code == Some(*c) && message.as_ref().is_none_or(|m| m.is_match(msg)),
+        }
+    }
+}
+
+// Parsed before compiling so a bad regex is reported as such rather than as
+// "did not match any variant".
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawErrorPattern {
+    Message(String),
+    Code { code: i64, #[serde(default)] message: Option<String> },
+}
+
+impl TryFrom<RawErrorPattern> for ErrorPattern {
+    type Error = String;
+    fn try_from(raw: RawErrorPattern) -> Result<Self, String> {
+        Ok(match raw {
+            RawErrorPattern::Message(m) => ErrorPattern::Message(m.try_into()?),
+            RawErrorPattern::Code { code, message } => {
+                ErrorPattern::Code { code, message: message.map(MessagePattern::try_from).transpose()? }
+            }
+        })
+    }
+}
+
+/// Regex compiled once at config load; keeps its source for re-serialization.
+#[derive(Clone, Debug, Serialize)]
+#[serde(into = "String")]
+pub struct MessagePattern {
+    src: String,
+    re: Regex,
+}
+
+impl MessagePattern {
+    pub fn is_match(&self, haystack: &str) -> bool { self.re.is_match(haystack) }
+}
+
+impl TryFrom<String> for MessagePattern {
+    type Error = String;
+    fn try_from(src: String) -> Result<Self, String> {
+        let re = Regex::builder()
+            .syntax(syntax::Config::new().case_insensitive(true))
+            .build(&src)
+            .map_err(|e| {
+                let why = e.syntax_error().map(|se| se.to_string()).unwrap_or_else(|| e.to_string());
+                format!("invalid error pattern {:?}: {}", src, why)
+            })?;
+        Ok(Self { src, re })
+    }
+}
+
+impl From<MessagePattern> for String {
+    fn from(p: MessagePattern) -> String { p.src }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RpcEndpoints {
@@ -66,25 +884,301 @@ pub struct RpcEndpoints {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Endpoint {
+    #[serde(default)]
     pub url: String,
     #[serde(default)]
+    pub url_file: Option<PathBuf>,        // read url from this file (mounted secret); re-read on change
+    #[serde(default)]
+    pub auth_token: Option<String>,       // sent upstream as "Authorization: Bearer <token>"
+    #[serde(default)]
+    pub auth_token_file: Option<PathBuf>, // read auth_token from this file; re-read on change
+    #[serde(default)]
+    pub name: Option<String>,         // stable identifier, e.g. "alchemy-main"
+    #[serde(default)]
+    pub display_name: Option<String>, // shown instead of the name / masked URL
+    #[serde(default)]
     pub max_tps: Option<u32>, // None or 0 => unlimited
+    #[serde(default)]
+    pub adaptive_tps: Option<AdaptiveTpsConfig>, // learn the limit from 429s, starting at max_tps
+    #[serde(default)]
+    pub probe_budget_per_min: Option<u32>, // health probes allowed per minute; None or 0 => every round
     #[serde(default = "default_weight")]
     pub weight: u32,
+    #[serde(default)]
+    pub pool_max_idle: Option<usize>, // overrides http_client.pool_max_idle_per_host
+    #[serde(default)]
+    pub connection_max_lifetime_s: Option<u64>, // overrides http_client.connection_max_lifetime_s
+    #[serde(default)]
+    pub dns_refresh_s: Option<u64>,             // overrides http_client.dns_refresh_s
+    #[serde(default)]
+    pub drained: bool, // kept (probed, shown) but never selected for traffic
+    #[serde(default)]
+    pub ban: Option<BanConfig>, // operator ban, shown apart from breaker bans
+    #[serde(default)]
+    pub supports_graphql: bool,        // eligible for POST /graphql
+    #[serde(default)]
+    pub graphql_url: Option<String>,   // default: the url's origin + "/graphql"
+    #[serde(default)]
+    pub capture_headers: Vec<String>,  // response headers whose latest value /status shows
+    #[serde(default)]
+    pub quota_guard: Option<QuotaGuardConfig>, // avoid the provider while a quota header runs low
+    #[serde(default)]
+    pub schedule: Option<ScheduleConfig>,      // time windows the provider takes traffic in; None => always
+    #[serde(default)]
+    pub cost_per_million: Option<f64>,         // price per million requests; None => free (self-hosted, flat rate)
+    #[serde(default)]
+    pub max_blocks_behind: Option<u64>,        // overrides health_monitor.max_blocks_behind for this provider
+    #[serde(default)]
+    pub infrastructure: Option<String>,        // backend it terminates in, e.g. "aws-us-east"; broadcasts spread over distinct ones
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QuotaGuardConfig {
+    pub header: String,     // e.g. x-ratelimit-remaining; captured even if not in capture_headers
+    pub min_remaining: f64, // below this the provider is only used when nothing else is left
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BanConfig {
+    pub reason: String,
+    #[serde(default)]
+    pub until: Option<u64>, // unix seconds; absent => until removed from config
 }
 fn default_weight() -> u32 { 1 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AdaptiveTpsConfig {
+    #[serde(default = "default_adaptive_min_tps")]
+    pub min_tps: f64,
+    #[serde(default)]
+    pub max_tps: Option<f64>,  // ceiling for upward probing; None => no ceiling
+    #[serde(default = "default_adaptive_decrease_factor")]
+    pub decrease_factor: f64,  // limit multiplier on a 429
+    #[serde(default = "default_adaptive_increase_tps")]
+    pub increase_tps: f64,     // added after each clean interval
+    #[serde(default = "default_adaptive_increase_interval_s")]
+    pub increase_interval_s: u64,
+}
+fn default_adaptive_min_tps() -> f64 { 1.0 }
+fn default_adaptive_decrease_factor() -> f64 { 0.5 }
+fn default_adaptive_increase_tps() -> f64 { 1.0 }
+fn default_adaptive_increase_interval_s() -> u64 { 10 }
+
+impl AdaptiveTpsConfig {
+    pub fn clamp(&self, tps: f64) -> f64 {
+        let min = self.min_tps.max(0.1);
+        tps.min(self.max_tps.unwrap_or(f64::INFINITY)).max(min)
+    }
+}
+
+impl Endpoint {
+    /// Key used for internal maps: the name when given, else the URL.
+    pub fn id(&self) -> &str { self.name.as_deref().unwrap_or(&self.url) }
+    // name, or the masked url, for messages
+    fn id_label(&self) -> String { self.name.clone().unwrap_or_else(|| crate::redact::mask_url(&self.url)) }
+
+    /// GraphQL endpoint when `supports_graphql` is set.
+    pub fn graphql_endpoint(&self) -> Option<String> {
+        if !self.supports_graphql { return None; }
+        if let Some(u) = &self.graphql_url { return Some(u.clone()); }
+        let mut url = reqwest::Url::parse(&self.url).ok()?;
+        url.set_path("/graphql");
+        url.set_query(None);
+        Some(url.to_string())
+    }
+}
+
+// An exact method key wins; otherwise the most specific matching pattern (one with a
+// params part, then the longest) applies.
+fn ttl_lookup(map: &HashMap<String, u64>, method: &str, params: &str) -> Option<u64> {
+    if let Some(ttl) = map.get(method) {
+        return Some(*ttl);
+    }
+    map.iter()
+        .filter(|(k, _)| match k.split_once(':') {
+            Some((m, p)) => wildcard_match(m, method) && wildcard_match(p, params),
+            None => wildcard_match(k, method),
+        })
+        .max_by_key(|(k, _)| (k.contains(':'), k.len()))
+        .map(|(_, ttl)| *ttl)
+}
+
+/// Methods whose reply depends on server-side state (filters, subscriptions, the
+/// txpool) or changes it; cache_ttl_default never applies to them.
+const STATEFUL_METHODS: &[&str] = &[
+    "eth_newFilter",
+    "eth_newBlockFilter",
+    "eth_newPendingTransactionFilter",
+    "eth_getFilterChanges",
+    "eth_getFilterLogs",
+    "eth_uninstallFilter",
+    "eth_subscribe",
+    "eth_unsubscribe",
+    "txpool_*",
+];
+
+fn is_stateful(method: &str) -> bool {
+    STATEFUL_METHODS.iter().any(|p| wildcard_match(p, method))
+}
+
 impl Config {
+    /// TTL for a call, from cache_ttl (see ttl_lookup) or cache_ttl_default; the
+    /// default never covers broadcast or stateful methods.
+    pub fn cache_ttl_for(&self, method: &str, params: &str) -> u64 {
+        match ttl_lookup(&self.cache_ttl, method, params) {
+            Some(ttl) => ttl,
+            None if self.relay.broadcast_methods.iter().any(|m| m == method) || is_stateful(method) => 0,
+            None => self.cache_ttl_default,
+        }
+    }
+
+    /// TTL for a provider's JSON-RPC error reply to a call, keyed like cache_ttl_for;
+    /// 0 (not cached) for unlisted and broadcast methods.
+    pub fn cache_error_ttl_for(&self, method: &str, params: &str) -> u64 {
+        if self.relay.broadcast_methods.iter().any(|m| m == method) {
+            return 0;
+        }
+        ttl_lookup(&self.cache_error_ttl, method, params).unwrap_or(0)
+    }
+
+    /// TTL for null results of `method`, when negative caching covers it.
+    pub fn negative_ttl_for(&self, method: &str) -> Option<u64> {
+        let n = self.negative_cache.as_ref()?;
+        (n.ttl_ms > 0 && n.methods.iter().any(|m| m == method)).then_some(n.ttl_ms)
+    }
+
     pub fn load_from_path(path: &PathBuf) -> anyhow::Result<Self> {
         let content = fs::read_to_string(path)?;
         let mut cfg: Self = serde_yaml::from_str(&content)?;
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        cfg.rpc_endpoints.resolve_secret_files(base)?;
         apply_env_overrides(&mut cfg);
         Ok(cfg)
     }
+
+    /// Semantic checks a YAML parse can't catch; a reload failing them keeps the old config.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let eps: Vec<&Endpoint> = self.rpc_endpoints.primary.iter().chain(self.rpc_endpoints.secondary.iter()).collect();
+        if eps.is_empty() {
+            anyhow::bail!("no rpc_endpoints configured");
+        }
+        // Only explicit names; same-url entries are folded by `RpcEndpoints::distinct`.
+        for (i, ep) in eps.iter().enumerate() {
+            let Some(name) = ep.name.as_deref() else { continue };
+            if eps[..i].iter().any(|e| e.name.as_deref() == Some(name)) {
+                anyhow::bail!("duplicate endpoint name {:?}", name);
+            }
+        }
+        for (name, value) in &self.relay.default_headers {
+            if reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                anyhow::bail!("relay.default_headers: {:?} is not a valid header name", name);
+            }
+            if reqwest::header::HeaderValue::from_str(value).is_err() {
+                anyhow::bail!("relay.default_headers: value of {:?} is not a valid header value", name);
+            }
+        }
+        if let Some(s) = self.relay.failover_statuses.iter().find(|s| self.relay.ban_statuses.contains(s)) {
+            anyhow::bail!("HTTP status {} is in both relay.failover_statuses and relay.ban_statuses", s);
+        }
+        if let Some(ts) = self.cache_ttl_scaling.as_ref().filter(|ts| ts.min_ttl_ms > ts.max_ttl_ms) {
+            anyhow::bail!("cache_ttl_scaling.min_ttl_ms {} exceeds max_ttl_ms {}", ts.min_ttl_ms, ts.max_ttl_ms);
+        }
+        let (primary, secondary, _) = self.rpc_endpoints.distinct();
+        let providers = primary.len() + secondary.len();
+        if self.relay.broadcast_redundancy > providers {
+            anyhow::bail!(
+                "relay.broadcast_redundancy {} exceeds the {} configured endpoint(s)",
+                self.relay.broadcast_redundancy,
+                providers
+            );
+        }
+        Ok(())
+    }
+
+    /// Secret files referenced by endpoints (already resolved to absolute/config-relative paths).
+    pub fn secret_files(&self) -> Vec<PathBuf> {
+        self.rpc_endpoints
+            .primary
+            .iter()
+            .chain(self.rpc_endpoints.secondary.iter())
+            .flat_map(|ep| [ep.url_file.clone(), ep.auth_token_file.clone()])
+            .flatten()
+            .collect()
+    }
+}
+
+impl RpcEndpoints {
+    /// Endpoints to build providers from: a url listed again (in either tier, primary first)
+    /// is folded into its first entry, so one upstream never gets two token buckets and
+    /// breakers. Also returns the (folded, kept) labels for each entry left out.
+    pub fn distinct(&self) -> (Vec<&Endpoint>, Vec<&Endpoint>, Vec<(String, String)>) {
+        let mut seen: Vec<&Endpoint> = Vec::new();
+        let mut folded = Vec::new();
+        let mut tiers = [Vec::new(), Vec::new()];
+        for (tier, eps) in tiers.iter_mut().zip([&self.primary, &self.secondary]) {
+            for ep in eps {
+                match seen.iter().find(|e| e.url == ep.url) {
+                    Some(first) => folded.push((ep.id_label(), first.id_label())),
+                    None => {
+                        seen.push(ep);
+                        tier.push(ep);
+                    }
+                }
+            }
+        }
+        let [primary, secondary] = tiers;
+        (primary, secondary, folded)
+    }
+
+    // Relative secret paths are relative to the config file's directory.
+    fn resolve_secret_files(&mut self, base: &Path) -> anyhow::Result<()> {
+        for ep in self.primary.iter_mut().chain(self.secondary.iter_mut()) {
+            if let Some(f) = ep.url_file.as_mut() {
+                if f.is_relative() { *f = base.join(&*f); }
+                ep.url = read_secret(f)?;
+            }
+            if let Some(f) = ep.auth_token_file.as_mut() {
+                if f.is_relative() { *f = base.join(&*f); }
+                ep.auth_token = Some(read_secret(f)?);
+            }
+            if ep.url.is_empty() {
+                anyhow::bail!("endpoint {} has neither url nor url_file", ep.name.as_deref().unwrap_or("<unnamed>"));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_secret(path: &Path) -> anyhow::Result<String> {
+    let v = fs::read_to_string(path).map_err(|e| anyhow::anyhow!("reading secret file {}: {}", path.display(), e))?;
+    let v = v.trim();
+    if v.is_empty() {
+        anyhow::bail!("secret file {} is empty", path.display());
+    }
+    Ok(v.to_string())
 }
 
 pub fn apply_env_overrides(cfg: &mut Config) {
     if let Ok(net) = env::var("RLY_NETWORK") { cfg.network = net; }
+    if let Ok(fmt) = env::var("RLY_LOG_FORMAT") {
+        match fmt.to_ascii_lowercase().as_str() {
+            "json" => cfg.logging.format = LogFormat::Json,
+            "compact" => cfg.logging.format = LogFormat::Compact,
+            _ => {}
+        }
+    }
+    if let Ok(cols) = env::var("RLY_UI_COLUMNS") {
+        let parsed: Result<Vec<_>, _> = cols.split(',').filter(|c| !c.trim().is_empty()).map(|c| UiColumnSpec::try_from(c.to_string())).collect();
+        if let Ok(list) = parsed { cfg.ui.columns = list; }
+    }
+    if let Ok(set) = env::var("RLY_UI_CHARSET") {
+        match set.to_ascii_lowercase().as_str() {
+            "auto" => cfg.ui.charset = UiCharset::Auto,
+            "unicode" => cfg.ui.charset = UiCharset::Unicode,
+            "ascii" => cfg.ui.charset = UiCharset::Ascii,
+            _ => {}
+        }
+    }
     if let Ok(addr) = env::var("RLY_HTTP_ADDR") { cfg.server.bind_addr = addr; }
     if let Ok(port) = env::var("RLY_HTTP_PORT") {
         if let Ok(p) = port.parse::<u16>() { cfg.server.port = p; }
@@ -92,6 +1186,12 @@ pub fn apply_env_overrides(cfg: &mut Config) {
     if let Ok(n) = env::var("RLY_BROADCAST_REDUNDANCY") {
         if let Ok(nu) = n.parse::<usize>() { cfg.relay.broadcast_redundancy = nu.max(1); }
     }
+    if let Ok(ms) = env::var("RLY_BROADCAST_DEDUP_MS") {
+        if let Ok(v) = ms.parse::<u64>() { cfg.relay.broadcast_dedup_ms = v; }
+    }
+    if let Ok(v) = env::var("RLY_BROADCAST_FINISH_ALL") {
+        cfg.relay.broadcast_finish_all = v == "1" || v.eq_ignore_ascii_case("true");
+    }
     if let Ok(ms) = env::var("RLY_LATENCY_THRESHOLD_MS") {
         cfg.relay.latency_threshold_ms = ms.parse::<u64>().ok();
     }
@@ -105,3 +1205,56 @@ pub fn apply_env_overrides(cfg: &mut Config) {
         if let Ok(v) = sec.parse::<u64>() { cfg.relay.ban_seconds = v; }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg(extra: &str) -> Config {
+        let yaml = format!(
+            "network: test\nserver: {{ bind_addr: \"127.0.0.1\", port: 0 }}\nrelay: {{}}\nrpc_endpoints: {{ primary: [{{ url: \"http://a.invalid\" }}] }}\n{extra}"
+        );
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    #[test]
+    fn default_ttl_skips_broadcast_and_stateful_methods() {
+        let c = cfg("cache_ttl_default: 1000\ncache_ttl: { eth_getFilterLogs: 50 }\n");
+        assert_eq!(c.cache_ttl_for("eth_blockNumber", "[]"), 1000);
+        assert_eq!(c.cache_ttl_for("eth_sendRawTransaction", "[]"), 0);
+        for m in ["eth_newFilter", "eth_getFilterChanges", "eth_uninstallFilter", "eth_subscribe", "txpool_content", "txpool_status"] {
+            assert_eq!(c.cache_ttl_for(m, "[]"), 0, "{m}");
+        }
+        // An explicit entry still wins.
+        assert_eq!(c.cache_ttl_for("eth_getFilterLogs", "[]"), 50);
+    }
+
+    #[test]
+    fn default_auth_errors_skip_a_bare_unauthorized() {
+        let auth = default_auth_errors();
+        let hit = |msg: &str| auth.iter().any(|p| p.matches(&serde_json::json!({ "code": -32000, "message": msg })));
+        assert!(hit("Invalid API key"));
+        assert!(hit("Unauthorized: invalid api key"));
+        assert!(!hit("unauthorized"));
+        assert!(!hit("method debug_traceTransaction unauthorized for this plan"));
+    }
+
+    #[test]
+    fn unnamed_same_url_endpoints_fold_into_one_provider() {
+        let mut c = cfg("");
+        c.relay.broadcast_redundancy = 1;
+        c.rpc_endpoints.primary.push(c.rpc_endpoints.primary[0].clone());
+        c.validate().unwrap();
+        let reg = crate::state::build_registry(&c.rpc_endpoints, &c.http_client, &c.relay.default_headers);
+        assert_eq!(reg.all().len(), 1);
+
+        let named = |n: &str, url: &str| {
+            let mut ep = c.rpc_endpoints.primary[0].clone();
+            ep.name = Some(n.into());
+            ep.url = url.into();
+            ep
+        };
+        c.rpc_endpoints.primary = vec![named("a", "http://a.invalid"), named("a", "http://b.invalid")];
+        assert!(c.validate().is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::config::{Config, Endpoint};
+use crate::redact::mask_url;
+use serde_json::{Map, Value};
+
+/// One changed setting between two configs. `path` is dotted ("relay.max_provider_tries",
+/// "rpc_endpoints.primary[alchemy].max_tps"); provider additions/removals have a null side.
+pub struct Change {
+    pub path: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// What a reload changes, with credentials masked so the result can go to the log.
+pub fn diff(old: &Config, new: &Config) -> Vec<Change> {
+    let mut out = Vec::new();
+    let (mut o, mut n) = (to_value(old), to_value(new));
+    for v in [&mut o, &mut n] {
+        if let Some(obj) = v.as_object_mut() {
+            obj.remove("rpc_endpoints");
+        }
+    }
+    walk("", &o, &n, &mut out);
+    for (tier, old_eps, new_eps) in [
+        ("primary", &old.rpc_endpoints.primary, &new.rpc_endpoints.primary),
+        ("secondary", &old.rpc_endpoints.secondary, &new.rpc_endpoints.secondary),
+    ] {
+        diff_endpoints(tier, old_eps, new_eps, &mut out);
+    }
+    out
+}
+
+fn diff_endpoints(tier: &str, old: &[Endpoint], new: &[Endpoint], out: &mut Vec<Change>) {
+    let label = |ep: &Endpoint| ep.name.clone().unwrap_or_else(|| mask_url(&ep.url));
+    for ep in old.iter().filter(|ep| !new.iter().any(|n| n.id() == ep.id())) {
+        out.push(Change { path: format!("rpc_endpoints.{}[{}]", tier, label(ep)), old: "present".into(), new: Value::Null });
+    }
+    for ep in new {
+        let path = format!("rpc_endpoints.{}[{}]", tier, label(ep));
+        match old.iter().find(|o| o.id() == ep.id()) {
+            Some(prev) => {
+                // a rotated key can mask to the same string; still say the URL changed
+                if prev.url != ep.url && mask_url(&prev.url) == mask_url(&ep.url) {
+                    out.push(Change { path: format!("{}.url", path), old: "***".into(), new: "*** (changed)".into() });
+                }
+                walk(&path, &to_value(prev), &to_value(ep), out)
+            }
+            None => out.push(Change { path, old: Value::Null, new: "added".into() }),
+        }
+    }
+}
+
+fn walk(path: &str, old: &Value, new: &Value, out: &mut Vec<Change>) {
+    if old == new {
+        return;
+    }
+    match (old, new) {
+        (Value::Object(o), Value::Object(n)) => {
+            let mut keys: Vec<&String> = o.keys().chain(n.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for k in keys {
+                let sub = if path.is_empty() { k.clone() } else { format!("{}.{}", path, k) };
+                walk(&sub, o.get(k).unwrap_or(&Value::Null), n.get(k).unwrap_or(&Value::Null), out);
+            }
+        }
+        _ => out.push(Change { path: path.to_string(), old: old.clone(), new: new.clone() }),
+    }
+}
+
+fn to_value<T: serde::Serialize>(v: &T) -> Value {
+    let mut v = serde_json::to_value(v).unwrap_or(Value::Null);
+    redact(&mut v);
+    v
+}
+
+// Field names whose values are credentials; provider URLs are masked like in /status.
+fn redact(v: &mut Value) {
+    let Value::Object(obj) = v else { return };
+    let obj: &mut Map<String, Value> = obj;
+    for (k, val) in obj.iter_mut() {
+        let key = k.to_ascii_lowercase();
+        if key == "url" || key.ends_with("_url") {
+            if let Some(s) = val.as_str() {
+                *val = Value::String(mask_url(s));
+            }
+        } else if ["token", "password", "secret", "dsn", "api_key"].iter().any(|s| key.contains(s)) {
+            if !val.is_null() {
+                *val = Value::String("***".into());
+            }
+        } else {
+            redact(val);
+        }
+    }
+}
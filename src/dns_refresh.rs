@@ -0,0 +1,32 @@
+use crate::state::{AppState, ProviderState};
+use reqwest::Url;
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+use tracing::debug;
+
+/// Keeps pooled upstream connections pointed at current addresses:
+/// recycles pools past their max lifetime and re-resolves provider hosts
+/// so GeoDNS / failover DNS changes are picked up without waiting for errors.
+pub async fn connection_refresh_loop(app: Arc<AppState>) {
+    loop {
+        let all = app.snapshot().registry.all();
+        for p in all {
+            p.recycle_client_if_expired();
+            if p.claim_dns_refresh() {
+                tokio::spawn(async move { resolve(p).await });
+            }
+        }
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
+async fn resolve(p: Arc<ProviderState>) {
+    let Ok(url) = Url::parse(&p.url) else { return };
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else { return };
+    let host = host.to_string();
+    let lookup = tokio::net::lookup_host((host.as_str(), port)).await;
+    match lookup {
+        Ok(addrs) => p.apply_resolved_addrs(addrs.map(|a| a.ip()).collect()),
+        Err(e) => debug!("dns lookup for {} failed: {}", host, e),
+    }
+}
@@ -1,43 +1,104 @@
-use once_cell::sync::Lazy;
-use parking_lot::RwLock;
-use std::collections::HashMap;
-
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-#[repr(u8)]
-pub enum ErrorReason {
-    None = 0,
-    RpcError = 1,
-    BadJson = 2,
-    HttpError = 3,
-    Timeout = 4,
-}
-
-impl ErrorReason {
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            ErrorReason::None => "-",
-            ErrorReason::RpcError => "rpc_error",
-            ErrorReason::BadJson => "bad_json",
-            ErrorReason::HttpError => "http_error",
-            ErrorReason::Timeout => "timeout",
-        }
-    }
-}
-
-static LAST_ERR: Lazy<RwLock<HashMap<String, ErrorReason>>> =
-    Lazy::new(|| RwLock::new(HashMap::new()));
-
-pub fn set_last_error(url: &str, reason: ErrorReason) {
-    let mut map = LAST_ERR.write();
-    if reason == ErrorReason::None {
-        // Keep a "None" entry for visibility (“-” in UI) rather than removing.
-        map.insert(url.to_string(), reason);
-    } else {
-        map.insert(url.to_string(), reason);
-    }
-}
-
-pub fn get_last_error(url: &str) -> ErrorReason {
-    let map = LAST_ERR.read();
-    map.get(url).copied().unwrap_or(ErrorReason::None)
-}
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ErrorReason {
+    None = 0,
+    RpcError = 1,
+    BadJson = 2,
+    HttpError = 3, // transport failure none of the finer kinds below fit
+    Timeout = 4,
+    TooLarge = 5,
+    RateLimited = 6,
+    AuthFailed = 7,
+    DnsFailure = 8,
+    ConnectTimeout = 9,
+    TlsError = 10,
+    ConnectionReset = 11, // reset, aborted or closed mid-exchange
+    Http4xx = 12,         // other than 401/403/429, which have their own kinds
+    Http5xx = 13,
+}
+
+impl ErrorReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorReason::None => "-",
+            ErrorReason::RpcError => "rpc_error",
+            ErrorReason::BadJson => "bad_json",
+            ErrorReason::HttpError => "http_error",
+            ErrorReason::Timeout => "timeout",
+            ErrorReason::TooLarge => "too_large",
+            ErrorReason::RateLimited => "rate_limited",
+            ErrorReason::AuthFailed => "auth_failed",
+            ErrorReason::DnsFailure => "dns_failure",
+            ErrorReason::ConnectTimeout => "connect_timeout",
+            ErrorReason::TlsError => "tls_error",
+            ErrorReason::ConnectionReset => "connection_reset",
+            ErrorReason::Http4xx => "http_4xx",
+            ErrorReason::Http5xx => "http_5xx",
+        }
+    }
+}
+
+/// One recorded upstream failure; `http_status` is set when the provider answered with an HTTP error.
+#[derive(Copy, Clone, Debug)]
+pub struct ErrorEvent {
+    pub unix_s: u64,
+    pub reason: ErrorReason,
+    pub http_status: Option<u16>,
+}
+
+const HISTORY: usize = 10;
+
+#[derive(Default)]
+struct ProviderErrors {
+    last: Option<ErrorEvent>,
+    recent: VecDeque<ErrorEvent>, // newest last
+}
+
+static LAST_ERR: Lazy<RwLock<HashMap<String, ProviderErrors>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+pub fn set_last_error(provider_id: &str, reason: ErrorReason, http_status: Option<u16>) {
+    let unix_s = crate::timing::epoch_secs();
+    let event = ErrorEvent { unix_s, reason, http_status };
+    let mut map = LAST_ERR.write();
+    let entry = map.entry(provider_id.to_string()).or_default();
+    // Keep a "None" entry for visibility (“-” in UI); only real errors go into the history.
+    entry.last = Some(event);
+    if reason != ErrorReason::None {
+        if entry.recent.len() >= HISTORY {
+            entry.recent.pop_front();
+        }
+        entry.recent.push_back(event);
+    }
+}
+
+pub fn get_last_error(provider_id: &str) -> ErrorReason {
+    let map = LAST_ERR.read();
+    map.get(provider_id).and_then(|e| e.last).map_or(ErrorReason::None, |e| e.reason)
+}
+
+pub fn get_last_http_status(provider_id: &str) -> Option<u16> {
+    LAST_ERR.read().get(provider_id).and_then(|e| e.last).and_then(|e| e.http_status)
+}
+
+/// Last error for display, e.g. "http_5xx 502".
+pub fn last_error_label(provider_id: &str) -> String {
+    match (get_last_error(provider_id), get_last_http_status(provider_id)) {
+        (reason, Some(status)) => format!("{} {}", reason.as_str(), status),
+        (reason, None) => reason.as_str().to_string(),
+    }
+}
+
+/// Up to the last 10 failures, newest first.
+pub fn recent_errors(provider_id: &str) -> Vec<ErrorEvent> {
+    LAST_ERR.read().get(provider_id).map(|e| e.recent.iter().rev().copied().collect()).unwrap_or_default()
+}
+
+/// Drop everything recorded for a provider that is gone for good.
+pub fn forget(provider_id: &str) {
+    LAST_ERR.write().remove(provider_id);
+}
@@ -0,0 +1,49 @@
+use crate::relay::{healthy_candidates_where, record_failure, HttpState};
+use crate::upstream;
+use axum::{extract::State, http::StatusCode, Json};
+use serde_json::{json, Value};
+use std::sync::atomic::Ordering;
+
+/// POST /graphql — forward the query to a provider flagged `supports_graphql`,
+/// failing over on transport errors. GraphQL-level `errors` are the caller's
+/// and are returned as-is.
+pub async fn graphql(State(state): State<HttpState>, Json(body): Json<Value>) -> (StatusCode, Json<Value>) {
+    state.app.total_calls.fetch_add(1, Ordering::Relaxed);
+
+    let snap = state.app.snapshot();
+    let (rc, breaker_cfg) = (&snap.cfg.relay, &snap.breaker);
+    let cands = healthy_candidates_where(&snap.registry, rc.weight_decay.as_ref(), |p| p.graphql_url().is_some());
+    if cands.is_empty() {
+        return gql_error(StatusCode::SERVICE_UNAVAILABLE, "No healthy GraphQL-capable RPCs available");
+    }
+
+    let mut rr_idx = state.app.rr_main.fetch_add(1, Ordering::Relaxed) as usize;
+    let mut last_err = String::new();
+    for _ in 0..rc.max_provider_tries.max(1) {
+        let prov = cands.rotation(rr_idx).find(|p| p.try_consume_token()).cloned();
+        rr_idx = rr_idx.wrapping_add(1);
+
+        let Some(prov) = prov else {
+            return gql_error(StatusCode::TOO_MANY_REQUESTS, "Rate limited; try later");
+        };
+        let Some(url) = prov.graphql_url() else { continue };
+        prov.call_count.fetch_add(1, Ordering::Relaxed);
+
+        match upstream::post_json(&prov, &url, &body, rc.upstream_timeout(), rc.response_limit()).await {
+            Ok(v) => {
+                prov.breaker_success();
+                prov.record_outcome(v.get("errors").is_none());
+                return (StatusCode::OK, Json(v));
+            }
+            Err(e) => {
+                last_err = e.to_string();
+                record_failure(&prov, breaker_cfg, "graphql", e.reason(), e.http_status(), &last_err);
+            }
+        }
+    }
+    gql_error(StatusCode::BAD_GATEWAY, &format!("Upstream provider error after failover: {}", last_err))
+}
+
+fn gql_error(status: StatusCode, message: &str) -> (StatusCode, Json<Value>) {
+    (status, Json(json!({ "errors": [{ "message": message }] })))
+}
@@ -0,0 +1,250 @@
+use crate::jsonrpc::JsonRpcRequest;
+use crate::relay::{self, CallLimits, CallTrace, HttpState};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::Engine;
+use serde_json::Value;
+use std::time::Duration;
+
+// gRPC-Web ingress (feature "grpc"): one unary Relay/Call wrapping a JSON-RPC call.
+// Served over HTTP/1.1 next to the JSON-RPC route, so no HTTP/2 stack is needed; native
+// gRPC clients reach it through a grpc-web capable client or proxy. Messages are
+// hand-encoded protobuf:
+//   message CallRequest { string method = 1; string params = 2; }  // params: JSON text, default []
+//   message CallReply   { string result = 1; string error = 2; }   // JSON text of either member
+// A JSON-RPC error from the provider is a successful call with `error` set; only relay-level
+// failures (no provider, rate limit, deadline, ...) become a non-OK grpc-status.
+
+pub const PATH: &str = "/rly.v1.Relay/Call";
+
+// grpc-status codes used here
+const OK: u32 = 0;
+const INVALID_ARGUMENT: u32 = 3;
+const DEADLINE_EXCEEDED: u32 = 4;
+const RESOURCE_EXHAUSTED: u32 = 8;
+const INTERNAL: u32 = 13;
+const UNAVAILABLE: u32 = 14;
+const UNAUTHENTICATED: u32 = 16;
+
+/// POST /rly.v1.Relay/Call (application/grpc-web+proto or application/grpc-web-text)
+pub async fn call(State(state): State<HttpState>, headers: HeaderMap, body: Bytes) -> Response {
+    let text = headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).is_some_and(|ct| ct.starts_with("application/grpc-web-text"));
+    let reply = |status: u32, message: &str, data: Option<Vec<u8>>| grpc_web_reply(text, status, message, data);
+
+    let body = if text {
+        match base64::engine::general_purpose::STANDARD.decode(body.trim_ascii()) {
+            Ok(b) => b,
+            Err(e) => return reply(INVALID_ARGUMENT, &format!("bad base64 body: {}", e), None),
+        }
+    } else {
+        body.to_vec()
+    };
+    let Some(msg) = unframe(&body) else { return reply(INVALID_ARGUMENT, "expected one uncompressed grpc-web message", None) };
+    let (method, params) = match decode_request(msg) {
+        Ok(v) => v,
+        Err(e) => return reply(INVALID_ARGUMENT, &e, None),
+    };
+    if method.is_empty() {
+        return reply(INVALID_ARGUMENT, "method is required", None);
+    }
+    let params: Value = if params.trim().is_empty() {
+        Value::Array(Vec::new())
+    } else {
+        match serde_json::from_str(&params) {
+            Ok(v @ (Value::Array(_) | Value::Object(_))) => v,
+            Ok(_) => return reply(INVALID_ARGUMENT, "params must be a JSON array or object", None),
+            Err(e) => return reply(INVALID_ARGUMENT, &format!("params is not JSON: {}", e), None),
+        }
+    };
+
+    let snap = state.app.snapshot();
+    let deadline = headers.get("grpc-timeout").and_then(|v| v.to_str().ok()).and_then(parse_timeout);
+    let mut limits = snap.cfg.call_overrides.as_ref().map(|co| relay::call_limits(co, &headers)).unwrap_or_default();
+    if let Some(d) = deadline {
+        // Never longer than what the relay would otherwise allow one attempt.
+        let cap = limits.timeout.unwrap_or_else(|| snap.cfg.relay.upstream_timeout());
+        limits = CallLimits { timeout: Some(d.min(cap)), ..limits };
+    }
+    let api_key = headers.get("x-rly-key").and_then(|v| v.to_str().ok());
+    let mut trace = CallTrace::new();
+    let run = relay::relay_call(&state, &snap, JsonRpcRequest::new(&method, params), api_key, limits, &mut trace);
+    // Dropping the future (deadline, or the client going away) cancels the upstream call.
+    let (status, Json(mut v)) = match deadline {
+        Some(d) => match tokio::time::timeout(d, run).await {
+            Ok(r) => r,
+            Err(_) => return reply(DEADLINE_EXCEEDED, "deadline exceeded", None),
+        },
+        None => run.await,
+    };
+    state.app.totals.record(status.as_u16(), &v);
+
+    if status != StatusCode::OK {
+        let message = v.pointer("/error/message").and_then(Value::as_str).unwrap_or("relay error").to_string();
+        return reply(status_code(status), &message, None);
+    }
+    let mut out = Vec::new();
+    if let Some(r) = v.get_mut("result").map(Value::take) {
+        put_string(&mut out, 1, &r.to_string());
+    }
+    if let Some(e) = v.get_mut("error").map(Value::take) {
+        put_string(&mut out, 2, &e.to_string());
+    }
+    reply(OK, "", Some(out))
+}
+
+fn status_code(status: StatusCode) -> u32 {
+    match status.as_u16() {
+        400 | 413 => INVALID_ARGUMENT,
+        401 | 403 => UNAUTHENTICATED,
+        429 => RESOURCE_EXHAUSTED,
+        502..=504 => UNAVAILABLE,
+        _ => INTERNAL,
+    }
+}
+
+/// grpc-timeout: up to 8 digits and a unit (H, M, S, m, u, n).
+fn parse_timeout(v: &str) -> Option<Duration> {
+    let (n, unit) = v.split_at(v.len().checked_sub(1)?);
+    if n.is_empty() || n.len() > 8 || !n.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let n: u64 = n.parse().ok()?;
+    Some(match unit {
+        "H" => Duration::from_secs(n * 3600),
+        "M" => Duration::from_secs(n * 60),
+        "S" => Duration::from_secs(n),
+        "m" => Duration::from_millis(n),
+        "u" => Duration::from_micros(n),
+        "n" => Duration::from_nanos(n),
+        _ => return None,
+    })
+}
+
+// The single data frame of a unary request: flag 0 (uncompressed), u32 BE length, message.
+fn unframe(body: &[u8]) -> Option<&[u8]> {
+    let (&flag, rest) = body.split_first()?;
+    let len = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+    (flag == 0 && rest.len() == 4 + len).then(|| &rest[4..])
+}
+
+fn frame(out: &mut Vec<u8>, flag: u8, msg: &[u8]) {
+    out.push(flag);
+    out.extend_from_slice(&(msg.len() as u32).to_be_bytes());
+    out.extend_from_slice(msg);
+}
+
+fn grpc_web_reply(text: bool, status: u32, message: &str, data: Option<Vec<u8>>) -> Response {
+    let mut body = Vec::new();
+    if let Some(msg) = data {
+        frame(&mut body, 0x00, &msg);
+    }
+    let mut trailers = format!("grpc-status:{}\r\n", status);
+    if !message.is_empty() {
+        trailers.push_str(&format!("grpc-message:{}\r\n", percent_encode(message)));
+    }
+    frame(&mut body, 0x80, trailers.as_bytes());
+    let (content_type, body) = if text {
+        ("application/grpc-web-text+proto", base64::engine::general_purpose::STANDARD.encode(body).into_bytes())
+    } else {
+        ("application/grpc-web+proto", body)
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    (StatusCode::OK, headers, body).into_response()
+}
+
+// grpc-message is percent-encoded outside printable ASCII (and '%' itself).
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| if (0x20..0x7f).contains(&b) && b != b'%' { (b as char).to_string() } else { format!("%{:02X}", b) })
+        .collect()
+}
+
+fn decode_request(mut buf: &[u8]) -> Result<(String, String), String> {
+    let (mut method, mut params) = (String::new(), String::new());
+    while !buf.is_empty() {
+        let key = varint(&mut buf).ok_or("truncated field key")?;
+        match (key >> 3, key & 7) {
+            (field @ (1 | 2), 2) => {
+                let s = bytes(&mut buf).ok_or("truncated string")?;
+                let s = String::from_utf8(s.to_vec()).map_err(|_| "string field is not UTF-8")?;
+                if field == 1 { method = s } else { params = s }
+            }
+            // Unknown fields are skipped, as protobuf requires.
+            (_, 0) => { varint(&mut buf).ok_or("truncated varint")?; }
+            (_, 1) => buf = buf.get(8..).ok_or("truncated fixed64")?,
+            (_, 2) => { bytes(&mut buf).ok_or("truncated bytes")?; }
+            (_, 5) => buf = buf.get(4..).ok_or("truncated fixed32")?,
+            (_, wt) => return Err(format!("unsupported wire type {}", wt)),
+        }
+    }
+    Ok((method, params))
+}
+
+fn varint(buf: &mut &[u8]) -> Option<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&b, rest) = buf.split_first()?;
+        *buf = rest;
+        v |= u64::from(b & 0x7f) << shift;
+        if b & 0x80 == 0 {
+            return Some(v);
+        }
+    }
+    None
+}
+
+fn bytes<'a>(buf: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = usize::try_from(varint(buf)?).ok()?;
+    let s = buf.get(..len)?;
+    *buf = &buf[len..];
+    Some(s)
+}
+
+fn put_string(out: &mut Vec<u8>, field: u64, s: &str) {
+    let mut put_varint = |mut v: u64| {
+        while v >= 0x80 {
+            out.push(v as u8 | 0x80);
+            v >>= 7;
+        }
+        out.push(v as u8);
+    };
+    put_varint(field << 3 | 2);
+    put_varint(s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_round_trips_through_framing() {
+        let mut msg = Vec::new();
+        put_string(&mut msg, 1, "eth_getBalance");
+        msg.extend_from_slice(&[0x18, 0x96, 0x01]); // unknown varint field 3 = 150
+        put_string(&mut msg, 2, &"[\"0xab\",\"latest\"]".repeat(10));
+        let mut body = Vec::new();
+        frame(&mut body, 0, &msg);
+        let (method, params) = decode_request(unframe(&body).unwrap()).unwrap();
+        assert_eq!(method, "eth_getBalance");
+        assert_eq!(params.len(), 170); // length needs a two-byte varint
+        assert!(unframe(&body[..body.len() - 1]).is_none());
+        assert!(decode_request(&msg[..msg.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn grpc_timeout_units() {
+        assert_eq!(parse_timeout("250m"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_timeout("2S"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_timeout("1H"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_timeout("5"), None);
+        assert_eq!(parse_timeout("m"), None);
+        assert_eq!(parse_timeout("123456789m"), None);
+    }
+}
@@ -1,88 +1,194 @@
-use crate::config::Config;
-use crate::state::{ProviderRegistry, ProviderState};
-use reqwest::Client;
+use crate::config::ProbeTokens;
+use crate::jsonrpc::{self, JsonRpcRequest};
+use crate::state::{AppState, ProviderState};
+use crate::upstream;
 use serde_json::json;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
 use tokio::time::{sleep, Duration};
-use tracing::debug;
+use tracing::{debug, info, warn};
 
-fn hex_to_u64(h: &str) -> Option<u64> {
-    let s = h.trim_start_matches("0x");
-    u64::from_str_radix(s, 16).ok()
+// Set by the health loop while health_monitor.degradation thresholds are in force.
+static DEGRADED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_degraded() -> bool { DEGRADED.load(Ordering::Relaxed) }
+
+// Multiplier for cache TTLs under cache_ttl_scaling (f64 bits); 1.0 while the pool is fine.
+static TTL_SCALE: AtomicU64 = AtomicU64::new(1f64.to_bits());
+
+pub fn cache_ttl_scale() -> f64 { f64::from_bits(TTL_SCALE.load(Ordering::Relaxed)) }
+
+// Probes are small; the timeout is fixed so one slow provider can't stall a round.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// One eth_blockNumber probe over the provider's transport (same client, headers and
+/// auth as relay traffic); updates latency/health and returns the head on success.
+async fn probe(p: &ProviderState, tokens: ProbeTokens, max_bytes: usize) -> Option<u64> {
+    let payload = JsonRpcRequest::new("eth_blockNumber", json!([])).to_value();
+    if tokens == ProbeTokens::Accounted {
+        p.charge_token();
+    }
+    let start = std::time::Instant::now();
+    match upstream::call(p, &payload, PROBE_TIMEOUT, max_bytes).await {
+        Ok(v) => {
+            let latency_ms = start.elapsed().as_millis() as u64;
+            if let Some(bn) = v.get("result").and_then(|r| r.as_str()).and_then(jsonrpc::parse_quantity) {
+                p.set_latest_block(bn);
+                p.set_latency(latency_ms);
+                p.mark_healthy(true);
+                return Some(bn);
+            }
+            debug!(provider = %p.label(), reason = "bad_result", "health probe failed");
+            p.mark_healthy(false);
+            None
+        }
+        Err(e) => {
+            debug!(provider = %p.label(), reason = e.reason().as_str(), error = %e, "health probe failed");
+            p.mark_healthy(false);
+            None
+        }
+    }
 }
 
-pub async fn health_loop(cfg: Arc<RwLock<Config>>, registry: Arc<RwLock<ProviderRegistry>>, client: Client) {
+pub async fn health_loop(app: Arc<AppState>) {
     loop {
-        let (interval_s, max_behind) = {
-            let c = cfg.read().await;
-            (
-                c.health_monitor.monitor_interval_s,
-                c.health_monitor.max_blocks_behind,
-            )
-        };
-
-        let all = { registry.read().await.all() };
-        if all.is_empty() {
+        let snap = app.snapshot();
+        let hm = &snap.cfg.health_monitor;
+        let (interval_s, max_behind, degradation, lag_weighting) =
+            (hm.monitor_interval_s, hm.max_blocks_behind, hm.degradation.clone(), hm.lag_weighting);
+        let (tokens, max_bytes) = (hm.probe_tokens, snap.cfg.relay.response_limit());
+        app.expire_retired(hm.retired_retention());
+
+        let all = snap.registry.all();
+        // Providers out of probe budget keep their last verdict this round.
+        let due: Vec<_> = all.iter().filter(|p| p.try_probe_budget()).cloned().collect();
+        // Misconfigured providers are probed like the rest; every auth_reprobe_s a passing probe lets one back in.
+        let reprobe: Vec<bool> = due.iter().map(|p| p.auth_reprobe_due(&snap.breaker)).collect();
+        drop(snap);
+        if due.is_empty() {
             sleep(Duration::from_secs(interval_s.max(1))).await;
             continue;
         }
 
         // Probe all endpoints concurrently
-        let mut handles = Vec::with_capacity(all.len());
-        for p in all.iter() {
-            let client = client.clone();
+        let mut handles = Vec::with_capacity(due.len());
+        for (p, reprobe) in due.iter().zip(reprobe) {
             let p = p.clone();
             handles.push(tokio::spawn(async move {
-                let payload = json!({
-                    "jsonrpc":"2.0",
-                    "id":1,
-                    "method":"eth_blockNumber",
-                    "params":[]
-                });
-                let start = std::time::Instant::now();
-                let res = client.post(&p.url).json(&payload).timeout(Duration::from_secs(3)).send().await;
-                match res {
-                    Ok(resp) => match resp.json::<serde_json::Value>().await {
-                        Ok(v) => {
-                            let latency_ms = start.elapsed().as_millis() as u64;
-                            if let Some(hex) = v.get("result").and_then(|r| r.as_str()) {
-                                if let Some(bn) = hex_to_u64(hex) {
-                                    p.set_latest_block(bn);
-                                    p.set_latency(latency_ms);
-                                    p.mark_healthy(true);
-                                    return Some((p, bn));
-                                }
-                            }
-                            p.mark_healthy(false);
-                            None
-                        }
-                        Err(_) => { p.mark_healthy(false); None }
-                    },
-                    Err(_) => { p.mark_healthy(false); None }
+                let bn = probe(&p, tokens, max_bytes).await;
+                p.record_probe(bn.is_some());
+                if reprobe && bn.is_some() {
+                    p.clear_misconfigured("health re-probe accepted our credentials");
                 }
+                bn.map(|bn| (p, bn))
             }));
         }
 
-        let mut max_block = 0u64;
         let mut ok_states: Vec<(Arc<ProviderState>, u64)> = Vec::new();
         for h in handles {
             if let Ok(Some((p, bn))) = h.await {
-                if bn > max_block { max_block = bn; }
                 ok_states.push((p, bn));
             }
         }
 
+        // Head and pool health over every provider: this round's result where it was probed,
+        // its last known head and verdict where it was out of probe budget.
+        let max_block = all.iter().map(|p| p.get_latest_block()).max().unwrap_or(0);
+        // Judge against the normal thresholds first; if too many fail them, loosen them.
+        let strict_ok = all
+            .iter()
+            .filter(|p| p.is_healthy() && max_block.saturating_sub(p.get_latest_block()) <= p.max_blocks_behind(max_behind, None))
+            .count();
+        let unhealthy_pct = (all.len() - strict_ok) as f64 * 100.0 / all.len() as f64;
+        let degraded = degradation.as_ref().filter(|d| unhealthy_pct > d.unhealthy_pct);
+        set_degraded(degraded.is_some(), unhealthy_pct);
+
         // Compute "behind" and mark over-threshold as unhealthy
         for (p, bn) in ok_states.into_iter() {
-            let behind = max_block.saturating_sub(bn);
-            p.set_behind(behind);
-            if behind > max_behind {
-                p.mark_healthy(false);
-            }
+            judge_lag(&p, max_block.saturating_sub(bn), p.max_blocks_behind(max_behind, degraded), lag_weighting);
+        }
+        for p in all.iter() {
+            p.uptime.lock().observe(p.is_healthy());
+            p.is_off_schedule(); // logs window changes even without traffic
         }
+        update_ttl_scale(&app, degraded.is_some());
 
-        debug!("health check done, max_block={}", max_block);
+        debug!(max_block, "health check done");
         sleep(Duration::from_secs(interval_s.max(1))).await;
     }
 }
+
+fn judge_lag(p: &ProviderState, behind: u64, max_behind: u64, lag_weighting: bool) {
+    p.set_behind(behind);
+    let lag_factor = match (lag_weighting, max_behind) {
+        (false, _) => 1.0,
+        (true, 0) => if behind == 0 { 1.0 } else { 0.0 },
+        (true, max) => 1.0 - behind as f64 / max as f64,
+    };
+    p.set_lag_factor(lag_factor);
+    if behind > max_behind {
+        p.mark_healthy(false);
+    }
+}
+
+/// Probe providers added by a reload right away, judged against the heads the last
+/// round saw, so they join rotation with real latency instead of waiting a full interval.
+pub async fn warm_up(added: Vec<Arc<ProviderState>>, app: Arc<AppState>) {
+    let (max_behind, degraded, lag_weighting, tokens, max_bytes) = {
+        let snap = app.snapshot();
+        let hm = &snap.cfg.health_monitor;
+        let degraded = hm.degradation.clone().filter(|_| is_degraded());
+        (hm.max_blocks_behind, degraded, hm.lag_weighting, hm.probe_tokens, snap.cfg.relay.response_limit())
+    };
+    let added: Vec<_> = added.into_iter().filter(|p| p.try_probe_budget()).collect();
+    let heads = futures::future::join_all(added.iter().map(|p| probe(p, tokens, max_bytes))).await;
+    let max_block = app.snapshot().registry.all().iter().map(|p| p.get_latest_block()).max().unwrap_or(0);
+    for (p, bn) in added.iter().zip(heads) {
+        p.record_probe(bn.is_some());
+        if let Some(bn) = bn {
+            judge_lag(p, max_block.saturating_sub(bn), p.max_blocks_behind(max_behind, degraded.as_ref()), lag_weighting);
+        }
+        p.uptime.lock().observe(p.is_healthy());
+        info!(provider = %p.label(), healthy = p.is_healthy(), latency_ms = p.get_latency(), behind = p.get_behind(), "new provider probed");
+    }
+}
+
+/// Pick the cache TTL multiplier from the pool as selection sees it now: degraded_scale in
+/// degraded mode, strained_scale when few or only slow providers are selectable, else 1.
+fn update_ttl_scale(app: &AppState, degraded: bool) {
+    let snap = app.snapshot();
+    let scale = match &snap.cfg.cache_ttl_scaling {
+        None => 1.0,
+        Some(ts) if degraded => ts.degraded_scale,
+        Some(ts) => {
+            let cands = crate::relay::healthy_candidates(&snap.registry, None);
+            let mut latencies: Vec<u64> = cands.providers().iter().map(|p| p.get_latency()).collect();
+            latencies.sort_unstable();
+            let median = latencies.get(latencies.len() / 2).copied();
+            let slow = ts.slow_latency_ms.zip(median).is_some_and(|(limit, ms)| ms >= limit);
+            if latencies.len() <= ts.small_pool || slow { ts.strained_scale } else { 1.0 }
+        }
+    };
+    let before = f64::from_bits(TTL_SCALE.swap(scale.to_bits(), Ordering::Relaxed));
+    if before != scale {
+        info!(from = before, to = scale, "cache TTL scale changed");
+    }
+}
+
+fn set_degraded(on: bool, unhealthy_pct: f64) {
+    if DEGRADED.swap(on, Ordering::Relaxed) == on {
+        return;
+    }
+    if on {
+        warn!(unhealthy_pct, "too many unhealthy providers; entering degraded mode with loosened thresholds");
+        crate::reporting::report(
+            "degraded_mode",
+            "entered degraded mode: serving from lagging/slow providers",
+            json!({ "unhealthy_pct": unhealthy_pct }),
+        );
+    } else {
+        info!(unhealthy_pct, "left degraded mode");
+    }
+}
@@ -0,0 +1,34 @@
+use crate::config::Config;
+use std::time::Duration;
+
+/// `relay healthcheck`: probe the local /readyz and return a process exit code.
+/// Meant for Docker HEALTHCHECK / k8s exec probes, so images don't need curl.
+pub async fn run(cfg: &Config) -> i32 {
+    // A wildcard bind address isn't connectable; probe loopback instead.
+    let host = match cfg.server.bind_addr.as_str() {
+        "0.0.0.0" | "" => "127.0.0.1".to_string(),
+        "::" | "[::]" => "[::1]".to_string(),
+        h if h.contains(':') && !h.starts_with('[') => format!("[{}]", h),
+        h => h.to_string(),
+    };
+    let url = format!("http://{}:{}/readyz", host, cfg.server.port);
+
+    let client = match reqwest::Client::builder().timeout(Duration::from_secs(3)).build() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("healthcheck: {}", e);
+            return 1;
+        }
+    };
+    match client.get(&url).send().await {
+        Ok(resp) if resp.status().is_success() => 0,
+        Ok(resp) => {
+            eprintln!("healthcheck: {} returned {}", url, resp.status());
+            1
+        }
+        Err(e) => {
+            eprintln!("healthcheck: {} unreachable: {}", url, e);
+            1
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// One JSON-RPC 2.0 call, as received from a client or sent upstream.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JsonRpcRequest {
+    #[serde(default = "default_version")]
+    pub jsonrpc: String,
+    #[serde(default)]
+    pub id: Value, // absent => null
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub params: Value,
+}
+
+fn default_version() -> String { "2.0".to_string() }
+
+impl JsonRpcRequest {
+    pub fn new(method: &str, params: Value) -> Self {
+        Self { jsonrpc: default_version(), id: Value::from(1), method: method.to_string(), params }
+    }
+
+    /// Parse and validate one call; the error is a message for a -32600 reply.
+    pub fn from_value(v: Value) -> Result<Self, String> {
+        if !v.is_object() {
+            return Err(format!("request must be an object, got {}", type_name(&v)));
+        }
+        let req: Self = serde_json::from_value(v).map_err(|e| format!("malformed request: {}", e))?;
+        if req.jsonrpc != "2.0" {
+            return Err(format!("unsupported jsonrpc version {:?}", req.jsonrpc));
+        }
+        if req.method.is_empty() {
+            return Err("method must not be empty".to_string());
+        }
+        if !matches!(req.params, Value::Array(_) | Value::Object(_) | Value::Null) {
+            return Err(format!("params must be an array or object, got {}", type_name(&req.params)));
+        }
+        if !matches!(req.id, Value::String(_) | Value::Number(_) | Value::Null) {
+            return Err(format!("id must be a string, number or null, got {}", type_name(&req.id)));
+        }
+        Ok(req)
+    }
+
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+}
+
+/// A request body: one call, or a batch whose members are parsed and validated one by
+/// one. Batches aren't served yet: the relay answers them with a single invalid-request
+/// error.
+#[derive(Debug)]
+pub enum RequestBody {
+    Single(JsonRpcRequest),
+    Batch(Vec<Result<JsonRpcRequest, String>>),
+}
+
+impl RequestBody {
+    pub fn parse(v: Value) -> Result<Self, String> {
+        match v {
+            Value::Array(items) if items.is_empty() => Err("empty batch".to_string()),
+            Value::Array(items) => Ok(RequestBody::Batch(items.into_iter().map(JsonRpcRequest::from_value).collect())),
+            other => JsonRpcRequest::from_value(other).map(RequestBody::Single),
+        }
+    }
+}
+
+/// A raw request body as JSON; the error is a message for a -32700 reply.
+pub fn parse_body(body: &[u8]) -> Result<Value, String> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Err("empty body".to_string());
+    }
+    serde_json::from_slice(body).map_err(|e| format!("body is not valid JSON: {}", e))
+}
+
+/// A reply the relay builds itself. Provider replies are passed through as plain JSON.
+#[derive(Clone, Debug, Serialize)]
+pub struct JsonRpcResponse {
+    pub jsonrpc: &'static str,
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+}
+
+impl JsonRpcResponse {
+    pub fn result(id: Value, result: Value) -> Self {
+        Self { jsonrpc: "2.0", id, result: Some(result), error: None }
+    }
+
+    pub fn error(id: Value, error: JsonRpcError) -> Self {
+        Self { jsonrpc: "2.0", id, result: None, error: Some(error) }
+    }
+
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl JsonRpcError {
+    /// The error object of a reply, if it has a well-formed one.
+    pub fn of(reply: &Value) -> Option<Self> {
+        serde_json::from_value(reply.get("error")?.clone()).ok()
+    }
+}
+
+/// A hex quantity such as a block number ("0x1b4"); the 0x prefix is optional.
+pub fn parse_quantity(s: &str) -> Option<u64> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u64::from_str_radix(digits, 16).ok()
+}
+
+/// Id to answer an invalid request with: its own id when that is usable, else null.
+pub fn reply_id(body: &Value) -> Value {
+    match body.get("id") {
+        Some(id @ (Value::String(_) | Value::Number(_))) => id.clone(),
+        _ => Value::Null,
+    }
+}
+
+/// A single call without an "id" member: per spec the client expects no reply at all.
+pub fn is_notification(body: &Value) -> bool {
+    body.as_object().is_some_and(|o| !o.contains_key("id"))
+}
+
+/// Re-address a reply (e.g. one served from cache) to request `id`.
+pub fn set_id(reply: &mut Value, id: &Value) {
+    if let Some(obj) = reply.as_object_mut() {
+        obj.insert("id".to_string(), id.clone());
+    }
+}
+
+fn type_name(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn single(v: Value) -> Result<JsonRpcRequest, String> {
+        match RequestBody::parse(v)? {
+            RequestBody::Single(req) => Ok(req),
+            RequestBody::Batch(_) => panic!("parsed as a batch"),
+        }
+    }
+
+    fn batch(v: Value) -> Vec<Result<JsonRpcRequest, String>> {
+        match RequestBody::parse(v) {
+            Ok(RequestBody::Batch(calls)) => calls,
+            other => panic!("not a batch: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn quantities() {
+        assert_eq!(parse_quantity("0x1b4"), Some(436));
+        assert_eq!(parse_quantity("ff"), Some(255));
+        assert_eq!(parse_quantity("0x0"), Some(0));
+        for bad in ["0x", "", "+1", "0x-1", "0xg", "0x10000000000000000"] {
+            assert_eq!(parse_quantity(bad), None, "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn jsonrpc_version() {
+        let absent = single(json!({"id": 1, "method": "eth_chainId"})).unwrap();
+        assert_eq!(absent.jsonrpc, "2.0");
+        let err = single(json!({"jsonrpc": "1.0", "id": 1, "method": "eth_chainId"})).unwrap_err();
+        assert!(err.contains("unsupported jsonrpc version"), "{}", err);
+        let err = single(json!({"jsonrpc": 2, "id": 1, "method": "eth_chainId"})).unwrap_err();
+        assert!(err.starts_with("malformed request"), "{}", err);
+    }
+
+    #[test]
+    fn method_must_be_a_non_empty_string() {
+        for method in [json!(1), json!(null), json!(["eth_chainId"]), json!({})] {
+            let err = single(json!({"jsonrpc": "2.0", "id": 1, "method": method})).unwrap_err();
+            assert!(err.starts_with("malformed request"), "{}", err);
+        }
+        let err = single(json!({"jsonrpc": "2.0", "id": 1})).unwrap_err();
+        assert!(err.contains("missing field `method`"), "{}", err);
+        let err = single(json!({"jsonrpc": "2.0", "id": 1, "method": ""})).unwrap_err();
+        assert_eq!(err, "method must not be empty");
+    }
+
+    #[test]
+    fn params_shapes() {
+        let call = |params: Option<Value>| {
+            let mut v = json!({"jsonrpc": "2.0", "id": 1, "method": "eth_call"});
+            if let Some(p) = params {
+                v["params"] = p;
+            }
+            single(v)
+        };
+        assert_eq!(call(Some(json!(["0x1", "latest"]))).unwrap().params, json!(["0x1", "latest"]));
+        assert_eq!(call(Some(json!({"to": "0x1"}))).unwrap().params, json!({"to": "0x1"}));
+        assert_eq!(call(None).unwrap().params, Value::Null);
+        assert_eq!(call(Some(Value::Null)).unwrap().params, Value::Null);
+        for bad in [json!("0x1"), json!(1), json!(true)] {
+            let err = call(Some(bad)).unwrap_err();
+            assert!(err.starts_with("params must be an array or object"), "{}", err);
+        }
+        // absent params aren't forwarded as "params": null
+        let forwarded = call(None).unwrap().to_value();
+        assert!(forwarded.get("params").is_none());
+    }
+
+    #[test]
+    fn id_forms() {
+        let call = |id: Option<Value>| {
+            let mut v = json!({"jsonrpc": "2.0", "method": "eth_chainId"});
+            if let Some(id) = id {
+                v["id"] = id;
+            }
+            v
+        };
+        for id in [json!(null), json!(7), json!("abc")] {
+            let body = call(Some(id.clone()));
+            assert_eq!(single(body.clone()).unwrap().id, id);
+            assert!(!is_notification(&body));
+        }
+        let notification = call(None);
+        assert!(is_notification(&notification));
+        assert_eq!(single(notification.clone()).unwrap().id, Value::Null);
+        assert_eq!(reply_id(&notification), Value::Null);
+
+        for bad in [json!([1]), json!({"n": 1}), json!(true)] {
+            let body = call(Some(bad));
+            let err = single(body.clone()).unwrap_err();
+            assert!(err.starts_with("id must be a string, number or null"), "{}", err);
+            assert_eq!(reply_id(&body), Value::Null);
+        }
+        assert_eq!(reply_id(&call(Some(json!(7)))), json!(7));
+        assert_eq!(reply_id(&call(Some(json!("abc")))), json!("abc"));
+    }
+
+    #[test]
+    fn non_object_requests() {
+        for v in [json!("eth_chainId"), json!(1), json!(null), json!(true)] {
+            let err = single(v).unwrap_err();
+            assert!(err.starts_with("request must be an object"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn batches() {
+        assert_eq!(RequestBody::parse(json!([])).unwrap_err(), "empty batch");
+
+        let calls = batch(json!([1, "x", null]));
+        assert_eq!(calls.len(), 3);
+        assert!(calls.iter().all(|c| c.as_ref().is_err_and(|e| e.starts_with("request must be an object"))));
+
+        let calls = batch(json!([
+            {"jsonrpc": "2.0", "id": 1, "method": "eth_chainId"},
+            {"jsonrpc": "2.0", "id": 2},
+            5,
+            {"jsonrpc": "2.0", "id": 3, "method": "eth_blockNumber", "params": []},
+        ]));
+        assert_eq!(calls.len(), 4);
+        assert_eq!(calls[0].as_ref().unwrap().method, "eth_chainId");
+        assert!(calls[1].is_err());
+        assert!(calls[2].is_err());
+        assert_eq!(calls[3].as_ref().unwrap().id, json!(3));
+    }
+
+    #[test]
+    fn non_json_bodies() {
+        for body in [&b""[..], b"  \n", b"not json", b"{\"jsonrpc\": \"2.0\",", b"\xff\xfe", b"[1, 2"] {
+            assert!(parse_body(body).is_err(), "{:?}", body);
+        }
+        assert_eq!(parse_body(b"").unwrap_err(), "empty body");
+        assert_eq!(parse_body(b" {\"id\": 1} ").unwrap(), json!({"id": 1}));
+    }
+
+    #[test]
+    fn error_object_of_reply() {
+        let e = JsonRpcError::of(&json!({"error": {"code": -32000, "message": "nonce too low"}})).unwrap();
+        assert_eq!((e.code, e.message.as_str(), e.data), (-32000, "nonce too low", None));
+        assert!(JsonRpcError::of(&json!({"error": "boom"})).is_none());
+        assert!(JsonRpcError::of(&json!({"result": "0x1"})).is_none());
+    }
+}
@@ -0,0 +1,145 @@
+//! Minimal Keccak-256 (the pre-NIST padding Ethereum uses), enough to derive
+//! transaction hashes from raw signed transactions.
+
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000_0000_0000_0001, 0x0000_0000_0000_8082, 0x8000_0000_0000_808a, 0x8000_0000_8000_8000,
+    0x0000_0000_0000_808b, 0x0000_0000_8000_0001, 0x8000_0000_8000_8081, 0x8000_0000_0000_8009,
+    0x0000_0000_0000_008a, 0x0000_0000_0000_0088, 0x0000_0000_8000_8009, 0x0000_0000_8000_000a,
+    0x0000_0000_8000_808b, 0x8000_0000_0000_008b, 0x8000_0000_0000_8089, 0x8000_0000_0000_8003,
+    0x8000_0000_0000_8002, 0x8000_0000_0000_0080, 0x0000_0000_0000_800a, 0x8000_0000_8000_000a,
+    0x8000_0000_8000_8081, 0x8000_0000_0000_8080, 0x0000_0000_8000_0001, 0x8000_0000_8000_8008,
+];
+
+const ROTATIONS: [u32; 24] = [1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44];
+const PI_LANES: [usize; 24] = [10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1];
+
+const RATE: usize = 136;
+
+fn keccak_f(a: &mut [u64; 25]) {
+    for rc in ROUND_CONSTANTS {
+        // theta
+        let mut c = [0u64; 5];
+        for x in 0..5 {
+            c[x] = a[x] ^ a[x + 5] ^ a[x + 10] ^ a[x + 15] ^ a[x + 20];
+        }
+        for x in 0..5 {
+            let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+            for y in 0..5 {
+                a[x + 5 * y] ^= d;
+            }
+        }
+        // rho + pi
+        let mut last = a[1];
+        for i in 0..24 {
+            let j = PI_LANES[i];
+            let tmp = a[j];
+            a[j] = last.rotate_left(ROTATIONS[i]);
+            last = tmp;
+        }
+        // chi
+        for y in 0..5 {
+            let row = [a[5 * y], a[5 * y + 1], a[5 * y + 2], a[5 * y + 3], a[5 * y + 4]];
+            for x in 0..5 {
+                a[5 * y + x] = row[x] ^ (!row[(x + 1) % 5] & row[(x + 2) % 5]);
+            }
+        }
+        // iota
+        a[0] ^= rc;
+    }
+}
+
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut state = [0u64; 25];
+    let mut absorb = |block: &[u8]| {
+        for (i, lane) in block.chunks(8).enumerate() {
+            let mut buf = [0u8; 8];
+            buf[..lane.len()].copy_from_slice(lane);
+            state[i] ^= u64::from_le_bytes(buf);
+        }
+        keccak_f(&mut state);
+    };
+
+    let mut chunks = data.chunks_exact(RATE);
+    for block in &mut chunks {
+        absorb(block);
+    }
+    let rest = chunks.remainder();
+    let mut last = [0u8; RATE];
+    last[..rest.len()].copy_from_slice(rest);
+    last[rest.len()] ^= 0x01;
+    last[RATE - 1] ^= 0x80;
+    absorb(&last);
+
+    let mut out = [0u8; 32];
+    for (i, lane) in state.iter().take(4).enumerate() {
+        out[i * 8..i * 8 + 8].copy_from_slice(&lane.to_le_bytes());
+    }
+    out
+}
+
+/// Decode a `0x`-prefixed (or bare) hex string. Only hex digits are accepted:
+/// from_str_radix alone would also take a sign, e.g. "+f".
+pub fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    if !s.len().is_multiple_of(2) || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok()).collect()
+}
+
+pub fn encode_hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(2 + bytes.len() * 2);
+    s.push_str("0x");
+    for b in bytes {
+        s.push_str(&format!("{:02x}", b));
+    }
+    s
+}
+
+/// Hash of a raw signed transaction (`eth_sendRawTransaction` param) as the node would report it.
+pub fn tx_hash(raw_hex: &str) -> Option<String> {
+    let raw = decode_hex(raw_hex)?;
+    if raw.is_empty() {
+        return None;
+    }
+    Some(encode_hex(&keccak256(&raw)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex_of(data: &[u8]) -> String { encode_hex(&keccak256(data)) }
+
+    #[test]
+    fn known_answers() {
+        assert_eq!(hex_of(b""), "0xc5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+        assert_eq!(hex_of(b"abc"), "0x4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45");
+        // one byte short of a block: both padding bits land in the same byte
+        assert_eq!(hex_of(&[b'a'; 135]), "0x34367dc248bbd832f4e3e69dfaac2f92638bd0bbd18f2912ba4ef454919cf446");
+        // exactly one block: the padding needs a block of its own
+        let block: Vec<u8> = (0..136u8).collect();
+        assert_eq!(hex_of(&block), "0x7ce759f1ab7f9ce437719970c26b0a66ff11fe3e38e17df89cf5d29c7d7f807e");
+        assert_eq!(hex_of(&[b'a'; 137]), "0xd869f639c7046b4929fc92a4d988a8b22c55fbadb802c0c66ebcd484f1915f39");
+    }
+
+    #[test]
+    fn signed_tx_hash() {
+        // the EIP-155 example transaction (nonce 9, 1 ether to 0x3535..., chain id 1)
+        let raw = "0xf86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+        assert_eq!(tx_hash(raw).as_deref(), Some("0x33469b22e9f636356c4160a87eb19df52b7412e8eac32a4a55ffe88ea8350788"));
+        assert_eq!(tx_hash("0x"), None);
+    }
+
+    #[test]
+    fn decode_hex_takes_hex_digits_only() {
+        assert_eq!(decode_hex("0x00ff"), Some(vec![0x00, 0xff]));
+        assert_eq!(decode_hex("ABcd"), Some(vec![0xab, 0xcd]));
+        assert_eq!(decode_hex(""), Some(vec![]));
+        assert_eq!(decode_hex("0xabc"), None);
+        for bad in ["+f", "0x+f00", "-1", " f", "0xzz", "0x\u{e9}"] {
+            assert_eq!(decode_hex(bad), None, "{bad:?}");
+        }
+        assert_eq!(encode_hex(&[0, 0xab]), "0x00ab");
+    }
+}
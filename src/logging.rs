@@ -0,0 +1,220 @@
+use crate::config::{LogFormat, LogRotation, LogTarget, LoggingConfig};
+use crate::timing::epoch_secs;
+use serde_json::{Map, Value};
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::{field::Field, Event, Subscriber};
+use tracing_subscriber::{
+    field::Visit,
+    fmt::{format::Writer, writer::BoxMakeWriter, FmtContext, FormatEvent, FormatFields},
+    registry::LookupSpan,
+};
+
+/// Install the global tracing subscriber according to the `logging` config section.
+pub fn init(cfg: &LoggingConfig) -> anyhow::Result<()> {
+    let filter = std::env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string());
+    let (writer, ansi) = match cfg.target {
+        LogTarget::Stdout => (BoxMakeWriter::new(io::stdout), true),
+        LogTarget::File => {
+            let file = RotatingFile::open(PathBuf::from(&cfg.path), cfg.rotation, cfg.max_size_mb, cfg.max_files)?;
+            (BoxMakeWriter::new(Mutex::new(file)), false)
+        }
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_target(true)
+        .with_ansi(ansi)
+        .with_writer(writer);
+    match cfg.format {
+        LogFormat::Compact => builder.compact().init(),
+        LogFormat::Json => builder.event_format(JsonFormat).init(),
+    }
+    Ok(())
+}
+
+// ----------------------
+// Rotating file writer
+// ----------------------
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    day: u64,
+    rotation: LogRotation,
+    max_bytes: u64,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    pub fn open(path: PathBuf, rotation: LogRotation, max_size_mb: u64, max_files: usize) -> io::Result<Self> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            path,
+            file,
+            written,
+            day: epoch_secs() / 86_400,
+            rotation,
+            max_bytes: max_size_mb.max(1) * 1024 * 1024,
+            max_files: max_files.max(1),
+        })
+    }
+
+    fn rotate_if_needed(&mut self, incoming: usize) -> io::Result<()> {
+        match self.rotation {
+            LogRotation::Never => Ok(()),
+            LogRotation::Size => {
+                if self.written > 0 && self.written + incoming as u64 > self.max_bytes {
+                    // relay.log.(n-1) -> relay.log.n, ..., relay.log -> relay.log.1
+                    for i in (1..self.max_files).rev() {
+                        let _ = fs::rename(numbered(&self.path, i), numbered(&self.path, i + 1));
+                    }
+                    fs::rename(&self.path, numbered(&self.path, 1))?;
+                    self.reopen()?;
+                }
+                Ok(())
+            }
+            LogRotation::Daily => {
+                let today = epoch_secs() / 86_400;
+                if today != self.day {
+                    let (y, m, d) = civil_from_days(self.day as i64);
+                    let dated = suffixed(&self.path, &format!("{:04}-{:02}-{:02}", y, m, d));
+                    fs::rename(&self.path, dated)?;
+                    self.day = today;
+                    self.reopen()?;
+                    self.prune_dated();
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+
+    /// Keep only the newest `max_files` dated files next to the active log.
+    fn prune_dated(&self) {
+        let (Some(dir), Some(name)) = (self.path.parent(), self.path.file_name().and_then(|n| n.to_str())) else { return };
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        let prefix = format!("{}.", name);
+        let Ok(entries) = fs::read_dir(dir) else { return };
+        let mut dated: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_str().map(|n| n.starts_with(&prefix)).unwrap_or(false))
+            .map(|e| e.path())
+            .collect();
+        dated.sort();
+        while dated.len() > self.max_files {
+            let _ = fs::remove_file(dated.remove(0));
+        }
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Err(e) = self.rotate_if_needed(buf.len()) {
+            eprintln!("log rotation failed: {}", e);
+        }
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> { self.file.flush() }
+}
+
+fn numbered(path: &Path, n: usize) -> PathBuf { suffixed(path, &n.to_string()) }
+
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(".");
+    s.push(suffix);
+    PathBuf::from(s)
+}
+
+// ----------------------
+// JSON event format (one object per line)
+// ----------------------
+pub struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, _ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let meta = event.metadata();
+        let mut obj = Map::new();
+        obj.insert("ts".into(), Value::String(rfc3339_now()));
+        obj.insert("level".into(), Value::String(meta.level().to_string()));
+        obj.insert("target".into(), Value::String(meta.target().to_string()));
+        event.record(&mut JsonVisitor(&mut obj));
+        writeln!(writer, "{}", Value::Object(obj))
+    }
+}
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), Value::String(value.to_string()));
+    }
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), Value::from(value));
+    }
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), Value::from(value));
+    }
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), Value::from(value));
+    }
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), Value::from(value));
+    }
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().into(), Value::String(format!("{:?}", value)));
+    }
+}
+
+// ----------------------
+// Time helpers (UTC, no chrono dependency)
+// ----------------------
+
+/// Current UTC time as `YYYY-MM-DDTHH:MM:SS.mmmZ`.
+pub fn rfc3339_now() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = now.as_secs();
+    let (y, m, d) = civil_from_days((secs / 86_400) as i64);
+    let rem = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        y, m, d, rem / 3600, (rem % 3600) / 60, rem % 60, now.subsec_millis()
+    )
+}
+
+/// Days since 1970-01-01 -> (year, month, day). Howard Hinnant's algorithm.
+pub fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    (y, m, d)
+}
@@ -0,0 +1,164 @@
+use crate::config::LogsPaginationConfig;
+use crate::jsonrpc::{JsonRpcRequest, JsonRpcResponse};
+use crate::keccak::{encode_hex, keccak256};
+use crate::relay::{self, CallLimits, CallTrace, HttpState};
+use crate::relay_error::RelayError;
+use crate::state::Snapshot;
+use axum::{http::StatusCode, Json};
+use futures::future::join_all;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// Paginated eth_getLogs: `[filter, cursor?]`. The range is split into chunk_blocks-sized
+/// eth_getLogs calls, chunks_per_page of them per page, run through the normal relay path.
+pub const METHOD: &str = "rly_getLogsPaged";
+
+pub async fn serve(
+    state: &HttpState,
+    snap: &Arc<Snapshot>,
+    req: JsonRpcRequest,
+    pc: &LogsPaginationConfig,
+    api_key: Option<&str>,
+    limits: CallLimits,
+    trace: &mut CallTrace,
+) -> (StatusCode, Json<Value>) {
+    state.app.total_calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let id = req.id.clone();
+    let head = snap.registry.all().iter().map(|p| p.get_latest_block()).max().unwrap_or(0);
+    let (filter, from, to) = match parse(&req.params, head) {
+        Ok(v) => v,
+        Err(detail) => return RelayError::InvalidParams { detail }.into_response(id),
+    };
+    if to < from {
+        return (StatusCode::OK, Json(page_reply(id, Vec::new(), from, to, None)));
+    }
+
+    let span = pc.chunk_blocks.max(1);
+    let page_end = from.saturating_add(span.saturating_mul(pc.chunks_per_page.max(1) as u64) - 1).min(to);
+    let chunks: Vec<(u64, u64)> = (from..=page_end).step_by(span as usize).map(|s| (s, (s + span - 1).min(page_end))).collect();
+    let page = Page { state, snap, pc, head, api_key, limits };
+    let results = join_all(chunks.iter().map(|&range| fetch_chunk(&page, &filter, range))).await;
+
+    let mut logs = Vec::new();
+    for (status, reply, sub) in results {
+        trace.attempts.extend(sub.attempts);
+        if sub.served_by.is_some() {
+            trace.served_by = sub.served_by;
+        }
+        match reply {
+            Ok(chunk) => logs.extend(chunk),
+            // The whole page fails; the client retries it with the same cursor.
+            Err(mut reply) => {
+                crate::jsonrpc::set_id(&mut reply, &id);
+                return (status, Json(reply));
+            }
+        }
+    }
+    let next = (page_end < to).then(|| cursor(&filter, page_end + 1, to));
+    (StatusCode::OK, Json(page_reply(id, logs, from, page_end, next)))
+}
+
+// What every chunk of one page shares.
+struct Page<'a> {
+    state: &'a HttpState,
+    snap: &'a Arc<Snapshot>,
+    pc: &'a LogsPaginationConfig,
+    head: u64,
+    api_key: Option<&'a str>,
+    limits: CallLimits,
+}
+
+// One sub-range; deep enough ranges are served from and kept in the pinned cache.
+async fn fetch_chunk(page: &Page<'_>, filter: &Value, (start, end): (u64, u64)) -> (StatusCode, Result<Vec<Value>, Value>, CallTrace) {
+    let Page { state, snap, pc, head, api_key, limits } = *page;
+    let mut sub = filter.clone();
+    sub["fromBlock"] = hex(start);
+    sub["toBlock"] = hex(end);
+    let params = json!([sub]);
+    let key = ("eth_getLogs".to_string(), crate::cache_key::canonical_params("eth_getLogs", &params));
+    let pin = head >= end.saturating_add(pc.min_confirmations);
+    let mut trace = CallTrace::new();
+    if pin {
+        if let Some(Value::Array(logs)) = state.relay.cache.get_pinned(&key) {
+            state.app.cache_hits.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            trace.cache = "hit";
+            return (StatusCode::OK, Ok(logs), trace);
+        }
+    }
+    let (status, Json(mut reply)) = relay::relay_call(state, snap, JsonRpcRequest::new("eth_getLogs", params), api_key, limits, &mut trace).await;
+    match reply.get_mut("result").map(Value::take) {
+        Some(Value::Array(logs)) if reply.get("error").is_none() => {
+            if pin {
+                state.relay.cache.insert_pinned(key, Value::Array(logs.clone()), pc.cache_max_entries);
+            }
+            (status, Ok(logs), trace)
+        }
+        _ => (status, Err(reply), trace),
+    }
+}
+
+// (filter without the range, first block, last block) of this page's request.
+fn parse(params: &Value, head: u64) -> Result<(Value, u64, u64), String> {
+    let filter = match params.get(0) {
+        Some(Value::Object(f)) => f,
+        _ => return Err("params[0] must be an eth_getLogs filter object".to_string()),
+    };
+    if filter.contains_key("blockHash") {
+        return Err("blockHash filters cover a single block; use eth_getLogs".to_string());
+    }
+    let block = |key: &str| match filter.get(key) {
+        None => resolve("latest", head),
+        Some(Value::String(s)) => resolve(s, head),
+        Some(other) => Err(format!("{} must be a block number or tag, got {}", key, other)),
+    };
+    let mut rest = filter.clone();
+    rest.remove("fromBlock");
+    rest.remove("toBlock");
+    let rest = Value::Object(rest);
+    let (from, to) = (block("fromBlock")?, block("toBlock")?);
+    match params.get(1) {
+        None | Some(Value::Null) => Ok((rest, from, to)),
+        Some(Value::String(c)) => {
+            let (next, end) = parse_cursor(c, &rest).ok_or("cursor does not belong to this filter")?;
+            Ok((rest, next, end))
+        }
+        Some(_) => Err("params[1] must be the cursor string from the previous page".to_string()),
+    }
+}
+
+fn resolve(tag: &str, head: u64) -> Result<u64, String> {
+    match tag {
+        "earliest" => Ok(0),
+        "latest" if head > 0 => Ok(head),
+        "latest" => Err("head block not known yet; pass an explicit toBlock".to_string()),
+        _ => tag
+            .strip_prefix("0x")
+            .and_then(|h| u64::from_str_radix(h, 16).ok())
+            .ok_or_else(|| format!("unsupported block {:?} (hex number, latest or earliest)", tag)),
+    }
+}
+
+// "<next>-<to>-<fingerprint>": the range end is fixed by the first page, so "latest" doesn't move
+// under a running backfill, and the fingerprint stops a cursor from being reused with another filter.
+fn cursor(filter: &Value, next: u64, to: u64) -> String {
+    format!("{:x}-{:x}-{}", next, to, fingerprint(filter))
+}
+
+fn parse_cursor(c: &str, filter: &Value) -> Option<(u64, u64)> {
+    let mut parts = c.splitn(3, '-');
+    let next = u64::from_str_radix(parts.next()?, 16).ok()?;
+    let to = u64::from_str_radix(parts.next()?, 16).ok()?;
+    (parts.next()? == fingerprint(filter)).then_some((next, to))
+}
+
+fn fingerprint(filter: &Value) -> String {
+    let canonical = crate::cache_key::canonical_params("eth_getLogs", &json!([filter]));
+    encode_hex(&keccak256(canonical.as_bytes())[..8]).split_off(2)
+}
+
+fn page_reply(id: Value, logs: Vec<Value>, from: u64, to: u64, next: Option<String>) -> Value {
+    let result = json!({ "logs": logs, "fromBlock": hex(from), "toBlock": hex(to), "next": next });
+    JsonRpcResponse::result(id, result).to_value()
+}
+
+fn hex(n: u64) -> Value { Value::String(format!("0x{:x}", n)) }
@@ -1,68 +1,153 @@
+mod adaptive_tps;
+mod admin;
+mod api_spec;
+mod archive;
+mod auth;
+mod backfill;
+mod bench;
+mod cache_key;
+mod cache_shadow;
 mod config;
+mod config_diff;
 mod state;
 mod token_bucket;
 mod circuit_breaker;
+mod compare;
+mod dns_refresh;
+mod graphql;
+#[cfg(feature = "grpc")]
+mod grpc;
 mod health;
+mod healthcheck;
+mod jsonrpc;
+mod keccak;
+mod logging;
+mod logs_page;
+mod memory_guard;
+mod metrics;
+mod namespace_stats;
+mod origin_stats;
+mod rates;
+mod raw_tx;
+mod redact;
 mod relay;
+mod relay_error;
+mod relay_totals;
+mod reporting;
+mod rest;
+mod schedule;
+mod selector;
+mod simulate;
+mod status_query;
+mod timing;
+mod tx_track;
 mod ui;
+mod upstream;
+mod uptime;
 // NEW: declare the error_reason module so others can use crate::error_reason
 mod error_reason;
 
-use axum::{routing::get, Router};
+use axum::{middleware, routing::{get, post}};
 use config::Config;
 use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
-use reqwest::Client;
 use std::{env, net::SocketAddr, path::PathBuf, sync::Arc};
 use tokio::net::TcpListener;
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
 use anyhow::Result;
 
-use state::{AppState, reconcile_registry};
+use api_spec::Documented;
+use state::{AppState, Snapshot, reconcile_registry};
 use relay::{HttpState, RelayCtx};
 use health::health_loop;
 use ui::run_terminal_dashboard;
 
 static DEFAULT_CONFIG_PATH: &str = "config.yaml";
+const RELOAD_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(300);
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(std::env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string()))
-        .with_target(true)
-        .compact()
-        .init();
-
-    // Load config
     let cfg_path = env::var("RLY_CONFIG_PATH").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
     let cfg_path = PathBuf::from(cfg_path);
+
+    if env::args().nth(1).as_deref() == Some("healthcheck") {
+        let cfg = Config::load_from_path(&cfg_path)?;
+        std::process::exit(healthcheck::run(&cfg).await);
+    }
+    if env::args().nth(1).as_deref() == Some("bench") {
+        let args: Vec<String> = env::args().skip(2).collect();
+        std::process::exit(bench::run(&args));
+    }
+    if env::args().nth(1).as_deref() == Some("compare") {
+        let cfg = Config::load_from_path(&cfg_path)?;
+        let args: Vec<String> = env::args().skip(2).collect();
+        std::process::exit(compare::run(&cfg, &args).await);
+    }
+    if env::args().nth(1).as_deref() == Some("simulate") {
+        let cfg = Config::load_from_path(&cfg_path)?;
+        let args: Vec<String> = env::args().skip(2).collect();
+        std::process::exit(simulate::run(&cfg, &args));
+    }
+
+    // Load config
     let cfg = Config::load_from_path(&cfg_path)?;
+    logging::init(&cfg.logging)?;
+    reporting::configure(&cfg.error_reporting, &cfg.network);
+    reporting::install_panic_hook();
     info!("loaded config for network {}", cfg.network);
+    if let Err(e) = cfg.validate() {
+        // kept non-fatal at startup so existing deployments still come up; reloads are gated
+        warn!("config validation: {:#}", e);
+    }
 
     // State
     let app_state = Arc::new(AppState::new(cfg));
-    let client = Client::builder()
-        .pool_max_idle_per_host(32)
-        .tcp_keepalive(Some(std::time::Duration::from_secs(30)))
-        .build()?;
 
-    let relay_ctx = RelayCtx::new(client.clone());
+    let relay_ctx = RelayCtx::new();
+    let cache = relay_ctx.cache.clone();
     let http_state = HttpState { app: app_state.clone(), relay: relay_ctx };
 
     // Health monitor
     {
-        let cfg_arc = app_state.cfg.clone();
-        let reg_arc = app_state.registry.clone();
-        let client = client.clone();
-        tokio::spawn(async move {
-            health_loop(cfg_arc, reg_arc, client).await;
+        let app = app_state.clone();
+        reporting::spawn_supervised("health_loop", async move {
+            health_loop(app).await;
+        });
+    }
+
+    // History depth probing (idle unless health_monitor.archive_probe is set)
+    reporting::spawn_supervised("archive_probe", archive::archive_probe_loop(app_state.clone()));
+
+    // Connection lifetime / DNS refresh
+    {
+        let app = app_state.clone();
+        reporting::spawn_supervised("connection_refresh", async move {
+            dns_refresh::connection_refresh_loop(app).await;
         });
     }
 
+    // Expired cache entries (lookups only evict the keys they touch)
+    {
+        let cache = cache.clone();
+        reporting::spawn_supervised("cache_purge", async move {
+            loop {
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                cache.purge_expired().await;
+            }
+        });
+    }
+
+    // RSS watch (idle unless memory_guard is configured)
+    reporting::spawn_supervised("memory_guard", memory_guard::memory_guard_loop(app_state.clone(), cache.clone()));
+
+    // Push metrics exporters (idle unless configured)
+    reporting::spawn_supervised("statsd_exporter", metrics::statsd_loop(app_state.clone()));
+    reporting::spawn_supervised("influx_exporter", metrics::influx_loop(app_state.clone()));
+
     // Config watcher
     {
         let app_state = app_state.clone();
         let cfg_path = cfg_path.clone();
-        tokio::spawn(async move {
+        reporting::spawn_supervised("config_watcher", async move {
             if let Err(e) = watch_config_and_apply(cfg_path, app_state).await {
                 error!("config watcher error: {:?}", e);
             }
@@ -73,16 +158,37 @@ async fn main() -> Result<()> {
     let enable_tui = env::var("RLY_TUI").ok().map(|v| v != "0").unwrap_or(true);
     if enable_tui {
         let app = app_state.clone();
-        tokio::spawn(async move { run_terminal_dashboard(app).await; });
+        tokio::spawn(async move { run_terminal_dashboard(app, cache).await; });
     }
 
     // HTTP server
     let (addr, router) = {
-        let cfg = app_state.cfg.read().await;
+        let snap = app_state.snapshot();
+        let cfg = &snap.cfg;
         let addr: SocketAddr = format!("{}:{}", cfg.server.bind_addr, cfg.server.port).parse()?;
-        let router = Router::new()
-            .route("/", get(relay::health).post(relay::relay))
-            .route("/status", get(relay::status))
+        let protected = Documented::new(true)
+            .route("/status", api_spec::STATUS, get(relay::status))
+            .route("/tx/:hash", api_spec::TX_TRACK, get(tx_track::tx_status))
+            .route("/stats/origins", api_spec::STATS_ORIGINS, get(origin_stats::origins))
+            .route("/admin/drain", api_spec::ADMIN_DRAIN, post(admin::drain))
+            .route("/admin/ban", api_spec::ADMIN_BAN, post(admin::ban))
+            .route("/admin/unban", api_spec::ADMIN_UNBAN, post(admin::unban))
+            .route("/admin/snapshot", api_spec::ADMIN_SNAPSHOT, post(admin::snapshot))
+            .route("/backfill", api_spec::BACKFILL, post(backfill::backfill))
+            .map_router(|r| r.route_layer(middleware::from_fn_with_state(http_state.clone(), auth::require_auth)));
+        let router = Documented::new(false)
+            .route("/", api_spec::HEALTH, get(relay::health).post(relay::relay))
+            .route("/readyz", api_spec::READYZ, get(relay::readyz))
+            .route("/graphql", api_spec::GRAPHQL, post(graphql::graphql))
+            .route("/v1/block/:block", api_spec::REST_BLOCK, get(rest::block))
+            .route("/v1/balance/:address", api_spec::REST_BALANCE, get(rest::balance))
+            .route("/v1/tx/:hash", api_spec::REST_TX, get(rest::tx))
+            .route("/v1/tx/:hash/receipt", api_spec::REST_RECEIPT, get(rest::receipt));
+        #[cfg(feature = "grpc")]
+        let router = router.route(grpc::PATH, api_spec::GRPC, post(grpc::call));
+        let router = router
+            .merge(protected)
+            .finish()
             .with_state(http_state);
         (addr, router)
     };
@@ -96,12 +202,19 @@ async fn main() -> Result<()> {
 async fn watch_config_and_apply(cfg_path: PathBuf, app: Arc<AppState>) -> Result<()> {
     use tokio::sync::mpsc;
     let (tx, mut rx) = mpsc::channel::<()>(8);
+    let tx_secrets = tx.clone();
 
+    // Only events for the config file itself, or Kubernetes' "..data" symlink swap for a
+    // mounted ConfigMap; editors and other tools touch plenty of neighbours.
+    let cfg_name = cfg_path.file_name().map(|n| n.to_os_string());
     let mut watcher: RecommendedWatcher = Watcher::new(
         move |res: Result<Event, notify::Error>| {
             if let Ok(ev) = res {
+                let relevant = ev.paths.iter().filter_map(|p| p.file_name()).any(|n| {
+                    Some(n) == cfg_name.as_deref() || n.to_string_lossy().starts_with("..")
+                });
                 match ev.kind {
-                    EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_) => {
+                    EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_) if relevant => {
                         let _ = tx.try_send(());
                     }
                     _ => {}
@@ -114,31 +227,97 @@ async fn watch_config_and_apply(cfg_path: PathBuf, app: Arc<AppState>) -> Result
     let watch_dir = cfg_path.parent().unwrap_or_else(|| std::path::Path::new("."));
     watcher.watch(watch_dir, RecursiveMode::NonRecursive)?;
 
+    // Secret files usually live outside the config dir (k8s mounts, Vault agent);
+    // poll their contents and trigger the same reload path on rotation.
+    {
+        let app = app.clone();
+        let tx = tx_secrets;
+        tokio::spawn(async move {
+            let mut last: Option<u64> = None;
+            loop {
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                let files = app.snapshot().cfg.secret_files();
+                let digest = hash_files(&files);
+                if last.is_some() && last != Some(digest) {
+                    info!("secret file changed; reloading config");
+                    let _ = tx.try_send(());
+                }
+                last = Some(digest);
+            }
+        });
+    }
+
+    let mut consecutive_failures = 0u32;
+    let digest = |files: Vec<PathBuf>| hash_files(&[vec![cfg_path.clone()], files].concat());
+    let mut last_digest = digest(app.snapshot().cfg.secret_files());
     loop {
         rx.recv().await;
-        match Config::load_from_path(&cfg_path) {
+        // One save is often several events (truncate, write, rename); act once things go quiet.
+        while tokio::time::timeout(RELOAD_DEBOUNCE, rx.recv()).await.is_ok() {}
+        let current = digest(app.snapshot().cfg.secret_files());
+        if current == last_digest {
+            debug!("config watcher event without content change; not reloading");
+            continue;
+        }
+        last_digest = current;
+        match Config::load_from_path(&cfg_path).and_then(|c| c.validate().map(|_| c)) {
             Ok(new_cfg) => {
-                // swap config
-                {
-                    let mut cfg_guard = app.cfg.write().await;
-                    *cfg_guard = new_cfg.clone();
+                let current = app.snapshot();
+                let changes = config_diff::diff(&current.cfg, &new_cfg);
+                if changes.is_empty() {
+                    info!("config reloaded; no effective changes");
                 }
-                // update breaker cfg
-                {
-                    let mut bcfg = app.breaker_cfg.write().await;
-                    bcfg.ban_error_threshold = new_cfg.relay.ban_error_threshold;
-                    bcfg.ban_seconds = new_cfg.relay.ban_seconds;
+                for c in &changes {
+                    info!(setting = %c.path, old = %c.old, new = %c.new, "config change");
                 }
-                // reconcile providers
-                {
-                    let mut reg = app.registry.write().await;
-                    reconcile_registry(&mut reg, &new_cfg.rpc_endpoints);
+                consecutive_failures = 0;
+                reporting::configure(&new_cfg.error_reporting, &new_cfg.network);
+                // Building providers (an HTTP client with its TLS setup each) is slow enough to stall
+                // a runtime worker, so it runs on the blocking pool; requests keep using the current
+                // snapshot, providers untouched, meanwhile. Config, breaker settings, providers and
+                // the kept providers' new settings then go live together in the swap.
+                let retired: Vec<_> = app.retired().into_iter().map(|(p, _)| p).collect();
+                let built = tokio::task::spawn_blocking(move || {
+                    let r = reconcile_registry(&current.registry, &retired, &new_cfg.rpc_endpoints, &new_cfg.http_client, &new_cfg.relay.default_headers);
+                    (Snapshot::new(new_cfg, r.registry).with_staged(r.staged), r.added, r.removed)
+                })
+                .await;
+                let (next, added, removed) = match built {
+                    Ok(v) => v,
+                    Err(e) => {
+                        error!("failed to build providers for the new config (previous config stays active): {}", e);
+                        continue;
+                    }
+                };
+                app.swap(next);
+                app.retire(removed);
+                if !added.is_empty() {
+                    tokio::spawn(health::warm_up(added, app.clone()));
                 }
                 info!("applied new config (hot reload)");
             }
             Err(e) => {
-                error!("failed to reload config: {:?}", e);
+                error!("failed to reload config (previous config stays active): {:?}", e);
+                consecutive_failures += 1;
+                let threshold = app.snapshot().cfg.error_reporting.reload_failure_threshold.max(1);
+                if consecutive_failures >= threshold {
+                    reporting::report(
+                        "reload_failed",
+                        format!("config reload failed {} times in a row: {}", consecutive_failures, e),
+                        serde_json::json!({ "path": cfg_path.display().to_string() }),
+                    );
+                }
             }
         }
     }
 }
+
+fn hash_files(files: &[PathBuf]) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut h = std::collections::hash_map::DefaultHasher::new();
+    for f in files {
+        f.hash(&mut h);
+        std::fs::read(f).ok().hash(&mut h);
+    }
+    h.finish()
+}
@@ -0,0 +1,93 @@
+use crate::relay::TtlCache;
+use crate::state::AppState;
+use serde_json::{json, Value};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+use tokio::time::{sleep, Duration};
+use tracing::{info, warn};
+
+// Set by the memory guard loop while RSS is over memory_guard.max_rss_mb.
+static PRESSURE: AtomicBool = AtomicBool::new(false);
+static SHED: AtomicU64 = AtomicU64::new(0);
+
+const CHECK_EVERY: Duration = Duration::from_secs(1);
+
+pub fn under_pressure() -> bool { PRESSURE.load(Ordering::Relaxed) }
+
+/// Count one call turned away under memory pressure.
+pub fn record_shed() {
+    SHED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Resident set size from /proc (Linux); None elsewhere.
+#[cfg(unix)]
+pub fn rss_bytes() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    // SAFETY: sysconf has no preconditions.
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    Some(pages * u64::try_from(page).ok()?)
+}
+
+#[cfg(not(unix))]
+pub fn rss_bytes() -> Option<u64> { None }
+
+/// The "memory" object of /status; null while memory_guard is off.
+pub fn to_json(app: &AppState) -> Value {
+    let snap = app.snapshot();
+    let Some(mg) = &snap.cfg.memory_guard else { return Value::Null };
+    json!({
+        "rss_mb": rss_bytes().map(|b| b / (1024 * 1024)),
+        "max_rss_mb": mg.max_rss_mb,
+        "under_pressure": under_pressure(),
+        "shed": SHED.load(Ordering::Relaxed),
+    })
+}
+
+/// Watch RSS against memory_guard (re-read each tick, so reloads apply). Once over
+/// max_rss_mb, shed the configured methods until RSS is back under resume_pct of the
+/// limit; halve the cache on every tick RSS is still at or over max_rss_mb itself.
+pub async fn memory_guard_loop(app: Arc<AppState>, cache: TtlCache) {
+    let mut unsupported_logged = false;
+    loop {
+        sleep(CHECK_EVERY).await;
+        let Some(mg) = app.snapshot().cfg.memory_guard.clone() else {
+            set_pressure(false, 0, 0);
+            continue;
+        };
+        let Some(rss) = rss_bytes() else {
+            if !unsupported_logged {
+                warn!("memory_guard is configured but RSS can't be read on this platform; it stays inactive");
+                unsupported_logged = true;
+            }
+            continue;
+        };
+        let max = mg.max_rss_mb.saturating_mul(1024 * 1024);
+        let resume = (max as f64 * mg.resume_pct.clamp(0.0, 100.0) / 100.0) as u64;
+        let on = if under_pressure() { rss >= resume } else { rss >= max };
+        set_pressure(on, rss, max);
+        if on && rss >= max {
+            let freed = cache.shrink().await;
+            info!(rss_mb = rss / (1024 * 1024), freed_bytes = freed, "memory pressure: halved the response cache");
+        }
+    }
+}
+
+fn set_pressure(on: bool, rss: u64, max: u64) {
+    if PRESSURE.swap(on, Ordering::Relaxed) == on {
+        return;
+    }
+    let (rss_mb, max_rss_mb) = (rss / (1024 * 1024), max / (1024 * 1024));
+    if on {
+        warn!(rss_mb, max_rss_mb, "memory pressure: shedding memory_guard.shed_methods and shrinking the cache");
+        crate::reporting::report(
+            "memory_pressure",
+            format!("relay RSS {} MiB is over memory_guard.max_rss_mb {}", rss_mb, max_rss_mb),
+            json!({ "rss_mb": rss_mb, "max_rss_mb": max_rss_mb }),
+        );
+    } else {
+        info!(rss_mb, "memory pressure over; serving everything again");
+    }
+}
@@ -0,0 +1,222 @@
+use crate::config::{InfluxApi, InfluxConfig, StatsdConfig};
+use crate::state::AppState;
+use reqwest::Client;
+use std::{
+    collections::HashMap,
+    sync::{atomic::Ordering, Arc},
+};
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, Duration};
+use tracing::debug;
+
+// Keep datagrams under a typical MTU.
+const MAX_PACKET: usize = 1432;
+
+/// Point-in-time view of relay and provider counters shared by the push exporters.
+pub struct MetricsSnapshot {
+    pub network: String,
+    pub total_calls: u64,
+    pub cache_hits: u64,
+    pub providers: Vec<ProviderSample>,
+}
+
+pub struct ProviderSample {
+    pub id: String,
+    pub label: String,
+    pub healthy: bool,
+    pub banned: bool,
+    pub latency_ms: Option<u64>,
+    pub latest_block: u64,
+    pub behind: u64,
+    pub calls: u64,
+    pub errors: u64,
+}
+
+impl MetricsSnapshot {
+    pub async fn collect(app: &AppState) -> Self {
+        let snap = app.snapshot();
+        let network = snap.cfg.network.clone();
+        let providers = snap.registry.all();
+        Self {
+            network,
+            total_calls: app.total_calls.load(Ordering::Relaxed),
+            cache_hits: app.cache_hits.load(Ordering::Relaxed),
+            providers: providers
+                .iter()
+                .map(|p| {
+                    let lat = p.get_latency();
+                    ProviderSample {
+                        id: p.id.clone(),
+                        label: p.label(),
+                        healthy: p.is_healthy(),
+                        banned: p.breaker_is_banned(),
+                        latency_ms: (lat != u64::MAX).then_some(lat),
+                        latest_block: p.get_latest_block(),
+                        behind: p.get_behind(),
+                        calls: p.call_count.load(Ordering::Relaxed),
+                        errors: p.errors.load(Ordering::Relaxed),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Push metrics to StatsD / DogStatsD over UDP while `metrics.statsd` is configured.
+pub async fn statsd_loop(app: Arc<AppState>) {
+    let mut socket: Option<UdpSocket> = None;
+    // last pushed cumulative values, so counters are sent as deltas
+    let mut last: HashMap<String, u64> = HashMap::new();
+
+    loop {
+        let scfg = app.snapshot().cfg.metrics.statsd.clone();
+        let Some(scfg) = scfg else {
+            sleep(Duration::from_secs(5)).await;
+            continue;
+        };
+        if socket.is_none() {
+            socket = UdpSocket::bind("0.0.0.0:0").await.map_err(|e| debug!("statsd bind failed: {}", e)).ok();
+        }
+        if let Some(sock) = socket.as_ref() {
+            let snap = MetricsSnapshot::collect(&app).await;
+            let lines = statsd_lines(&snap, &scfg, &mut last);
+            for packet in pack(&lines) {
+                if let Err(e) = sock.send_to(packet.as_bytes(), scfg.addr.as_str()).await {
+                    debug!(addr = %scfg.addr, "statsd send failed: {}", e);
+                    break;
+                }
+            }
+        }
+        sleep(Duration::from_secs(scfg.interval_s.max(1))).await;
+    }
+}
+
+fn statsd_lines(snap: &MetricsSnapshot, cfg: &StatsdConfig, last: &mut HashMap<String, u64>) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut delta = |key: String, now: u64| -> u64 {
+        let prev = last.insert(key, now).unwrap_or(now);
+        now.saturating_sub(prev)
+    };
+
+    let mut base_tags = cfg.tags.clone();
+    base_tags.push(format!("network:{}", snap.network));
+
+    let line = |name: &str, value: String, kind: &str, provider: Option<&str>| -> String {
+        match (cfg.dogstatsd, provider) {
+            (true, p) => {
+                let mut tags = base_tags.clone();
+                if let Some(p) = p { tags.push(format!("provider:{}", p)); }
+                format!("{}.{}:{}|{}|#{}", cfg.prefix, name, value, kind, tags.join(","))
+            }
+            // plain StatsD has no tags; fold the provider into the metric path
+            (false, Some(p)) => format!("{}.provider.{}.{}:{}|{}", cfg.prefix, sanitize(p), name, value, kind),
+            (false, None) => format!("{}.{}:{}|{}", cfg.prefix, name, value, kind),
+        }
+    };
+
+    out.push(line("relay.calls", delta("relay.calls".into(), snap.total_calls).to_string(), "c", None));
+    out.push(line("relay.cache_hits", delta("relay.cache_hits".into(), snap.cache_hits).to_string(), "c", None));
+
+    for p in &snap.providers {
+        let id = p.label.as_str();
+        out.push(line("provider.calls", delta(format!("calls:{}", p.id), p.calls).to_string(), "c", Some(id)));
+        out.push(line("provider.errors", delta(format!("errors:{}", p.id), p.errors).to_string(), "c", Some(id)));
+        out.push(line("provider.healthy", u8::from(p.healthy).to_string(), "g", Some(id)));
+        out.push(line("provider.banned", u8::from(p.banned).to_string(), "g", Some(id)));
+        out.push(line("provider.behind", p.behind.to_string(), "g", Some(id)));
+        out.push(line("provider.latest_block", p.latest_block.to_string(), "g", Some(id)));
+        if let Some(lat) = p.latency_ms {
+            out.push(line("provider.latency_ms", lat.to_string(), "g", Some(id)));
+        }
+    }
+    out
+}
+
+/// Write metrics to InfluxDB (v1 `/write` or v2 `/api/v2/write`) while `metrics.influx` is configured.
+pub async fn influx_loop(app: Arc<AppState>) {
+    let client = Client::builder().timeout(Duration::from_secs(10)).build().unwrap_or_else(|_| Client::new());
+    loop {
+        let icfg = app.snapshot().cfg.metrics.influx.clone();
+        let Some(icfg) = icfg else {
+            sleep(Duration::from_secs(5)).await;
+            continue;
+        };
+        let snap = MetricsSnapshot::collect(&app).await;
+        let body = influx_lines(&snap, &icfg).join("\n");
+        if let Err(e) = influx_write(&client, &icfg, body).await {
+            debug!(url = %icfg.url, "influx write failed: {}", e);
+        }
+        sleep(Duration::from_secs(icfg.interval_s.max(1))).await;
+    }
+}
+
+async fn influx_write(client: &Client, cfg: &InfluxConfig, body: String) -> anyhow::Result<()> {
+    let base = cfg.url.trim_end_matches('/');
+    let req = match cfg.api {
+        InfluxApi::V1 => {
+            let mut q = vec![("precision", "s".to_string())];
+            if let Some(db) = &cfg.database { q.push(("db", db.clone())); }
+            if let Some(u) = &cfg.username { q.push(("u", u.clone())); }
+            if let Some(p) = &cfg.password { q.push(("p", p.clone())); }
+            client.post(format!("{}/write", base)).query(&q)
+        }
+        InfluxApi::V2 => {
+            let mut q = vec![("precision", "s".to_string())];
+            if let Some(org) = &cfg.org { q.push(("org", org.clone())); }
+            if let Some(b) = &cfg.bucket { q.push(("bucket", b.clone())); }
+            let mut r = client.post(format!("{}/api/v2/write", base)).query(&q);
+            if let Some(t) = &cfg.token { r = r.header("Authorization", format!("Token {}", t)); }
+            r
+        }
+    };
+    req.header("Content-Type", "text/plain; charset=utf-8").body(body).send().await?.error_for_status()?;
+    Ok(())
+}
+
+fn influx_lines(snap: &MetricsSnapshot, cfg: &InfluxConfig) -> Vec<String> {
+    let ts = crate::timing::epoch_secs();
+    let net = influx_escape(&snap.network);
+    let mut out = vec![format!(
+        "{}_relay,network={} total_calls={}i,cache_hits={}i {}",
+        cfg.measurement_prefix, net, snap.total_calls, snap.cache_hits, ts
+    )];
+    for p in &snap.providers {
+        let mut fields = format!(
+            "healthy={}i,banned={}i,behind={}i,latest_block={}i,calls={}i,errors={}i",
+            u8::from(p.healthy), u8::from(p.banned), p.behind, p.latest_block, p.calls, p.errors
+        );
+        if let Some(lat) = p.latency_ms {
+            fields.push_str(&format!(",latency_ms={}i", lat));
+        }
+        out.push(format!(
+            "{}_provider,network={},provider={} {} {}",
+            cfg.measurement_prefix, net, influx_escape(&p.label), fields, ts
+        ));
+    }
+    out
+}
+
+// Tag keys/values must escape commas, equals signs and spaces.
+fn influx_escape(s: &str) -> String {
+    s.replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ")
+}
+
+fn pack(lines: &[String]) -> Vec<String> {
+    let mut packets = Vec::new();
+    let mut cur = String::new();
+    for l in lines {
+        if !cur.is_empty() && cur.len() + 1 + l.len() > MAX_PACKET {
+            packets.push(std::mem::take(&mut cur));
+        }
+        if !cur.is_empty() { cur.push('\n'); }
+        cur.push_str(l);
+    }
+    if !cur.is_empty() { packets.push(cur); }
+    packets
+}
+
+/// Metric-path-safe version of a provider identifier.
+pub fn sanitize(s: &str) -> String {
+    let s = s.trim_start_matches("https://").trim_start_matches("http://");
+    s.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect()
+}
@@ -0,0 +1,54 @@
+use serde_json::{Map, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// Anything else (custom or mistyped prefixes) is folded into "other", so client input
+// can't grow the table.
+const NAMESPACES: [&str; 8] = ["eth", "net", "web3", "debug", "trace", "txpool", "rly", "other"];
+
+#[derive(Default)]
+struct Counters {
+    calls: AtomicU64,
+    errors: AtomicU64, // error replies, whether from a provider or the relay
+    cache_hits: AtomicU64,
+    total_ms: AtomicU64,
+}
+
+/// Per-namespace (method prefix before the first '_') aggregates for /status.
+#[derive(Default)]
+pub struct NamespaceStats {
+    slots: [Counters; NAMESPACES.len()],
+}
+
+impl NamespaceStats {
+    pub fn record(&self, method: &str, error: bool, cache_hit: bool, elapsed: Duration) {
+        let ns = method.split_once('_').map_or("", |(ns, _)| ns);
+        let i = NAMESPACES.iter().position(|&n| n == ns).unwrap_or(NAMESPACES.len() - 1);
+        let c = &self.slots[i];
+        c.calls.fetch_add(1, Ordering::Relaxed);
+        if error { c.errors.fetch_add(1, Ordering::Relaxed); }
+        if cache_hit { c.cache_hits.fetch_add(1, Ordering::Relaxed); }
+        c.total_ms.fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Namespaces that have seen traffic.
+    pub fn to_json(&self) -> Value {
+        let rows: Vec<(&str, u64, u64, u64, u64)> = NAMESPACES
+            .iter()
+            .zip(&self.slots)
+            .map(|(ns, c)| {
+                let load = |a: &AtomicU64| a.load(Ordering::Relaxed);
+                (*ns, load(&c.calls), load(&c.errors), load(&c.cache_hits), load(&c.total_ms))
+            })
+            .filter(|r| r.1 > 0)
+            .collect();
+        let mut out = Map::new();
+        for (ns, calls, errors, cache_hits, total_ms) in rows {
+            out.insert(
+                ns.to_string(),
+                serde_json::json!({ "calls": calls, "errors": errors, "cache_hits": cache_hits, "avg_ms": total_ms / calls }),
+            );
+        }
+        Value::Object(out)
+    }
+}
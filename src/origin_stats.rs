@@ -0,0 +1,78 @@
+use crate::config::OriginStatsConfig;
+use crate::relay::HttpState;
+use axum::{extract::State, http::{HeaderMap, StatusCode}, Json};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+
+const UNTAGGED: &str = "untagged"; // no X-RLY-Origin, or a caller not allowed to set one
+const OTHER: &str = "other";       // past max_origins / max_methods, or not a usable name
+const MAX_NAME_LEN: usize = 64;
+
+#[derive(Default)]
+struct Counters {
+    calls: u64,
+    errors: u64,
+    cache_hits: u64,
+    total_ms: u64,
+    methods: HashMap<String, u64>,
+}
+
+/// Per-origin (X-RLY-Origin) call counters for GET /stats/origins. Bounded by
+/// origin_stats.max_origins / max_methods so client input can't grow it without limit.
+#[derive(Default)]
+pub struct OriginStats {
+    origins: parking_lot::Mutex<HashMap<String, Counters>>,
+}
+
+impl OriginStats {
+    pub fn record(&self, cfg: &OriginStatsConfig, origin: &str, method: &str, error: bool, cache_hit: bool, elapsed: Duration) {
+        let mut origins = self.origins.lock();
+        let key = if origins.contains_key(origin) || origins.len() < cfg.max_origins { origin } else { OTHER };
+        let c = origins.entry(key.to_string()).or_default();
+        c.calls += 1;
+        if error { c.errors += 1; }
+        if cache_hit { c.cache_hits += 1; }
+        c.total_ms += elapsed.as_millis() as u64;
+        let method = if c.methods.contains_key(method) || c.methods.len() < cfg.max_methods { method } else { OTHER };
+        *c.methods.entry(method.to_string()).or_default() += 1;
+    }
+
+    pub fn to_json(&self) -> Value {
+        let origins = self.origins.lock();
+        let mut out = Map::new();
+        for (origin, c) in origins.iter() {
+            out.insert(
+                origin.clone(),
+                json!({
+                    "calls": c.calls,
+                    "errors": c.errors,
+                    "cache_hits": c.cache_hits,
+                    "avg_ms": c.total_ms / c.calls.max(1),
+                    "methods": c.methods,
+                }),
+            );
+        }
+        Value::Object(out)
+    }
+}
+
+/// The origin a call is counted under: its X-RLY-Origin if the caller may set one (any
+/// caller when trusted_keys is empty, else only those sending a listed X-RLY-Key).
+pub fn origin_of<'a>(cfg: &OriginStatsConfig, headers: &'a HeaderMap) -> &'a str {
+    let trusted = cfg.trusted_keys.is_empty() || crate::auth::has_trusted_key(&cfg.trusted_keys, headers);
+    let origin = headers.get("x-rly-origin").and_then(|v| v.to_str().ok()).map(str::trim);
+    match origin.filter(|_| trusted) {
+        None | Some("") => UNTAGGED,
+        Some(o) if o.len() <= MAX_NAME_LEN && o.bytes().all(|b| b.is_ascii_alphanumeric() || b"._-:/".contains(&b)) => o,
+        Some(_) => OTHER,
+    }
+}
+
+/// GET /stats/origins
+pub async fn origins(State(state): State<HttpState>) -> (StatusCode, Json<Value>) {
+    if state.app.snapshot().cfg.origin_stats.is_none() {
+        return (StatusCode::NOT_FOUND, Json(json!({ "error": "origin_stats is not enabled" })));
+    }
+    (StatusCode::OK, Json(json!({ "origins": state.app.origins.to_json() })))
+}
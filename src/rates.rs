@@ -0,0 +1,65 @@
+use serde_json::{json, Value};
+use std::time::Instant;
+
+/// The windows rates are reported over, as (label, seconds).
+pub const WINDOWS: [(&str, u64); 3] = [("1m", 60), ("5m", 300), ("15m", 900)];
+const SLOTS: usize = 900; // one per second of the longest window
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Slot {
+    sec: u64, // seconds since the tracker started; a stale slot is reset on reuse
+    calls: u32,
+    errors: u32,
+}
+
+/// Completed upstream attempts of one provider in one-second buckets, for request and
+/// error rates over the WINDOWS instead of the TUI's tick-to-tick deltas.
+#[derive(Debug)]
+pub struct RateWindows {
+    started: Instant,
+    slots: Box<[Slot; SLOTS]>,
+}
+
+impl Default for RateWindows {
+    fn default() -> Self { Self { started: Instant::now(), slots: Box::new([Slot::default(); SLOTS]) } }
+}
+
+impl RateWindows {
+    pub fn record(&mut self, ok: bool) {
+        let sec = self.started.elapsed().as_secs();
+        let slot = &mut self.slots[sec as usize % SLOTS];
+        if slot.sec != sec {
+            *slot = Slot { sec, calls: 0, errors: 0 };
+        }
+        slot.calls += 1;
+        if !ok { slot.errors += 1; }
+    }
+
+    /// (requests, errors) per second over the last `window_s` seconds. Only time since the
+    /// tracker started counts, so a fresh provider isn't diluted by time it didn't exist.
+    pub fn per_second(&self, window_s: u64) -> (f64, f64) {
+        let elapsed = self.started.elapsed();
+        let now = elapsed.as_secs();
+        let oldest = now.saturating_sub(window_s.min(SLOTS as u64) - 1);
+        let (calls, errors) = self
+            .slots
+            .iter()
+            .filter(|s| s.sec >= oldest && s.sec <= now)
+            .fold((0u64, 0u64), |(c, e), s| (c + s.calls as u64, e + s.errors as u64));
+        let span = elapsed.as_secs_f64().clamp(1.0, window_s as f64);
+        (calls as f64 / span, errors as f64 / span)
+    }
+
+    /// {"1m": {"requests_per_s", "errors_per_s"}, "5m": ..., "15m": ...} for /status.
+    pub fn to_json(&self) -> Value {
+        let round = |x: f64| (x * 1000.0).round() / 1000.0;
+        let windows: serde_json::Map<String, Value> = WINDOWS
+            .iter()
+            .map(|&(label, secs)| {
+                let (rps, eps) = self.per_second(secs);
+                (label.to_string(), json!({ "requests_per_s": round(rps), "errors_per_s": round(eps) }))
+            })
+            .collect();
+        Value::Object(windows)
+    }
+}
@@ -0,0 +1,232 @@
+//! Just enough RLP to read the fields of a signed transaction that broadcast_preflight
+//! checks: legacy and typed (EIP-2930, EIP-1559, EIP-4844, EIP-7702) envelopes.
+
+use crate::config::BroadcastPreflightConfig;
+use crate::keccak::{decode_hex, encode_hex};
+
+const GWEI: f64 = 1e9;
+
+/// The fields of a signed transaction the relay looks at.
+#[derive(Debug)]
+pub struct RawTx {
+    pub chain_id: Option<u64>, // None: legacy without EIP-155 replay protection
+    pub nonce: u64,
+    pub gas_limit: u64,
+    pub max_fee: u128,                  // gasPrice for legacy and EIP-2930
+    pub max_priority_fee: Option<u128>, // EIP-1559 and later
+    pub to: Option<String>,             // 0x-hex; None for contract creation
+    pub value: u128,
+    pub input_len: usize,
+}
+
+/// Decode `raw`; Ok(None) for a transaction type this doesn't know.
+pub fn decode(raw: &[u8]) -> Result<Option<RawTx>, String> {
+    let (&first, rest) = raw.split_first().ok_or("empty transaction")?;
+    if first >= 0xc0 {
+        let f = fields(raw)?;
+        let v = uint(&f, 6)?;
+        let chain_id = (v >= 35).then(|| ((v - 35) / 2) as u64);
+        return Ok(Some(RawTx { chain_id, max_priority_fee: None, ..common(&f, 0, 2, 1)? }));
+    }
+    let f = match first {
+        1 | 2 | 4 => fields(rest)?,
+        // the network form wraps the transaction with its blobs: [tx, blobs, commitments, proofs]
+        3 => match fields(rest)?.first() {
+            Some(Item::List(inner)) => items(inner)?,
+            _ => fields(rest)?,
+        },
+        _ => return Ok(None),
+    };
+    let chain_id = Some(u64_at(&f, 0)?);
+    let tx = match first {
+        1 => RawTx { chain_id, max_priority_fee: None, ..common(&f, 1, 3, 2)? },
+        _ => RawTx { chain_id, max_priority_fee: Some(uint(&f, 2)?), ..common(&f, 1, 4, 3)? },
+    };
+    Ok(Some(tx))
+}
+
+/// Check an eth_sendRawTransaction param against the policy; Err says what's wrong.
+/// Transaction types this doesn't know pass unchecked.
+pub fn preflight(pf: &BroadcastPreflightConfig, raw_hex: Option<&str>) -> Result<(), String> {
+    let raw = raw_hex.and_then(decode_hex).ok_or("params[0] must be a hex-encoded signed transaction")?;
+    let Some(tx) = decode(&raw).map_err(|e| format!("transaction does not decode: {}", e))? else { return Ok(()) };
+    match (pf.chain_id, tx.chain_id) {
+        (Some(want), Some(got)) if want != got => return Err(format!("transaction is signed for chain id {}, this relay serves chain id {}", got, want)),
+        (_, None) if pf.require_chain_id => return Err("legacy transaction without a chain id (pre-EIP-155) is not accepted".to_string()),
+        _ => {}
+    }
+    if tx.gas_limit < pf.min_gas_limit {
+        return Err(format!("gas limit {} is below the minimum {}", tx.gas_limit, pf.min_gas_limit));
+    }
+    if let Some(max) = pf.max_gas_limit.filter(|&max| tx.gas_limit > max) {
+        return Err(format!("gas limit {} is above the maximum {}", tx.gas_limit, max));
+    }
+    if tx.max_priority_fee.is_some_and(|tip| tip > tx.max_fee) {
+        return Err("maxPriorityFeePerGas is higher than maxFeePerGas".to_string());
+    }
+    let fee_gwei = tx.max_fee as f64 / GWEI;
+    if let Some(min) = pf.min_fee_gwei.filter(|&min| fee_gwei < min) {
+        return Err(format!("fee cap {} gwei is below the minimum {} gwei", fee_gwei, min));
+    }
+    if let Some(max) = pf.max_fee_gwei.filter(|&max| fee_gwei > max) {
+        return Err(format!("fee cap {} gwei is above the maximum {} gwei", fee_gwei, max));
+    }
+    Ok(())
+}
+
+// nonce, gas and fee at the given positions; to, value and input follow gas in every type.
+fn common(f: &[Item], nonce: usize, gas: usize, fee: usize) -> Result<RawTx, String> {
+    let (to, input) = match (f.get(gas + 1), f.get(gas + 3)) {
+        (Some(Item::Bytes(to)), Some(Item::Bytes(input))) => (to, input),
+        _ => return Err("malformed to/value/input fields".to_string()),
+    };
+    Ok(RawTx {
+        chain_id: None,
+        nonce: u64_at(f, nonce)?,
+        gas_limit: u64_at(f, gas)?,
+        max_fee: uint(f, fee)?,
+        max_priority_fee: None,
+        to: (!to.is_empty()).then(|| encode_hex(to)),
+        value: uint(f, gas + 2)?,
+        input_len: input.len(),
+    })
+}
+
+enum Item<'a> {
+    Bytes(&'a [u8]),
+    List(&'a [u8]), // payload, still encoded
+}
+
+// The members of the single RLP list that makes up all of `buf`.
+fn fields(buf: &[u8]) -> Result<Vec<Item<'_>>, String> {
+    match item(buf)? {
+        (Item::List(payload), []) => items(payload),
+        (Item::List(_), _) => Err("trailing bytes after the transaction".to_string()),
+        (Item::Bytes(_), _) => Err("expected an RLP list".to_string()),
+    }
+}
+
+fn items(mut payload: &[u8]) -> Result<Vec<Item<'_>>, String> {
+    let mut out = Vec::new();
+    while !payload.is_empty() {
+        let (it, rest) = item(payload)?;
+        out.push(it);
+        payload = rest;
+    }
+    Ok(out)
+}
+
+// The first item of `buf` and what follows it.
+fn item(buf: &[u8]) -> Result<(Item<'_>, &[u8]), String> {
+    let truncated = || "truncated RLP".to_string();
+    let (&b, rest) = buf.split_first().ok_or_else(truncated)?;
+    let (list, len, rest) = match b {
+        0x00..=0x7f => return Ok((Item::Bytes(&buf[..1]), rest)),
+        0x80..=0xb7 => (false, (b - 0x80) as usize, rest),
+        0xc0..=0xf7 => (true, (b - 0xc0) as usize, rest),
+        _ => {
+            let (list, len_of_len) = if b <= 0xbf { (false, (b - 0xb7) as usize) } else { (true, (b - 0xf7) as usize) };
+            if rest.len() < len_of_len || len_of_len > 8 {
+                return Err(truncated());
+            }
+            let len = rest[..len_of_len].iter().fold(0u64, |acc, &x| acc << 8 | x as u64);
+            (list, usize::try_from(len).map_err(|_| truncated())?, &rest[len_of_len..])
+        }
+    };
+    if rest.len() < len {
+        return Err(truncated());
+    }
+    let (body, rest) = rest.split_at(len);
+    Ok((if list { Item::List(body) } else { Item::Bytes(body) }, rest))
+}
+
+fn uint(f: &[Item], i: usize) -> Result<u128, String> {
+    match f.get(i) {
+        Some(Item::Bytes(b)) if b.len() <= 16 => Ok(b.iter().fold(0u128, |acc, &x| acc << 8 | x as u128)),
+        Some(Item::Bytes(_)) => Err(format!("field {} is too large", i)),
+        Some(Item::List(_)) => Err(format!("field {} is a list, expected a number", i)),
+        None => Err(format!("missing field {}", i)),
+    }
+}
+
+fn u64_at(f: &[Item], i: usize) -> Result<u64, String> {
+    u64::try_from(uint(f, i)?).map_err(|_| format!("field {} is too large", i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The EIP-155 example; the typed ones below are signed with the same key
+    // (0x4646...46, sender 0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f).
+    const LEGACY: &str = "0xf86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+    const EIP2930: &str = "0x01f8a501078506fc23ac0082c35094353535353535353535353535353535353535353582303984a9059cbbf838f794de0b295669a9fd93d5f28d9ec85e40f4cb697baee1a0000000000000000000000000000000000000000000000000000000000000000001a0d47644539acec3da5e3ecf5fe8863c628a9c97e8b71e9ea9167a6f4f83c03c32a0766b4bb8fc48c4f6e988b0828de7a64e475c686b77cff31cf187d9edc7ff6740";
+    const EIP1559: &str = "0x02f8750182012c84773594008509502f900082520894353535353535353535353535353535353535353588016345785d8a000080c001a0f30e4bd8094e53a679ddb8f55b5216b03c44623fc4279ef0791f9aa1f6930d49a037a864b98c9879affe00d9d0c53c8f38a5372994e266ac783514ed56851ceffc";
+    // EIP-1559 contract creation on chain 42161 with 100 bytes of init code
+    const CREATE: &str = "0x02f8ba82a4b180808405f5e100830f42408080b86400000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000c080a012faae608bd6562562b8f85564664cd1fdcd667f6b24b2b221ef86b9231f4d74a07c7c17a06fe7b62b13fec880f8508e70b19928987bbf5ebf2608d5d9ea1e935b";
+    const TO: &str = "0x3535353535353535353535353535353535353535";
+
+    fn raw(hex: &str) -> Vec<u8> { decode_hex(hex).unwrap() }
+
+    fn tx(hex: &str) -> RawTx { decode(&raw(hex)).unwrap().unwrap() }
+
+    #[test]
+    fn legacy() {
+        let t = tx(LEGACY);
+        assert_eq!((t.chain_id, t.nonce, t.gas_limit), (Some(1), 9, 21_000));
+        assert_eq!((t.max_fee, t.max_priority_fee), (20_000_000_000, None));
+        assert_eq!((t.to.as_deref(), t.value, t.input_len), (Some(TO), 1_000_000_000_000_000_000, 0));
+    }
+
+    #[test]
+    fn eip2930() {
+        let t = tx(EIP2930);
+        assert_eq!((t.chain_id, t.nonce, t.gas_limit), (Some(1), 7, 50_000));
+        assert_eq!((t.max_fee, t.max_priority_fee), (30_000_000_000, None));
+        assert_eq!((t.to.as_deref(), t.value, t.input_len), (Some(TO), 12_345, 4));
+    }
+
+    #[test]
+    fn eip1559() {
+        let t = tx(EIP1559);
+        assert_eq!((t.chain_id, t.nonce, t.gas_limit), (Some(1), 300, 21_000));
+        assert_eq!((t.max_fee, t.max_priority_fee), (40_000_000_000, Some(2_000_000_000)));
+        assert_eq!((t.to.as_deref(), t.value, t.input_len), (Some(TO), 100_000_000_000_000_000, 0));
+
+        let t = tx(CREATE);
+        assert_eq!((t.chain_id, t.nonce, t.gas_limit, t.max_priority_fee), (Some(42_161), 0, 1_000_000, Some(0)));
+        assert_eq!((t.to, t.value, t.input_len), (None, 0, 100));
+    }
+
+    #[test]
+    fn unknown_types_pass_undecoded() {
+        assert!(matches!(decode(&[0x05, 0xc0]), Ok(None)));
+        assert!(decode(&[]).is_err());
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        for hex in [LEGACY, EIP2930, EIP1559, CREATE] {
+            let full = raw(hex);
+            for n in 0..full.len() {
+                assert!(decode(&full[..n]).is_err(), "{} bytes of {}", n, &hex[..6]);
+            }
+            let mut longer = full.clone();
+            longer.push(0);
+            assert_eq!(decode(&longer).unwrap_err(), "trailing bytes after the transaction");
+        }
+    }
+
+    #[test]
+    fn oversized_lengths_are_rejected() {
+        // a list header claiming 64 KiB, and one claiming u64::MAX bytes
+        assert_eq!(decode(&[0xf9, 0xff, 0xff, 0x01]).unwrap_err(), "truncated RLP");
+        assert_eq!(decode(&[0xff; 12]).unwrap_err(), "truncated RLP");
+        assert_eq!(decode(&[0x02, 0xbb, 0xff, 0xff, 0xff, 0xff]).unwrap_err(), "truncated RLP");
+        // a 9-byte nonce and a 17-byte value
+        let nonce = raw("0xe889010101010101010101018252089435353535353535353535353535353535353535358080250101");
+        assert_eq!(decode(&nonce).unwrap_err(), "field 0 is too large");
+        let value = raw("0xf0010182520894353535353535353535353535353535353535353591010101010101010101010101010101010180250101");
+        assert_eq!(decode(&value).unwrap_err(), "field 4 is too large");
+    }
+}
@@ -0,0 +1,51 @@
+use reqwest::Url;
+
+// Query parameters whose values are treated as credentials.
+const SECRET_PARAMS: &[&str] = &[
+    "key", "apikey", "api_key", "api-key", "token", "access_token", "auth", "secret", "password", "pass", "x-api-key",
+];
+
+/// Redact credential-looking parts of a provider URL for display:
+/// userinfo, secret-named query params, and long opaque path segments
+/// (e.g. `/v3/<project-id>`, `/v2/<key>`).
+pub fn mask_url(raw: &str) -> String {
+    let Ok(mut url) = Url::parse(raw) else { return mask_opaque(raw) };
+
+    if !url.username().is_empty() || url.password().is_some() {
+        let _ = url.set_username("***");
+        let _ = url.set_password(None);
+    }
+
+    let segments: Option<Vec<String>> = url
+        .path_segments()
+        .map(|segs| segs.map(|s| if looks_like_secret(s) { "***".to_string() } else { s.to_string() }).collect());
+    if let Some(segs) = segments {
+        url.set_path(&segs.join("/"));
+    }
+
+    if url.query().is_some() {
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(k, v)| {
+                let secret = SECRET_PARAMS.contains(&k.to_ascii_lowercase().as_str()) || looks_like_secret(&v);
+                (k.into_owned(), if secret { "***".to_string() } else { v.into_owned() })
+            })
+            .collect();
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+
+    // Url serializes query "***" as "%2A%2A%2A"; keep it readable.
+    url.to_string().replace("%2A%2A%2A", "***")
+}
+
+/// Long tokens mixing letters and digits are almost always API keys or project ids.
+fn looks_like_secret(s: &str) -> bool {
+    s.len() >= 16
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && s.chars().any(|c| c.is_ascii_digit())
+        && s.chars().any(|c| c.is_ascii_alphabetic())
+}
+
+fn mask_opaque(raw: &str) -> String {
+    raw.split('/').map(|s| if looks_like_secret(s) { "***" } else { s }).collect::<Vec<_>>().join("/")
+}
@@ -1,59 +1,278 @@
-use crate::state::{AppState, ProviderRegistry, ProviderState};
-use axum::{extract::State, http::StatusCode, Json};
-use futures::stream::{FuturesUnordered, StreamExt};
-use reqwest::Client;
+use crate::state::{AppState, Candidates, HistoryDepth, ProviderRegistry, ProviderState, Snapshot};
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    body::Bytes,
+    Json,
+};
 use serde_json::{json, Value};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     sync::Arc,
     time::{Duration, Instant},
 };
 use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+use crate::circuit_breaker::BreakerConfig;
+use crate::config::{BroadcastTargets, CallOverridesConfig, Config, RelayConfig, WeightDecayConfig};
 
 // NEW: last-error classification
 use crate::error_reason::{self, ErrorReason};
+use crate::jsonrpc::{self, JsonRpcRequest, RequestBody};
+use crate::relay_error::RelayError;
+use crate::selector::{self, Selector};
+use crate::status_query::StatusQuery;
+use crate::timing::{self, Phases};
+use crate::token_bucket::TokenBucket;
+use crate::tx_track::{self, TxTracker};
+use crate::upstream;
 
 // ----------------------
 // TTL Cache (simple, per-entry)
 // ----------------------
+type CacheKey = (String, String); // (method, params)
+
 #[derive(Clone, Default)]
 pub struct TtlCache {
-    inner: Arc<RwLock<HashMap<(String, String), (Instant, Value)>>>,
+    inner: Arc<RwLock<CacheInner>>,
+    pinned: Arc<parking_lot::Mutex<PinnedLru>>, // pinned_cache: no expiry, evicted least-recently-used
+    // per-method (hits, misses); separate lock so stats never wait on a cache write
+    lookups: Arc<parking_lot::Mutex<HashMap<String, (u64, u64)>>>,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    entries: HashMap<CacheKey, (Instant, Value, usize)>, // (expiry, value, approx bytes)
+    bytes: usize,
+}
+
+impl CacheInner {
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some((_, _, size)) = self.entries.remove(key) {
+            self.bytes = self.bytes.saturating_sub(size);
+        }
+    }
+}
+
+#[derive(Default)]
+struct PinnedLru {
+    entries: HashMap<CacheKey, (Value, usize, u64)>, // (value, approx bytes, last-use tick)
+    by_use: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    bytes: usize,
+}
+
+impl PinnedLru {
+    fn touch(&mut self, key: &CacheKey) -> Option<Value> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (v, _, used) = self.entries.get_mut(key)?;
+        self.by_use.remove(used);
+        *used = tick;
+        self.by_use.insert(tick, key.clone());
+        Some(v.clone())
+    }
+
+    fn insert(&mut self, key: CacheKey, val: Value, max_entries: usize) {
+        if let Some((_, size, used)) = self.entries.remove(&key) {
+            self.by_use.remove(&used);
+            self.bytes = self.bytes.saturating_sub(size);
+        }
+        while self.entries.len() >= max_entries.max(1) {
+            let Some((_, oldest)) = self.by_use.pop_first() else { break };
+            if let Some((_, size, _)) = self.entries.remove(&oldest) {
+                self.bytes = self.bytes.saturating_sub(size);
+            }
+        }
+        self.tick += 1;
+        let size = key.0.len() + key.1.len() + approx_json_size(&val);
+        self.bytes += size;
+        self.by_use.insert(self.tick, key.clone());
+        self.entries.insert(key, (val, size, self.tick));
+    }
+}
+
+/// Point-in-time cache figures for the dashboard.
+pub struct CacheStats {
+    pub entries: usize,
+    pub approx_bytes: usize,
+    pub pinned_entries: usize,
+    pub pinned_bytes: usize,
+    pub per_method: Vec<(String, u64, u64)>, // (method, hits, misses), busiest first
 }
 
 impl TtlCache {
     pub fn new() -> Self {
-        Self { inner: Arc::new(RwLock::new(HashMap::new())) }
+        Self::default()
     }
 
-    pub async fn get(&self, key: &(String, String)) -> Option<Value> {
-        let mut guard = self.inner.write().await; // write to allow cleanup
-        if let Some((exp, v)) = guard.get(key) {
-            if *exp > Instant::now() {
-                return Some(v.clone());
-            } else {
-                guard.remove(key);
+    pub async fn get(&self, key: &CacheKey) -> Option<Value> {
+        let hit = {
+            let mut guard = self.inner.write().await; // write to allow cleanup
+            match guard.entries.get(key) {
+                Some((exp, v, _)) if *exp > Instant::now() => Some(v.clone()),
+                Some(_) => {
+                    guard.remove(key);
+                    None
+                }
+                None => None,
             }
-        }
-        None
+        };
+        self.count_lookup(key, hit.is_some());
+        hit
     }
 
-    pub async fn insert_with_ttl(&self, key: (String, String), val: Value, ttl: Duration) {
+    pub fn get_pinned(&self, key: &CacheKey) -> Option<Value> {
+        let hit = self.pinned.lock().touch(key);
+        self.count_lookup(key, hit.is_some());
+        hit
+    }
+
+    pub fn insert_pinned(&self, key: CacheKey, val: Value, max_entries: usize) {
+        self.pinned.lock().insert(key, val, max_entries);
+    }
+
+    /// Per-method counters are capped at LOOKUP_METHODS names (cache_ttl_default makes
+    /// any client-supplied method cacheable); later names are counted under "other".
+    fn count_lookup(&self, key: &CacheKey, hit: bool) {
+        const LOOKUP_METHODS: usize = 64;
+        let mut lookups = self.lookups.lock();
+        let name = if lookups.contains_key(&key.0) || lookups.len() < LOOKUP_METHODS { key.0.as_str() } else { "other" };
+        let counts = match lookups.get_mut(name) {
+            Some(counts) => counts,
+            None => lookups.entry(name.to_string()).or_default(),
+        };
+        if hit { counts.0 += 1 } else { counts.1 += 1 }
+    }
+
+    pub async fn insert_with_ttl(&self, key: CacheKey, val: Value, ttl: Duration) {
         let exp = Instant::now() + ttl;
-        self.inner.write().await.insert(key, (exp, val));
+        let size = key.0.len() + key.1.len() + approx_json_size(&val);
+        let mut guard = self.inner.write().await;
+        guard.remove(&key);
+        guard.bytes += size;
+        guard.entries.insert(key, (exp, val, size));
+    }
+
+    /// Drop expired entries; lookups only clean up the keys they touch.
+    pub async fn purge_expired(&self) {
+        let now = Instant::now();
+        let mut guard = self.inner.write().await;
+        let mut freed = 0;
+        guard.entries.retain(|_, (exp, _, size)| {
+            let keep = *exp > now;
+            if !keep { freed += *size; }
+            keep
+        });
+        guard.bytes = guard.bytes.saturating_sub(freed);
+    }
+
+    /// Drop about half of the cache: the TTL entries closest to expiry and the least
+    /// recently used pinned ones. Returns the approximate bytes freed.
+    pub async fn shrink(&self) -> usize {
+        let before = {
+            let mut guard = self.inner.write().await;
+            let before = guard.bytes;
+            let mut by_expiry: Vec<(Instant, CacheKey)> = guard.entries.iter().map(|(k, (exp, _, _))| (*exp, k.clone())).collect();
+            by_expiry.sort_by_key(|(exp, _)| *exp);
+            let half = by_expiry.len().div_ceil(2);
+            for (_, key) in by_expiry.into_iter().take(half) {
+                guard.remove(&key);
+            }
+            before - guard.bytes
+        };
+        let mut pinned = self.pinned.lock();
+        let bytes = pinned.bytes;
+        for _ in 0..pinned.entries.len().div_ceil(2) {
+            let Some((_, oldest)) = pinned.by_use.pop_first() else { break };
+            if let Some((_, size, _)) = pinned.entries.remove(&oldest) {
+                pinned.bytes = pinned.bytes.saturating_sub(size);
+            }
+        }
+        before + (bytes - pinned.bytes)
+    }
+
+    pub async fn stats(&self) -> CacheStats {
+        let (entries, approx_bytes) = {
+            let guard = self.inner.read().await;
+            (guard.entries.len(), guard.bytes)
+        };
+        let (pinned_entries, pinned_bytes) = {
+            let pinned = self.pinned.lock();
+            (pinned.entries.len(), pinned.bytes)
+        };
+        let mut per_method: Vec<_> = self.lookups.lock().iter().map(|(m, &(h, mi))| (m.clone(), h, mi)).collect();
+        per_method.sort_by(|a, b| (b.1 + b.2).cmp(&(a.1 + a.2)).then_with(|| a.0.cmp(&b.0)));
+        CacheStats { entries, approx_bytes, pinned_entries, pinned_bytes, per_method }
+    }
+}
+
+// Rough in-memory footprint of a JSON value (payload bytes plus per-node overhead).
+fn approx_json_size(v: &Value) -> usize {
+    const NODE: usize = std::mem::size_of::<Value>();
+    NODE + match v {
+        Value::String(s) => s.len(),
+        Value::Array(a) => a.iter().map(approx_json_size).sum(),
+        Value::Object(o) => o.iter().map(|(k, v)| k.len() + approx_json_size(v)).sum(),
+        _ => 0,
     }
 }
 
 // ----------------------
 #[derive(Clone)]
 pub struct RelayCtx {
-    pub client: Client,
     pub cache: TtlCache,
+    pub recent_broadcasts: Arc<parking_lot::Mutex<RecentBroadcasts>>,
+    pub tx_tracker: Arc<parking_lot::Mutex<TxTracker>>,
+    pub fresh_broadcasts: Arc<parking_lot::Mutex<FreshBroadcasts>>,
+    pub backfill_budget: Arc<TokenBucket>, // backfill.max_calls_per_s
 }
 
 impl RelayCtx {
-    pub fn new(client: Client) -> Self {
-        Self { client, cache: TtlCache::new() }
+    pub fn new() -> Self {
+        Self {
+            cache: TtlCache::new(),
+            recent_broadcasts: Arc::default(),
+            tx_tracker: Arc::default(),
+            fresh_broadcasts: Arc::default(),
+            backfill_budget: Arc::new(TokenBucket::new(0)),
+        }
+    }
+}
+
+/// Successful broadcast replies by tx hash, so wallet retries aren't re-sent to every target.
+#[derive(Default)]
+pub struct RecentBroadcasts {
+    entries: HashMap<String, (Instant, Value)>, // (sent at, reply)
+}
+
+impl RecentBroadcasts {
+    fn get(&mut self, key: &str, window: Duration) -> Option<Value> {
+        self.entries.retain(|_, (at, _)| at.elapsed() < window);
+        self.entries.get(key).map(|(_, v)| v.clone())
+    }
+
+    fn insert(&mut self, key: String, reply: Value) {
+        self.entries.insert(key, (Instant::now(), reply));
+    }
+}
+
+/// Providers that accepted each recent raw transaction, by tx hash, for relay.receipt_fanout_ms.
+#[derive(Default)]
+pub struct FreshBroadcasts {
+    entries: HashMap<String, (Instant, Vec<String>)>, // (sent at, accepting provider labels)
+}
+
+impl FreshBroadcasts {
+    fn get(&mut self, hash: &str, window: Duration) -> Option<Vec<String>> {
+        self.entries.retain(|_, (at, _)| at.elapsed() < window);
+        self.entries.get(hash).map(|(_, accepted)| accepted.clone())
+    }
+
+    fn insert(&mut self, hash: String, accepted: Vec<String>) {
+        self.entries.insert(hash, (Instant::now(), accepted));
     }
 }
 
@@ -70,41 +289,277 @@ pub async fn health() -> (StatusCode, Json<Value>) {
     (StatusCode::OK, Json(json!({"status":"ok"})))
 }
 
-pub async fn status(State(state): State<HttpState>) -> (StatusCode, Json<Value>) {
-    let reg = state.app.registry.read().await;
-    let mut list = Vec::new();
-    for p in reg.primaries.iter().chain(reg.secondaries.iter()) {
-        let obj = json!({
-            "url": p.url,
-            "healthy": p.is_healthy(),
-            "latest_block": p.get_latest_block(),
-            "behind": p.get_behind(),
-            "latency_ms": p.get_latency(),
-            "call_count": p.call_count.load(std::sync::atomic::Ordering::Relaxed),
-            "errors": p.errors.load(std::sync::atomic::Ordering::Relaxed),
-            "banned_until": p.breaker.lock().banned_until(),
-            // NEW: persistently show the last error reason (not cleared on success)
-            "last_error": error_reason::get_last_error(&p.url).as_str(),
-        });
-        list.push(obj);
+/// Ready when at least one provider is currently selectable (healthy and not banned).
+pub async fn readyz(State(state): State<HttpState>) -> (StatusCode, Json<Value>) {
+    if healthy_candidates(&state.app.snapshot().registry, None).is_empty() {
+        (StatusCode::SERVICE_UNAVAILABLE, Json(json!({"status":"unavailable"})))
+    } else {
+        (StatusCode::OK, Json(json!({"status":"ready"})))
+    }
+}
+
+pub async fn status(State(state): State<HttpState>, Query(q): Query<StatusQuery>) -> Response {
+    if q.watch() {
+        return crate::status_query::watch(state.app.clone(), q);
     }
-    (StatusCode::OK, Json(json!({ "rpcs": list })))
+    q.respond(status_json(&state.app, q.include_retired()))
+}
+
+/// The /status document; also written by dashboard snapshot exports.
+pub fn status_json(app: &AppState, include_retired: bool) -> Value {
+    let snap = app.snapshot();
+    let reg = &snap.registry;
+    let list: Vec<Value> = reg.primaries.iter().chain(reg.secondaries.iter()).map(|p| provider_json(p, &snap.cfg)).collect();
+    let namespaces = app.namespaces.to_json();
+    let shadow = snap.cfg.cache_shadow.as_ref().map(|_| app.cache_shadow.to_json());
+    let network = json!({
+        "providers": list.len(),
+        "healthy": list.iter().filter(|p| p["healthy"] == true).count(),
+        "call_count": list.iter().filter_map(|p| p["call_count"].as_u64()).sum::<u64>(),
+        "errors": list.iter().filter_map(|p| p["errors"].as_u64()).sum::<u64>(),
+        "estimated_spend": list.iter().filter_map(|p| p["estimated_spend"].as_f64()).sum::<f64>(),
+    });
+    let mut out = json!({
+        "network": snap.cfg.network,
+        "networks": { snap.cfg.network.clone(): network },
+        "summary": app.totals.to_json(),
+        "degraded": crate::health::is_degraded(),
+        "namespaces": namespaces,
+        "cache_shadow": shadow,
+        "memory": crate::memory_guard::to_json(app),
+        "cache_ttl_scale": snap.cfg.cache_ttl_scaling.as_ref().map(|_| crate::health::cache_ttl_scale()),
+        "rpcs": list,
+    });
+    if include_retired {
+        let retired: Vec<Value> = app
+            .retired()
+            .iter()
+            .map(|(p, at)| {
+                let mut obj = provider_json(p, &snap.cfg);
+                obj["retired_s_ago"] = json!(at.elapsed().as_secs());
+                obj
+            })
+            .collect();
+        out["retired"] = json!(retired);
+    }
+    out
+}
+
+fn provider_json(p: &ProviderState, cfg: &Config) -> Value {
+    let (decay, hm) = (cfg.relay.weight_decay.as_ref(), &cfg.health_monitor);
+    let captured: serde_json::Map<String, Value> = p
+        .captured_headers()
+        .into_iter()
+        .map(|(name, value, age_s)| (name, json!({ "value": value, "age_s": age_s })))
+        .collect();
+    let recent_errors: Vec<Value> = error_reason::recent_errors(&p.id)
+        .iter()
+        .map(|e| json!({ "at": e.unix_s, "reason": e.reason.as_str(), "http_status": e.http_status }))
+        .collect();
+    json!({
+        "name": p.name,
+        "url": crate::redact::mask_url(&p.url),
+        "label": p.label(),
+        "healthy": p.is_healthy(),
+        "drained": p.is_drained(),
+        "off_schedule": p.is_off_schedule(),
+        "latest_block": p.get_latest_block(),
+        "behind": p.get_behind(),
+        "latency_ms": p.get_latency(),
+        "call_count": p.call_count.load(std::sync::atomic::Ordering::Relaxed),
+        "errors": p.errors.load(std::sync::atomic::Ordering::Relaxed),
+        "success_count": p.success_count.load(std::sync::atomic::Ordering::Relaxed),
+        "failure_count": p.failure_count.load(std::sync::atomic::Ordering::Relaxed),
+        "success_rate": p.success_rate(),
+        "weight": p.get_weight(),
+        "effective_weight": p.get_weight() as f64 * p.weight_factor(decay),
+        "recent_error_rate": p.recent_error_rate(),
+        "lag_factor": p.lag_factor(),
+        "max_tps": p.max_tps.load(std::sync::atomic::Ordering::Relaxed),
+        "cost_per_million": p.cost_per_million(),
+        "infrastructure": p.infrastructure(),
+        "max_blocks_behind": p.max_blocks_behind(hm.max_blocks_behind, hm.degradation.as_ref().filter(|_| crate::health::is_degraded())),
+        "estimated_spend": p.estimated_spend().map(|s| (s * 10_000.0).round() / 10_000.0),
+        "learned_tps": p.learned_tps().map(|t| (t * 10.0).round() / 10.0),
+        "bucket_fill": p.bucket_fill().map(|f| (f * 1000.0).round() / 1000.0),
+        "bucket_rejections": p.bucket_rejections.load(std::sync::atomic::Ordering::Relaxed),
+        "probe_count": p.probe_count.load(std::sync::atomic::Ordering::Relaxed),
+        "probe_failures": p.probe_failures.load(std::sync::atomic::Ordering::Relaxed),
+        "probes_skipped": p.probes_skipped.load(std::sync::atomic::Ordering::Relaxed),
+        "latency_breakdown": p.phases.lock().to_json(),
+        "captured_headers": captured,
+        "near_quota": p.near_quota(),
+        "history_depth": match p.history_depth() {
+            HistoryDepth::Unknown => Value::Null,
+            HistoryDepth::Archive => json!("archive"),
+            HistoryDepth::Blocks(n) => json!(n),
+        },
+        "uptime": uptime_json(p),
+        "rates": p.rates.lock().to_json(),
+        "banned_until": p.breaker.lock().banned_until(),
+        "manual_ban": crate::admin::manual_ban_json(p),
+        "misconfigured_since": p.misconfigured_since(),
+        // NEW: persistently show the last error reason (not cleared on success)
+        "last_error": error_reason::get_last_error(&p.id).as_str(),
+        "last_http_status": error_reason::get_last_http_status(&p.id),
+        "recent_errors": recent_errors,
+    })
 }
 
-pub async fn relay(State(state): State<HttpState>, Json(body): Json<Value>) -> (StatusCode, Json<Value>) {
+pub async fn relay(State(state): State<HttpState>, headers: HeaderMap, body: Bytes) -> Response {
+    let mut trace = CallTrace::new();
+    let body = match jsonrpc::parse_body(&body) {
+        Ok(v) => v,
+        Err(detail) => {
+            let (status, reply) = RelayError::ParseError { detail }.into_response(Value::Null);
+            state.app.totals.record(status.as_u16(), &reply);
+            return (status, reply).into_response();
+        }
+    };
+    let id = jsonrpc::reply_id(&body);
+    let notification = jsonrpc::is_notification(&body);
+    let mut req = match RequestBody::parse(body) {
+        Ok(RequestBody::Single(req)) => req,
+        Ok(RequestBody::Batch(calls)) => {
+            let detail = format!("batch requests are not supported (got {} calls); send them one by one", calls.len());
+            return invalid_request(&state, id, detail).into_response();
+        }
+        Err(detail) => return invalid_request(&state, id, detail).into_response(),
+    };
+    if notification {
+        // Still served (and accounted) like a call, under an id of our own; the client gets no reply.
+        req.id = Value::from(1);
+    }
+    let method = req.method.clone();
+    // One snapshot for the whole request, handed down to the call itself.
+    let snap = state.app.snapshot();
+    if let Some(mg) = snap.cfg.memory_guard.as_ref().filter(|mg| crate::memory_guard::under_pressure() && mg.sheds(&method)) {
+        if !snap.cfg.relay.broadcast_methods.contains(&method) {
+            crate::memory_guard::record_shed();
+            let (status, Json(reply)) = RelayError::Overloaded.into_response(id);
+            state.app.totals.record(status.as_u16(), &reply);
+            let mut headers = HeaderMap::new();
+            headers.insert("retry-after", HeaderValue::from(mg.retry_after_s.max(1)));
+            return (status, headers, Json(reply)).into_response();
+        }
+    }
+    let api_key = headers.get("x-rly-key").and_then(|v| v.to_str().ok());
+    let limits = snap.cfg.call_overrides.as_ref().map(|co| call_limits(co, &headers)).unwrap_or_default();
+    let paging = snap.cfg.logs_pagination.as_ref().filter(|_| method == crate::logs_page::METHOD);
+    let (status, Json(mut reply)) = match paging {
+        Some(pc) => crate::logs_page::serve(&state, &snap, req, pc, api_key, limits, &mut trace).await,
+        None => relay_call(&state, &snap, req, api_key, limits, &mut trace).await,
+    };
+    let failed = status != StatusCode::OK || reply.get("error").is_some();
+    state.app.totals.record(status.as_u16(), &reply);
+    state.app.namespaces.record(&method, failed, trace.cache == "hit", trace.started.elapsed());
+    if let Some(oc) = &snap.cfg.origin_stats {
+        let origin = crate::origin_stats::origin_of(oc, &headers);
+        state.app.origins.record(oc, origin, &method, failed, trace.cache == "hit", trace.started.elapsed());
+    }
+    if let Some(limit) = snap.cfg.logging.slow_request_ms.filter(|&ms| trace.started.elapsed() >= Duration::from_millis(ms)) {
+        warn!(
+            method,
+            provider = trace.served_by.as_deref().unwrap_or("-"),
+            attempts = trace.attempts.len(),
+            total_ms = trace.started.elapsed().as_millis() as u64,
+            upstream_ms = trace.attempts.iter().map(|a| a.latency_ms).max().unwrap_or(0),
+            cache = trace.cache,
+            breakdown = %trace.breakdown(),
+            limit_ms = limit,
+            "slow request"
+        );
+    }
+    if headers.get("x-rly-debug").is_some_and(|v| v == "1") {
+        if let Some(obj) = reply.as_object_mut() {
+            obj.insert("relay".to_string(), trace.to_json());
+        }
+    }
+    // The pool the call chose from; a cache hit never got that far.
+    let pool = trace.pool.take().unwrap_or_else(|| healthy_candidates(&snap.registry, snap.cfg.relay.weight_decay.as_ref()));
+    let limits = rate_limit_headers(pool.providers(), status);
+    if notification {
+        return (StatusCode::NO_CONTENT, limits).into_response();
+    }
+    (status, limits, Json(reply)).into_response()
+}
+
+fn invalid_request(state: &HttpState, id: Value, detail: String) -> (StatusCode, HeaderMap, Json<Value>) {
+    let (status, reply) = RelayError::InvalidRequest { detail }.into_response(id);
+    state.app.totals.record(status.as_u16(), &reply);
+    (status, rate_limit_headers(healthy_candidates(&state.app.snapshot().registry, None).providers(), status), reply)
+}
+
+/// Per-request replacements for relay.max_provider_tries / upstream_timeout_ms; None keeps the configured value.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CallLimits {
+    pub max_tries: Option<u32>,
+    pub timeout: Option<Duration>,
+}
+
+const MIN_OVERRIDE_TIMEOUT_MS: u64 = 100;
+
+/// X-RLY-Max-Tries / X-RLY-Timeout-Ms, clamped to the call_overrides ceilings. Only a caller
+/// sending one of trusted_keys as X-RLY-Key may override (none may with an empty list), and
+/// only with a plain number.
+pub fn call_limits(co: &CallOverridesConfig, headers: &HeaderMap) -> CallLimits {
+    if !crate::auth::has_trusted_key(&co.trusted_keys, headers) {
+        return CallLimits::default();
+    }
+    let number = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).and_then(|v| v.trim().parse::<u64>().ok());
+    CallLimits {
+        max_tries: number("x-rly-max-tries").map(|t| t.clamp(1, co.max_tries.max(1) as u64) as u32),
+        timeout: number("x-rly-timeout-ms")
+            .map(|ms| Duration::from_millis(ms.clamp(MIN_OVERRIDE_TIMEOUT_MS, co.max_timeout_ms.max(MIN_OVERRIDE_TIMEOUT_MS)))),
+    }
+}
+
+/// X-RLY-Rate-Limit-* over the buckets of every selectable provider; empty if any is unlimited.
+/// Reset is seconds until the pool is full again; a 429 also gets Retry-After (next token).
+fn rate_limit_headers(pool: &[Arc<ProviderState>], status: StatusCode) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let snapshots: Option<Vec<_>> = pool.iter().map(|p| p.bucket_snapshot()).collect();
+    let Some(snapshots) = snapshots.filter(|s| !s.is_empty()) else { return headers };
+    let limit: f64 = snapshots.iter().map(|(rate, _, _)| rate).sum();
+    let remaining: f64 = snapshots.iter().map(|(_, tokens, _)| tokens.floor().max(0.0)).sum();
+    let wait = |need: f64, tokens: f64, rate: f64| if rate > 0.0 { ((need - tokens).max(0.0) / rate).ceil() } else { 0.0 };
+    let reset = snapshots.iter().map(|&(rate, tokens, cap)| wait(cap, tokens, rate)).fold(0.0, f64::max);
+    let next = snapshots.iter().map(|&(rate, tokens, _)| wait(1.0, tokens, rate)).fold(f64::INFINITY, f64::min);
+    let mut set = |name: &'static str, v: f64| {
+        headers.insert(name, HeaderValue::from(v.round() as u64));
+    };
+    set("x-rly-rate-limit-limit", limit);
+    set("x-rly-rate-limit-remaining", remaining);
+    set("x-rly-rate-limit-reset", reset);
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        set("retry-after", next.max(1.0));
+    }
+    headers
+}
+
+/// Serve one JSON-RPC call (cache, selection, failover), recording what happened in `trace`.
+/// `snap` is the caller's snapshot, so a concurrent reload can't mix old and new settings;
+/// `api_key` is the caller's X-RLY-Key, if any; `limits` its call_overrides (normal failover only).
+pub async fn relay_call(
+    state: &HttpState,
+    snap: &Arc<Snapshot>,
+    req: JsonRpcRequest,
+    api_key: Option<&str>,
+    limits: CallLimits,
+    trace: &mut CallTrace,
+) -> (StatusCode, Json<Value>) {
     // increment incoming call counter
     state.app.total_calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
-    let cfg_arc = state.app.cfg.clone();
-    let reg_arc = state.app.registry.clone();
+    let (cfg, reg) = (&snap.cfg, &snap.registry);
 
-    let method = body.get("method").and_then(|m| m.as_str()).unwrap_or_default().to_string();
-    let id_value = body.get("id").cloned().unwrap_or(Value::Number(0u64.into()));
-    let mut params_value = body.get("params").cloned().unwrap_or(Value::Null);
+    let JsonRpcRequest { jsonrpc, id: id_value, method, params: mut params_value } = req;
 
-    // Normalize "eth_getTransactionCount" -> pending
+    // Normalize "eth_getTransactionCount" -> pending, for callers relay.pending_nonce covers
     if method == "eth_getTransactionCount" {
-        if let Value::Array(ref mut arr) = params_value {
+        let address = params_value.get(0).and_then(|a| a.as_str());
+        let rewrite = cfg.relay.pending_nonce.applies(api_key, address);
+        if let (true, Value::Array(ref mut arr)) = (rewrite, &mut params_value) {
             if !arr.is_empty() {
                 arr.truncate(2);
                 if arr.len() == 1 { arr.push(Value::String("pending".into())); }
@@ -113,199 +568,250 @@ pub async fn relay(State(state): State<HttpState>, Json(body): Json<Value>) -> (
         }
     }
 
+    // Turn away raw transactions that broadcast_preflight says are invalid, before any provider sees them
+    if let Some(pf) = cfg.broadcast_preflight.as_ref().filter(|_| method == "eth_sendRawTransaction") {
+        if let Err(detail) = crate::raw_tx::preflight(pf, params_value.get(0).and_then(Value::as_str)) {
+            debug!(method, detail, "raw transaction failed pre-flight checks");
+            return RelayError::RejectedTransaction { detail }.into_response(id_value);
+        }
+    }
+
+    // Lookups of a tx broadcast moments ago; see the fan-out path below
+    let fresh = match params_value.get(0).and_then(Value::as_str) {
+        Some(hash) if cfg.relay.receipt_fanout_ms > 0 && RECEIPT_FANOUT_METHODS.contains(&method.as_str()) => {
+            state.relay.fresh_broadcasts.lock().get(&hash.to_ascii_lowercase(), Duration::from_millis(cfg.relay.receipt_fanout_ms))
+        }
+        _ => None,
+    };
+
     // TTL cache lookup
-    let ttl_ms = {
-        let cfg = cfg_arc.read().await;
-        cfg.cache_ttl.get(&method).cloned().unwrap_or(0)
+    let cache_params = crate::cache_key::canonical_params(&method, &params_value);
+    let pin_limit = match &cfg.pinned_cache {
+        Some(pc) if pc.methods.contains(&method) => {
+            pinnable(&method, &params_value, pc.min_confirmations, reg).then_some(pc.max_entries)
+        }
+        _ => None,
     };
-    if ttl_ms > 0 {
-        let key = (method.clone(), params_value.clone().to_string());
+    let ttl_ms = match &cfg.cache_ttl_scaling {
+        Some(ts) => ts.apply(cfg.cache_ttl_for(&method, &cache_params), crate::health::cache_ttl_scale()),
+        None => cfg.cache_ttl_for(&method, &cache_params),
+    };
+    let negative_ttl_ms = cfg.negative_ttl_for(&method);
+    let error_ttl_ms = cfg.cache_error_ttl_for(&method, &cache_params);
+    if fresh.is_some() {
+        // a receipt cached as missing just before the broadcast may exist by now
+    } else if pin_limit.is_some() {
+        trace.cache = "miss";
+        if let Some(mut cached) = state.relay.cache.get_pinned(&(method.clone(), cache_params.clone())) {
+            trace.cache = "hit";
+            state.app.cache_hits.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            if let Some(sc) = &cfg.cache_shadow {
+                crate::cache_shadow::on_hit(state, sc, &method, &params_value, &cached);
+            }
+            jsonrpc::set_id(&mut cached, &id_value);
+            return (StatusCode::OK, Json(cached));
+        }
+    }
+    // a pinnable call's error reply is kept in the TTL cache like any other
+    if fresh.is_none() && (error_ttl_ms > 0 || pin_limit.is_none() && (ttl_ms > 0 || negative_ttl_ms.is_some())) {
+        let key = (method.clone(), cache_params.clone());
+        trace.cache = "miss";
         if let Some(mut cached) = state.relay.cache.get(&key).await {
+            trace.cache = "hit";
             // count cache hit
             state.app.cache_hits.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-            if let Some(obj) = cached.as_object_mut() {
-                obj.insert("id".to_string(), id_value.clone());
+            if let Some(sc) = &cfg.cache_shadow {
+                crate::cache_shadow::on_hit(state, sc, &method, &params_value, &cached);
             }
+            jsonrpc::set_id(&mut cached, &id_value);
             return (StatusCode::OK, Json(cached));
         }
     }
 
     // Choose candidates
-    let (cands, _lt, broadcast_methods, redundancy, tries, upstream_timeout_ms, breaker_cfg) = {
-        let cfg = cfg_arc.read().await;
-        let reg = reg_arc.read().await;
-
-        let lt = cfg.relay.latency_threshold_ms;
-        let methods = cfg.relay.broadcast_methods.clone();
-        let redundancy = cfg.relay.broadcast_redundancy.max(1);
-        let tries = cfg.relay.max_provider_tries.max(1);
-        let upstream_ms = cfg.relay.upstream_timeout_ms.max(1000);
-        let breaker_cfg = crate::circuit_breaker::BreakerConfig {
-            ban_error_threshold: cfg.relay.ban_error_threshold,
-            ban_seconds: cfg.relay.ban_seconds,
-        };
-
-        let healthy = healthy_candidates(&reg);
-        let under = filter_latency(healthy, lt);
-        (under, lt, methods, redundancy, tries, upstream_ms, breaker_cfg)
-    };
+    let (rc, breaker_cfg) = (&cfg.relay, &snap.breaker);
+    let views = selector::views(reg, rc.weight_decay.as_ref());
+    let mut select = Selector::new(cfg, crate::health::is_degraded());
+    if let (Some(_), Some(crate::cache_key::BlockRef::Number(n))) =
+        (&cfg.health_monitor.archive_probe, crate::cache_key::explicit_block(&method, &params_value))
+    {
+        let head = reg.primaries.iter().chain(reg.secondaries.iter()).map(|p| p.get_latest_block()).max().unwrap_or(0);
+        select.behind = Some(head.saturating_sub(n));
+    }
+    let live = selector::live_slots(&views, select.decay);
+    trace.pool = Some(reg.candidates(live.clone()));
+    let slots = select.cut(&views, live);
+    let cheapest = select.preferred(&views, &slots).map(|s| reg.candidates(s));
+    let cands = reg.candidates(slots);
 
     if cands.is_empty() {
-        let resp = json!({"jsonrpc":"2.0","id": id_value,"error":{"code":-32000,"message":"No healthy RPCs available"}});
-        return (StatusCode::INTERNAL_SERVER_ERROR, Json(resp));
+        crate::reporting::report("all_providers_down", "no healthy RPC providers available", json!({ "method": method }));
+        return RelayError::NoHealthyProviders.into_response(id_value);
     }
 
-    let upstream_timeout = Duration::from_millis(upstream_timeout_ms);
+    let upstream_timeout = limits.timeout.unwrap_or_else(|| rc.upstream_timeout());
+    let max_response_bytes = rc.response_limit();
 
     // Prepare payload and cache key
     let id_for_resp = id_value.clone();
-    let payload = json!({
-        "jsonrpc":"2.0",
-        "id": id_value,
-        "method": method,
-        "params": params_value.clone()
-    });
-    let cache_key_opt = if ttl_ms > 0 {
-        Some((payload.get("method").unwrap().as_str().unwrap_or_default().to_string(),
-              payload.get("params").cloned().unwrap_or(Value::Null).to_string()))
-    } else { None };
+    let payload = JsonRpcRequest { jsonrpc, id: id_value, method: method.clone(), params: params_value }.to_value();
+    let cache_key_opt =
+        (ttl_ms > 0 || negative_ttl_ms.is_some() || error_ttl_ms > 0 || pin_limit.is_some()).then(|| (method.clone(), cache_params));
+    let cache_reply = |v: &Value| {
+        let ttl = reply_ttl(v, ttl_ms, negative_ttl_ms, error_ttl_ms);
+        let pin = pin_limit.filter(|_| v.get("error").is_none() && v.get("result").is_some_and(|r| !r.is_null()));
+        let entry = cache_key_opt.clone().filter(|_| ttl > 0 || pin.is_some()).map(|key| (key, v.clone()));
+        let cache = state.relay.cache.clone();
+        async move {
+            match (entry, pin) {
+                (Some((key, v)), Some(max_entries)) => cache.insert_pinned(key, v, max_entries),
+                (Some((key, v)), None) => cache.insert_with_ttl(key, v, Duration::from_millis(ttl)).await,
+                (None, _) => {}
+            }
+        }
+    };
 
     // Broadcast path
-    if broadcast_methods.iter().any(|m| m == payload.get("method").and_then(|x| x.as_str()).unwrap_or("")) {
-        let uniq_sorted = unique_by_low_latency(cands);
-
-        let mut chosen = Vec::new();
-        for p in uniq_sorted {
-            if chosen.len() >= redundancy { break; }
-            if p.try_consume_token() { chosen.push(p); }
+    if rc.broadcast_methods.contains(&method) {
+        let dedup = (rc.broadcast_dedup_ms > 0).then(|| broadcast_key(&payload)).flatten();
+        let window = Duration::from_millis(rc.broadcast_dedup_ms);
+        if let Some(key) = &dedup {
+            if let Some(mut v) = state.relay.recent_broadcasts.lock().get(key, window) {
+                debug!(method, key = %key, "duplicate broadcast within dedup window; not re-sent");
+                trace.cache = "hit";
+                jsonrpc::set_id(&mut v, &id_for_resp);
+                return (StatusCode::OK, Json(v));
+            }
         }
+        let chosen = match rc.broadcast_targets {
+            BroadcastTargets::Fastest => distinct_with_tokens(by_low_latency(&cands), rc.broadcast_redundancy.max(1)),
+            BroadcastTargets::Spread => spread_with_tokens(reg, &cands, rc.broadcast_redundancy.max(1)),
+        };
         if chosen.is_empty() {
-            let resp = json!({"jsonrpc":"2.0","id": id_for_resp,"error":{"code":-32005,"message":"Rate limited; try later"}});
-            return (StatusCode::TOO_MANY_REQUESTS, Json(resp));
+            return RelayError::RateLimited.into_response(id_for_resp);
         }
+        state.app.totals.broadcast();
+        let hash = raw_tx_hash(&payload);
+        let raw_hex = payload["params"][0].as_str().map(str::to_string);
 
-        let client = state.relay.client.clone();
-        let payload_arc = Arc::new(payload);
-        let futs: FuturesUnordered<_> = chosen.into_iter().map(|p| {
-            let client = client.clone();
-            let url = p.url.clone();
-            let payload = payload_arc.clone();
-            // count attempt for this provider
-            p.call_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-            async move {
-                let res = tokio::time::timeout(upstream_timeout, client.post(url).json(&*payload).send()).await;
-                (p, res)
-            }
-        }).collect();
-
-        tokio::pin!(futs);
-        let mut first_err: Option<String> = None;
-
-        while let Some((prov, res)) = futs.next().await {
-            match res {
-                Ok(Ok(resp)) => match resp.json::<Value>().await {
-                    Ok(v) => {
-                        if v.get("error").is_none() {
-                            // NOTE: do NOT clear last error on success; keep it sticky
-                            prov.breaker_success();
-                            if let Some(ref key) = cache_key_opt {
-                                state.relay.cache.insert_with_ttl(key.clone(), v.clone(), Duration::from_millis(ttl_ms)).await;
-                            }
-                            return (StatusCode::OK, Json(v));
-                        } else {
-                            first_err.get_or_insert(format!("{}", v.get("error").unwrap_or(&Value::String("error".into()))));
-                            prov.errors.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                            prov.breaker_failure(&breaker_cfg);
-                            error_reason::set_last_error(&prov.url, ErrorReason::RpcError);
-                        }
+        return match first_success(chosen, payload, snap, &method, true, rc.broadcast_finish_all, trace).await {
+            Ok(v) => {
+                if let Some(key) = dedup.filter(|_| v.get("error").is_none()) {
+                    state.relay.recent_broadcasts.lock().insert(key, v.clone());
+                }
+                if let Some(hash) = hash.filter(|_| v.get("error").is_none()) {
+                    if rc.receipt_fanout_ms > 0 {
+                        state.relay.fresh_broadcasts.lock().insert(hash.clone(), accepted_by(trace));
                     }
-                    Err(e) => {
-                        first_err.get_or_insert(format!("bad json: {}", e));
-                        prov.errors.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                        prov.breaker_failure(&breaker_cfg);
-                        error_reason::set_last_error(&prov.url, ErrorReason::BadJson);
+                    if let Some(raw_hex) = raw_hex {
+                        tx_track::on_broadcast(state, snap, &raw_hex, hash, trace.served_by.clone());
                     }
-                },
-                Ok(Err(_e)) => {
-                    first_err.get_or_insert("upstream error".to_string());
-                    prov.errors.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    prov.breaker_failure(&breaker_cfg);
-                    error_reason::set_last_error(&prov.url, ErrorReason::HttpError);
                 }
-                Err(_) => {
-                    first_err.get_or_insert("upstream timeout".to_string());
-                    prov.errors.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    prov.breaker_failure(&breaker_cfg);
-                    error_reason::set_last_error(&prov.url, ErrorReason::Timeout);
+                cache_reply(&v).await;
+                (StatusCode::OK, Json(v))
+            }
+            Err((reason, detail)) => failure(reason, detail, "All broadcast attempts failed", trace).into_response(id_for_resp),
+        };
+    }
+
+    // Fan-out path: a tx broadcast moments ago is often known to only some providers, so
+    // ask the ones that accepted it and the fastest others at once; the first to find it wins.
+    if let Some(accepted) = &fresh {
+        let chosen = fanout_targets(reg, &cands, accepted, rc.receipt_fanout.max(1));
+        if !chosen.is_empty() {
+            match first_found(chosen, payload.clone(), snap, &method, trace).await {
+                Ok(v) => {
+                    if !v["result"].is_null() {
+                        cache_reply(&v).await;
+                    }
+                    return (StatusCode::OK, Json(v));
+                }
+                Err((ErrorReason::TooLarge, detail)) => {
+                    return RelayError::ResponseTooLarge { detail }.into_response(id_for_resp);
                 }
+                Err(_) => {}
             }
         }
+    }
 
-        let resp = json!({"jsonrpc":"2.0","id": id_for_resp,"error":{"code":-32603,"message": format!("All broadcast attempts failed: {}", first_err.unwrap_or_else(|| "unknown".into()))}});
-        return (StatusCode::BAD_GATEWAY, Json(resp));
+    // Race path: same read to the fastest K providers, first good answer wins.
+    // Unlike broadcast, a race that loses every leg falls through to normal failover.
+    if rc.race_methods.contains(&method) {
+        let chosen = fastest_with_tokens(&cands, rc.race_fanout.max(1));
+        if !chosen.is_empty() {
+            match first_success(chosen, payload.clone(), snap, &method, false, false, trace).await {
+                Ok(v) => {
+                    cache_reply(&v).await;
+                    return (StatusCode::OK, Json(v));
+                }
+                Err((ErrorReason::TooLarge, detail)) => {
+                    return RelayError::ResponseTooLarge { detail }.into_response(id_for_resp);
+                }
+                Err(_) => {}
+            }
+        }
     }
 
     // Non-broadcast path with failover
     let mut attempt = 0usize;
     let mut last_err = String::new();
+    let mut last_reason = ErrorReason::None;
+    let mut last_reply: Option<(Value, String)> = None; // (reply, provider label)
+    let mut agreed: Option<(Value, String)> = None; // the RPC error every attempt so far got, for cache_error_ttl
+    let mut disagree = error_ttl_ms == 0;
     let mut rr_idx = state.app.rr_main.fetch_add(1, std::sync::atomic::Ordering::Relaxed) as usize;
 
-    while attempt < tries as usize {
-        let mut candidates = cands.clone();
-
-        if !candidates.is_empty() {
-            rr_idx %= candidates.len();
-            candidates.rotate_left(rr_idx);
-        }
-
-        let prov = candidates.into_iter().find(|p| p.try_consume_token());
+    while attempt < limits.max_tries.unwrap_or(rc.max_provider_tries).max(1) as usize {
+        // the cheapest go first; anyone else only if none of them has a token
+        let preferred = cheapest.as_ref().filter(|_| attempt == 0).into_iter().flat_map(|c| c.rotation(rr_idx));
+        let prov = preferred.chain(cands.rotation(rr_idx)).find(|p| p.try_consume_token()).cloned();
         let Some(prov) = prov else {
-            let resp = json!({"jsonrpc":"2.0","id": id_for_resp,"error":{"code":-32005,"message":"Rate limited; try later"}});
-            return (StatusCode::TOO_MANY_REQUESTS, Json(resp));
+            return RelayError::RateLimited.into_response(id_for_resp);
         };
 
         // count attempt for this provider
         prov.call_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
-        let url = prov.url.clone();
-        let client = state.relay.client.clone();
-
-        let res = tokio::time::timeout(upstream_timeout, client.post(url).json(&payload).send()).await;
+        let started = Instant::now();
+        let (res, phases) = timing::timed(upstream::call(&prov, &payload, upstream_timeout, max_response_bytes)).await;
+        trace.record(&prov, started.elapsed(), phases, &res);
         match res {
-            Ok(Ok(resp)) => match resp.json::<Value>().await {
-                Ok(v) => {
-                    if v.get("error").is_none() {
-                        // NOTE: sticky last error — do not clear on success
-                        prov.breaker_success();
-                        if let Some(ref key) = cache_key_opt {
-                            state.relay.cache.insert_with_ttl(key.clone(), v.clone(), Duration::from_millis(ttl_ms)).await;
-                        }
-                        return (StatusCode::OK, Json(v));
-                    } else {
-                        last_err = format!("{}", v.get("error").unwrap_or(&Value::String("error".into())));
-                        prov.errors.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                        prov.breaker_failure(&breaker_cfg);
-                        error_reason::set_last_error(&prov.url, ErrorReason::RpcError);
-                    }
-                }
-                Err(e) => {
-                    last_err = format!("bad json: {}", e);
-                    prov.errors.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    prov.breaker_failure(&breaker_cfg);
-                    error_reason::set_last_error(&prov.url, ErrorReason::BadJson);
+            Ok(v) if v.get("error").is_none() => {
+                // NOTE: sticky last error — do not clear on success
+                prov.breaker_success();
+                prov.record_outcome(true);
+                cache_reply(&v).await;
+                trace.served_by = Some(prov.label());
+                return (StatusCode::OK, Json(v));
+            }
+            Ok(v) if is_terminal(rc, &v) => {
+                // the next provider would say the same thing
+                debug!(provider = %prov.label(), method, error = %v["error"], "terminal upstream error, no failover");
+                prov.record_outcome(false);
+                cache_reply(&v).await;
+                trace.served_by = Some(prov.label());
+                return (StatusCode::OK, Json(v));
+            }
+            Ok(v) => {
+                last_err = format!("{}", v.get("error").unwrap_or(&Value::String("error".into())));
+                last_reason = rpc_reason(rc, &v);
+                disagree |= last_reason == ErrorReason::AuthFailed || agreed.as_ref().is_some_and(|(a, _)| !same_error(a, &v));
+                agreed.get_or_insert_with(|| (v.clone(), prov.label()));
+                if is_non_breaking(rc, &v) {
+                    debug!(provider = %prov.label(), method, detail = %last_err, "non-breaking upstream error");
+                    prov.record_outcome(false);
+                    last_reply = Some((v, prov.label()));
+                } else {
+                    record_failure(&prov, breaker_cfg, &method, last_reason, None, &last_err);
+                    last_reply = None;
                 }
-            },
-            Ok(Err(_e)) => {
-                last_err = "upstream error".to_string();
-                prov.errors.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                prov.breaker_failure(&breaker_cfg);
-                error_reason::set_last_error(&prov.url, ErrorReason::HttpError);
             }
-            Err(_) => {
-                last_err = "upstream timeout".to_string();
-                prov.errors.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                prov.breaker_failure(&breaker_cfg);
-                error_reason::set_last_error(&prov.url, ErrorReason::Timeout);
+            Err(e) => {
+                last_err = e.to_string();
+                last_reason = e.reason();
+                record_failure(&prov, breaker_cfg, &method, last_reason, e.http_status(), &last_err);
+                last_reply = None;
+                disagree = true;
             }
         }
 
@@ -313,49 +819,658 @@ pub async fn relay(State(state): State<HttpState>, Json(body): Json<Value>) -> (
         rr_idx = rr_idx.wrapping_add(1);
     }
 
-    let resp = json!({"jsonrpc":"2.0","id": id_for_resp,"error":{"code":-32603,"message": format!("Upstream provider error after failover: {}", last_err)}});
-    (StatusCode::BAD_GATEWAY, Json(resp))
+    // Every provider tried gave the same error (e.g. a pruned block): that is the answer,
+    // cached under cache_error_ttl so the next identical calls don't trip more breakers.
+    if let Some((v, label)) = agreed.filter(|_| !disagree) {
+        cache_reply(&v).await;
+        trace.served_by = Some(label);
+        return (StatusCode::OK, Json(v));
+    }
+    // The provider answered properly; hand its error to the client unchanged.
+    if let Some((v, label)) = last_reply {
+        cache_reply(&v).await;
+        trace.served_by = Some(label);
+        return (StatusCode::OK, Json(v));
+    }
+    failure(last_reason, last_err, "Upstream provider error after failover", trace).into_response(id_for_resp)
 }
 
 // -------- helpers --------
 
-fn healthy_candidates(reg: &ProviderRegistry) -> Vec<Arc<ProviderState>> {
-    let now_healthy = |p: &Arc<ProviderState>| p.is_healthy() && !p.breaker_is_banned();
+/// What happened while serving one call; returned under "relay" for `X-RLY-Debug: 1`.
+pub struct CallTrace {
+    pub started: Instant,
+    pub cache: &'static str, // "hit", "miss", or "off" when the call isn't cacheable
+    pub served_by: Option<String>,
+    pub attempts: Vec<Attempt>,
+    pub pool: Option<Arc<Candidates>>, // selectable providers the call picked from, for X-RLY-Rate-Limit-*
+}
+
+pub struct Attempt {
+    pub provider: String,
+    pub latency_ms: u64,
+    pub phases: Phases,
+    pub error: Option<String>,
+}
+
+impl CallTrace {
+    pub fn new() -> Self {
+        Self { started: Instant::now(), cache: "off", served_by: None, attempts: Vec::new(), pool: None }
+    }
+
+    fn record(&mut self, prov: &ProviderState, elapsed: Duration, phases: Phases, res: &Result<Value, upstream::UpstreamError>) {
+        let error = match res {
+            Ok(v) => v.get("error").map(|e| e.to_string()),
+            Err(e) => Some(e.to_string()),
+        };
+        self.attempts.push(Attempt { provider: prov.label(), latency_ms: elapsed.as_millis() as u64, phases, error });
+    }
+
+    /// "label:ms[:error]" per attempt, for log lines.
+    pub fn breakdown(&self) -> String {
+        let parts: Vec<String> = self
+            .attempts
+            .iter()
+            .map(|a| match &a.error {
+                Some(e) => format!("{}:{}ms:{}", a.provider, a.latency_ms, e),
+                None => format!("{}:{}ms", a.provider, a.latency_ms),
+            })
+            .collect();
+        parts.join(" ")
+    }
+
+    pub fn attempts_json(&self) -> Vec<Value> {
+        self.attempts
+            .iter()
+            .map(|a| {
+                json!({
+                    "provider": a.provider,
+                    "latency_ms": a.latency_ms,
+                    "connect_ms": a.phases.connect_ms,
+                    "ttfb_ms": a.phases.ttfb_ms,
+                    "body_ms": a.phases.body_ms,
+                    "error": a.error,
+                })
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "provider": self.served_by,
+            "cache": self.cache,
+            "total_ms": self.started.elapsed().as_millis() as u64,
+            "attempts": self.attempts_json(),
+        })
+    }
+}
+
+fn uptime_json(p: &ProviderState) -> Value {
+    let tracker = p.uptime.lock();
+    let pct = |secs: u64| tracker.uptime_pct(Duration::from_secs(secs)).map(|p| (p * 1000.0).round() / 1000.0);
+    let transitions: Vec<Value> = tracker
+        .recent(10)
+        .iter()
+        .map(|t| json!({ "at": t.unix_s, "healthy": t.healthy }))
+        .collect();
+    json!({ "1h": pct(3_600), "24h": pct(86_400), "7d": pct(7 * 86_400), "transitions": transitions })
+}
 
-    let prim: Vec<_> = reg.primaries.iter().cloned().filter(now_healthy).collect();
-    if !prim.is_empty() { return apply_weights(prim); }
+/// Up to `n` providers holding a rate-limit token for a fresh-tx lookup: those that accepted
+/// the broadcast first (if still usable, even outside `cands`), then the fastest of `cands`.
+fn fanout_targets(reg: &ProviderRegistry, cands: &Candidates, accepted: &[String], n: usize) -> Vec<Arc<ProviderState>> {
+    let all = reg.all();
+    let usable = |p: &&Arc<ProviderState>| p.is_healthy() && !p.breaker_is_banned() && !p.is_drained();
+    let first = accepted.iter().filter_map(|label| all.iter().find(|p| p.label() == *label)).filter(usable).cloned();
+    let mut chosen: Vec<Arc<ProviderState>> = Vec::new();
+    for p in first.chain(by_low_latency(cands)) {
+        if chosen.len() >= n { break; }
+        if !chosen.iter().any(|c| Arc::ptr_eq(c, &p)) && p.try_consume_token() { chosen.push(p); }
+    }
+    chosen
+}
 
-    let sec: Vec<_> = reg.secondaries.iter().cloned().filter(now_healthy).collect();
-    apply_weights(sec)
+// Labels of the providers that took a broadcast, in the order they answered.
+fn accepted_by(trace: &CallTrace) -> Vec<String> {
+    let mut labels: Vec<String> = trace.attempts.iter().filter(|a| a.error.is_none()).map(|a| a.provider.clone()).collect();
+    // a benign "already known" counts too; the client got the hash from it
+    if let Some(served) = trace.served_by.as_ref().filter(|s| !labels.contains(s)) {
+        labels.insert(0, served.clone());
+    }
+    labels
 }
 
-fn apply_weights(list: Vec<Arc<ProviderState>>) -> Vec<Arc<ProviderState>> {
-    let mut out = Vec::new();
-    for p in list {
-        let w = p.get_weight();
-        for _ in 0..w { out.push(p.clone()); }
+/// Up to `n` distinct providers, fastest first, each holding a rate-limit token.
+pub(crate) fn fastest_with_tokens(cands: &Candidates, n: usize) -> Vec<Arc<ProviderState>> {
+    let mut chosen = Vec::new();
+    for p in by_low_latency(cands) {
+        if chosen.len() >= n { break; }
+        if p.try_consume_token() { chosen.push(p); }
     }
-    out
+    chosen
 }
 
-fn filter_latency(list: Vec<Arc<ProviderState>>, threshold_ms: Option<u64>) -> Vec<Arc<ProviderState>> {
-    if let Some(th) = threshold_ms {
-        let under: Vec<_> = list.iter().cloned().filter(|p| p.get_latency() < th).collect();
-        if !under.is_empty() { return under; }
-        if let Some(min) = list.iter().map(|p| p.get_latency()).min() {
-            return list.into_iter().filter(|p| p.get_latency() == min).collect();
+/// Up to `n` providers with tokens, alternating tiers and starting with the fastest primary
+/// (none are usable when `cands` is the secondary tier); a tier that runs out leaves the
+/// rest to the other. Secondaries outside `cands` are taken if selectable. Distinct
+/// infrastructure goes first, as in distinct_with_tokens.
+fn spread_with_tokens(reg: &ProviderRegistry, cands: &Candidates, n: usize) -> Vec<Arc<ProviderState>> {
+    let ranked = by_low_latency(cands);
+    let from_primaries = ranked.first().is_some_and(|p| reg.primaries.iter().any(|q| Arc::ptr_eq(p, q)));
+    let (primaries, secondaries) = if from_primaries {
+        let mut rest: Vec<_> = reg.secondaries.iter().filter(|p| selector::selectable(p)).cloned().collect();
+        rest.sort_by_key(|p| p.get_latency());
+        (ranked, rest)
+    } else {
+        (Vec::new(), ranked)
+    };
+    let mut tiers = [primaries.into_iter(), secondaries.into_iter()];
+    let (mut chosen, mut same_infra, mut turn) = (Vec::new(), Vec::new(), 0);
+    while chosen.len() < n {
+        let Some(p) = tiers[turn].next().or_else(|| tiers[1 - turn].next()) else { break };
+        if shares_infrastructure(&chosen, &p) {
+            same_infra.push(p);
+        } else if p.try_consume_token() {
+            chosen.push(p);
+            turn = 1 - turn;
         }
     }
-    list
+    top_up(chosen, same_infra, n)
+}
+
+/// Up to `n` providers with tokens from `ranked`, in order, except that one sharing an
+/// `infrastructure` label with a provider already chosen is only taken if nothing else is left.
+fn distinct_with_tokens(ranked: Vec<Arc<ProviderState>>, n: usize) -> Vec<Arc<ProviderState>> {
+    let (mut chosen, mut same_infra) = (Vec::new(), Vec::new());
+    for p in ranked {
+        if chosen.len() >= n { break; }
+        if shares_infrastructure(&chosen, &p) {
+            same_infra.push(p);
+        } else if p.try_consume_token() {
+            chosen.push(p);
+        }
+    }
+    top_up(chosen, same_infra, n)
 }
 
-fn unique_by_low_latency(mut list: Vec<Arc<ProviderState>>) -> Vec<Arc<ProviderState>> {
-    use std::collections::HashSet;
+// Unlabelled providers are assumed to be distinct from everything.
+fn shares_infrastructure(chosen: &[Arc<ProviderState>], p: &ProviderState) -> bool {
+    p.infrastructure().is_some_and(|label| chosen.iter().any(|c| c.infrastructure().as_ref() == Some(&label)))
+}
+
+fn top_up(mut chosen: Vec<Arc<ProviderState>>, rest: Vec<Arc<ProviderState>>, n: usize) -> Vec<Arc<ProviderState>> {
+    for p in rest {
+        if chosen.len() >= n { break; }
+        if p.try_consume_token() { chosen.push(p); }
+    }
+    chosen
+}
+
+/// Send `payload` to every provider in `chosen` at once and return the first
+/// success; on total failure the first error seen is returned.
+///
+/// Each leg runs as its own task and accounts its outcome (stats, breaker) as
+/// soon as it has one, independently of this function. Legs still in flight when
+/// we return are aborted, without any outcome recorded, unless `finish_rest`.
+///
+/// With `benign`, errors matching relay.broadcast_benign_errors count as success
+/// for the provider. For a raw transaction we answer with its hash straight away;
+/// otherwise the benign (or non-breaking) reply is returned only if no leg
+/// succeeds outright. A terminal error ("nonce too low", a revert) is held too:
+/// another leg may still accept the call, so it only wins once every leg is done.
+async fn first_success(
+    chosen: Vec<Arc<ProviderState>>,
+    payload: Value,
+    snap: &Arc<Snapshot>,
+    method: &str,
+    benign: bool,
+    finish_rest: bool,
+    trace: &mut CallTrace,
+) -> Result<Value, (ErrorReason, String)> {
+    let payload = Arc::new(payload);
+    let (handles, mut rx) = spawn_legs(chosen, &payload, snap, method, benign);
+    let _legs = Legs { handles, keep: finish_rest };
+
+    let mut first_err: Option<(ErrorReason, String)> = None;
+    let mut benign_reply: Option<(Value, String)> = None;
+    let mut terminal: Option<(Value, String)> = None;
+    let mut reply: Option<(Value, String)> = None;
+    while let Some((prov, res, elapsed, phases, leg)) = rx.recv().await {
+        trace.record(&prov, elapsed, phases, &res);
+        let Ok(v) = res else {
+            if let Leg::Failed(reason, detail) = leg { first_err.get_or_insert((reason, detail)); }
+            continue;
+        };
+        match leg {
+            Leg::Success => {
+                trace.served_by = Some(prov.label());
+                return Ok(v);
+            }
+            Leg::Benign => {
+                if let Some(hash) = raw_tx_hash(&payload) {
+                    trace.served_by = Some(prov.label());
+                    return Ok(json!({"jsonrpc":"2.0","id": payload["id"],"result": hash}));
+                }
+                benign_reply.get_or_insert((v, prov.label()));
+            }
+            Leg::Terminal => {
+                terminal.get_or_insert((v, prov.label()));
+            }
+            Leg::NonBreaking(reason, detail) => {
+                reply.get_or_insert((v, prov.label()));
+                first_err.get_or_insert((reason, detail));
+            }
+            Leg::Failed(reason, detail) => {
+                first_err.get_or_insert((reason, detail));
+            }
+        }
+    }
+    if let Some((v, label)) = benign_reply.or(terminal).or(reply) {
+        trace.served_by = Some(label);
+        return Ok(v);
+    }
+    Err(first_err.unwrap_or((ErrorReason::None, "unknown".into())))
+}
+
+/// For lookups of a just-broadcast tx: send `payload` to every provider in `chosen` at
+/// once and return the first reply with a non-null result. If none has one, the first
+/// null (or terminal) reply is returned; if no leg answered properly, the first error.
+async fn first_found(
+    chosen: Vec<Arc<ProviderState>>,
+    payload: Value,
+    snap: &Arc<Snapshot>,
+    method: &str,
+    trace: &mut CallTrace,
+) -> Result<Value, (ErrorReason, String)> {
+    let (handles, mut rx) = spawn_legs(chosen, &Arc::new(payload), snap, method, false);
+    let _legs = Legs { handles, keep: false };
+
+    let mut first_err: Option<(ErrorReason, String)> = None;
+    let mut reply: Option<(Value, String)> = None;
+    while let Some((prov, res, elapsed, phases, leg)) = rx.recv().await {
+        trace.record(&prov, elapsed, phases, &res);
+        match (leg, res) {
+            (Leg::Success, Ok(v)) if !v["result"].is_null() => {
+                trace.served_by = Some(prov.label());
+                return Ok(v);
+            }
+            (Leg::Success | Leg::Terminal, Ok(v)) => {
+                reply.get_or_insert((v, prov.label()));
+            }
+            (Leg::NonBreaking(reason, detail) | Leg::Failed(reason, detail), _) => {
+                first_err.get_or_insert((reason, detail));
+            }
+            _ => {}
+        }
+    }
+    if let Some((v, label)) = reply {
+        trace.served_by = Some(label);
+        return Ok(v);
+    }
+    Err(first_err.unwrap_or((ErrorReason::None, "unknown".into())))
+}
+
+type LegOutcome = (Arc<ProviderState>, Result<Value, upstream::UpstreamError>, Duration, Phases, Leg);
+
+// One task per provider sending `payload`; each accounts its own outcome before reporting it.
+fn spawn_legs(
+    chosen: Vec<Arc<ProviderState>>,
+    payload: &Arc<Value>,
+    snap: &Arc<Snapshot>,
+    method: &str,
+    benign: bool,
+) -> (Vec<tokio::task::JoinHandle<()>>, tokio::sync::mpsc::UnboundedReceiver<LegOutcome>) {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    let handles = chosen
+        .into_iter()
+        .map(|p| {
+            let (payload, snap, method, tx) = (payload.clone(), snap.clone(), method.to_string(), tx.clone());
+            // count attempt for this provider
+            p.call_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            tokio::spawn(async move {
+                let rc = &snap.cfg.relay;
+                let started = Instant::now();
+                let (res, phases) = timing::timed(upstream::call(&p, &payload, rc.upstream_timeout(), rc.response_limit())).await;
+                let leg = account_leg(&p, &res, &snap, &method, benign);
+                let _ = tx.send((p, res, started.elapsed(), phases, leg));
+            })
+        })
+        .collect();
+    (handles, rx)
+}
+
+/// How one fan-out leg ended, already accounted for in the provider's stats.
+pub(crate) enum Leg {
+    Success,
+    Benign,
+    Terminal,
+    NonBreaking(ErrorReason, String),
+    Failed(ErrorReason, String),
+}
+
+pub(crate) fn account_leg(prov: &ProviderState, res: &Result<Value, upstream::UpstreamError>, snap: &Snapshot, method: &str, benign: bool) -> Leg {
+    let (rc, breaker_cfg) = (&snap.cfg.relay, &snap.breaker);
+    let v = match res {
+        Ok(v) => v,
+        Err(e) => {
+            let detail = e.to_string();
+            record_failure(prov, breaker_cfg, method, e.reason(), e.http_status(), &detail);
+            return Leg::Failed(e.reason(), detail);
+        }
+    };
+    let Some(err) = v.get("error") else {
+        // NOTE: do NOT clear last error on success; keep it sticky
+        prov.breaker_success();
+        prov.record_outcome(true);
+        return Leg::Success;
+    };
+    if benign && rc.broadcast_benign_errors.iter().any(|p| p.matches(err)) {
+        prov.breaker_success();
+        prov.record_outcome(true);
+        debug!(provider = %prov.label(), method, error = %err, "benign broadcast error treated as success");
+        return Leg::Benign;
+    }
+    if is_terminal(rc, v) {
+        debug!(provider = %prov.label(), method, error = %err, "terminal upstream error");
+        prov.record_outcome(false);
+        return Leg::Terminal;
+    }
+    let detail = err.to_string();
+    let reason = rpc_reason(rc, v);
+    if is_non_breaking(rc, v) {
+        debug!(provider = %prov.label(), method, detail, "non-breaking upstream error");
+        prov.record_outcome(false);
+        return Leg::NonBreaking(reason, detail);
+    }
+    record_failure(prov, breaker_cfg, method, reason, None, &detail);
+    Leg::Failed(reason, detail)
+}
+
+// Fan-out leg tasks, aborted when the caller is done with them (or goes away) unless `keep`.
+struct Legs {
+    handles: Vec<tokio::task::JoinHandle<()>>,
+    keep: bool,
+}
+
+impl Drop for Legs {
+    fn drop(&mut self) {
+        if !self.keep {
+            self.handles.iter().for_each(|h| h.abort());
+        }
+    }
+}
+
+/// State read at a block deep enough below the best known head that it can no longer reorg.
+fn pinnable(method: &str, params: &Value, min_confirmations: u64, reg: &ProviderRegistry) -> bool {
+    match crate::cache_key::explicit_block(method, params) {
+        Some(crate::cache_key::BlockRef::Hash) => true,
+        Some(crate::cache_key::BlockRef::Number(n)) => {
+            let head = reg.all().iter().map(|p| p.get_latest_block()).max().unwrap_or(0);
+            head >= n.saturating_add(min_confirmations)
+        }
+        None => false,
+    }
+}
+
+/// How long to cache a reply: errors per cache_error_ttl, null results per negative_cache when it covers the method.
+fn reply_ttl(v: &Value, ttl_ms: u64, negative_ttl_ms: Option<u64>, error_ttl_ms: u64) -> u64 {
+    if v.get("error").is_some() {
+        return error_ttl_ms;
+    }
+    match (v.get("result"), negative_ttl_ms) {
+        (Some(Value::Null) | None, Some(neg)) => neg,
+        _ => ttl_ms,
+    }
+}
+
+// Same JSON-RPC error code and message; data may differ between providers.
+fn same_error(a: &Value, b: &Value) -> bool {
+    a["error"]["code"] == b["error"]["code"] && a["error"]["message"] == b["error"]["message"]
+}
+
+fn is_non_breaking(rc: &RelayConfig, reply: &Value) -> bool {
+    reply.get("error").is_some_and(|e| rc.non_breaking_errors.iter().any(|p| p.matches(e)))
+}
+
+// Error replies that reject our credentials are auth failures, not ordinary RPC errors.
+fn rpc_reason(rc: &RelayConfig, reply: &Value) -> ErrorReason {
+    match reply.get("error") {
+        Some(e) if rc.auth_errors.iter().any(|p| p.matches(e)) => ErrorReason::AuthFailed,
+        _ => ErrorReason::RpcError,
+    }
+}
+
+fn is_terminal(rc: &RelayConfig, reply: &Value) -> bool {
+    reply.get("error").is_some_and(|e| rc.terminal_errors.iter().any(|p| p.matches(e)))
+}
+
+// Answered by fan-out for relay.receipt_fanout_ms after the tx was broadcast.
+const RECEIPT_FANOUT_METHODS: &[&str] = &["eth_getTransactionReceipt", "eth_getTransactionByHash"];
+
+fn raw_tx_hash(payload: &Value) -> Option<String> {
+    if payload.get("method")?.as_str()? != "eth_sendRawTransaction" {
+        return None;
+    }
+    crate::keccak::tx_hash(payload.get("params")?.get(0)?.as_str()?)
+}
+
+// Dedup key for a broadcast: the tx hash for raw transactions, else method + params.
+fn broadcast_key(payload: &Value) -> Option<String> {
+    raw_tx_hash(payload).or_else(|| Some(format!("{}:{}", payload.get("method")?.as_str()?, payload.get("params")?)))
+}
+
+/// Relay error for a call whose every attempt failed, classified by the deciding reason.
+fn failure(reason: ErrorReason, detail: String, summary: &str, trace: &CallTrace) -> RelayError {
+    match reason {
+        ErrorReason::TooLarge => RelayError::ResponseTooLarge { detail },
+        ErrorReason::Timeout => RelayError::UpstreamTimeout { attempts: trace.attempts_json() },
+        _ => RelayError::AllAttemptsFailed { summary: format!("{}: {}", summary, detail), attempts: trace.attempts_json() },
+    }
+}
+
+pub(crate) fn record_failure(
+    prov: &ProviderState,
+    breaker_cfg: &BreakerConfig,
+    method: &str,
+    reason: ErrorReason,
+    http_status: Option<u16>,
+    detail: &str,
+) {
+    prov.record_outcome(false);
+    // failover_statuses (e.g. a load balancer's 502/503) only move the call on; ban_statuses ban at once
+    if !http_status.is_some_and(|s| breaker_cfg.failover_statuses.contains(&s)) {
+        prov.errors.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        prov.breaker_failure(breaker_cfg);
+    }
+    if let Some(status) = http_status.filter(|s| breaker_cfg.ban_statuses.contains(s)) {
+        prov.ban_on_status(breaker_cfg, status);
+    }
+    match reason {
+        ErrorReason::RateLimited => prov.on_rate_limited(),
+        ErrorReason::AuthFailed => prov.on_auth_failure(breaker_cfg, detail),
+        _ => {}
+    }
+    error_reason::set_last_error(&prov.id, reason, http_status);
+    // JSON-RPC errors are frequently caller-caused (reverts, bad nonces); keep them out of warn.
+    if reason == ErrorReason::RpcError {
+        debug!(provider = %prov.label(), method, reason = reason.as_str(), detail, "upstream call failed");
+    } else {
+        warn!(provider = %prov.label(), method, reason = reason.as_str(), http_status, detail, "upstream call failed");
+    }
+}
+
+pub(crate) fn healthy_candidates(reg: &ProviderRegistry, decay: Option<&WeightDecayConfig>) -> Arc<Candidates> {
+    healthy_candidates_where(reg, decay, |_| true)
+}
+
+/// `healthy_candidates` narrowed to the providers passing `keep`; the narrowed slots are the
+/// cache key, so the set is built once per distinct result rather than per call.
+pub(crate) fn healthy_candidates_where(
+    reg: &ProviderRegistry,
+    decay: Option<&WeightDecayConfig>,
+    keep: impl Fn(&ProviderState) -> bool,
+) -> Arc<Candidates> {
+    let mut slots = selector::live_slots(&selector::views(reg, decay), decay.is_some());
+    for (s, p) in slots.iter_mut().zip(reg.primaries.iter().chain(reg.secondaries.iter())) {
+        if !keep(p) {
+            *s = 0;
+        }
+    }
+    reg.candidates(slots)
+}
+
+fn by_low_latency(cands: &Candidates) -> Vec<Arc<ProviderState>> {
+    let mut list = cands.providers().to_vec();
     list.sort_by_key(|p| p.get_latency());
-    let mut seen = HashSet::new();
-    let mut out = Vec::new();
-    for p in list {
-        if seen.insert(p.url.clone()) { out.push(p); }
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RoutingPolicy;
+    use crate::state::AppState;
+    use crate::upstream::{SendFuture, UpstreamError, UpstreamTransport};
+    use std::collections::VecDeque;
+
+    /// Answers every provider's calls from one script, in order, and notes who was asked.
+    #[derive(Debug, Default)]
+    struct Scripted {
+        replies: parking_lot::Mutex<VecDeque<Result<Value, UpstreamError>>>,
+        asked: parking_lot::Mutex<Vec<String>>,
+    }
+
+    impl UpstreamTransport for Scripted {
+        fn send<'a>(&'a self, prov: &'a ProviderState, _: &'a Value, _: tokio::time::Instant, _: usize) -> SendFuture<'a> {
+            self.asked.lock().push(prov.label());
+            let reply = self.replies.lock().pop_front().expect("more calls than scripted replies");
+            Box::pin(async move { reply })
+        }
+    }
+
+    #[tokio::test]
+    async fn lookup_stats_fold_unknown_methods_into_other() {
+        let cache = TtlCache::new();
+        for i in 0..100 {
+            cache.get(&(format!("m{i}"), "[]".to_string())).await;
+        }
+        cache.get(&("m0".to_string(), "[]".to_string())).await;
+        let stats = cache.stats().await;
+        assert_eq!(stats.per_method.len(), 65);
+        assert!(stats.per_method.contains(&("other".to_string(), 0, 36)));
+        assert!(stats.per_method.contains(&("m0".to_string(), 0, 2)));
+    }
+
+    #[test]
+    fn candidate_sets_are_reused_per_key() {
+        let (state, _) = relay_over(Vec::new());
+        let reg = &state.app.snapshot().registry;
+        let plain = reg.candidates(vec![1, 1, 1]);
+        let decayed = reg.candidates(vec![4, 2, 4]);
+        assert!(!Arc::ptr_eq(&plain, &decayed));
+        assert!(Arc::ptr_eq(&plain, &reg.candidates(vec![1, 1, 1])));
+        assert!(Arc::ptr_eq(&decayed, &reg.candidates(vec![4, 2, 4])));
+        assert_eq!(decayed.providers().len(), 3);
+
+        // a latency cut is a key like any other: built once, then reused
+        for (p, ms) in reg.primaries.iter().zip([20, 900, 30]) {
+            p.set_latency(ms);
+        }
+        let select = Selector { decay: false, latency_threshold_ms: Some(100), routing: RoutingPolicy::Weighted, behind: None };
+        let cut = || reg.candidates(select.slots(&selector::views(reg, None)));
+        let fast = cut();
+        assert_eq!(fast.providers().len(), 2);
+        assert!(Arc::ptr_eq(&fast, &cut()));
+    }
+
+    #[test]
+    fn reloaded_provider_settings_wait_for_the_swap() {
+        let (state, _) = relay_over(Vec::new());
+        let snap = state.app.snapshot();
+        let mut cfg = snap.cfg.clone();
+        cfg.rpc_endpoints.primary[0].weight = 7;
+        cfg.rpc_endpoints.primary[1].drained = true;
+        let r = crate::state::reconcile_registry(&snap.registry, &[], &cfg.rpc_endpoints, &cfg.http_client, &cfg.relay.default_headers);
+        let (a, b) = (snap.registry.find("a").unwrap(), snap.registry.find("b").unwrap());
+        assert_eq!((a.get_weight(), b.is_drained()), (1, false), "nothing changes while the old snapshot serves");
+        drop(r); // a reload that never swaps leaves the providers as they were
+        assert_eq!((a.get_weight(), b.is_drained()), (1, false));
+
+        let r = crate::state::reconcile_registry(&snap.registry, &[], &cfg.rpc_endpoints, &cfg.http_client, &cfg.relay.default_headers);
+        state.app.swap(Snapshot::new(cfg, r.registry).with_staged(r.staged));
+        assert_eq!((a.get_weight(), b.is_drained()), (7, true));
+        assert!(Arc::ptr_eq(&a, &state.app.snapshot().registry.find("a").unwrap()));
+    }
+
+    fn relay_over(script: Vec<Result<Value, UpstreamError>>) -> (HttpState, Arc<Scripted>) {
+        let cfg: Config = serde_yaml::from_str(
+            r#"
+            network: test
+            server: { bind_addr: "127.0.0.1", port: 0 }
+            relay: { max_provider_tries: 3 }
+            rpc_endpoints:
+              primary:
+                - { url: "http://a.invalid", name: a }
+                - { url: "http://b.invalid", name: b }
+                - { url: "http://c.invalid", name: c }
+            "#,
+        )
+        .unwrap();
+        let fake = Arc::new(Scripted { replies: parking_lot::Mutex::new(script.into()), ..Default::default() });
+        let build = |eps: &[crate::config::Endpoint]| -> Vec<Arc<ProviderState>> {
+            eps.iter()
+                .map(|ep| ProviderState::with_transport(ep, &cfg.http_client, &cfg.relay.default_headers, fake.clone()))
+                .collect()
+        };
+        let registry = ProviderRegistry::new(build(&cfg.rpc_endpoints.primary), Vec::new());
+        let app = Arc::new(AppState::with_registry(cfg, registry));
+        (HttpState { app, relay: RelayCtx::new() }, fake)
+    }
+
+    async fn call(state: &HttpState) -> (StatusCode, Value, CallTrace) {
+        let mut trace = CallTrace::new();
+        let req = JsonRpcRequest::new("eth_call", json!([{ "to": "0x01" }, "latest"]));
+        let (status, Json(reply)) = relay_call(state, &state.app.snapshot(), req, None, CallLimits::default(), &mut trace).await;
+        (status, reply, trace)
+    }
+
+    #[tokio::test]
+    async fn fails_over_to_the_next_provider() {
+        let ok = json!({ "jsonrpc": "2.0", "id": 1, "result": "0x2a" });
+        let (state, fake) = relay_over(vec![Err(UpstreamError::Status(502)), Ok(ok)]);
+        let (status, reply, trace) = call(&state).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(reply["result"], "0x2a");
+        let asked = fake.asked.lock().clone();
+        assert_eq!(asked.len(), 2);
+        assert_ne!(asked[0], asked[1], "retried on the provider that failed");
+        assert_eq!(trace.served_by.as_deref(), Some(asked[1].as_str()));
+        let failed = state.app.snapshot().registry.find(&asked[0]).unwrap();
+        assert_eq!(failed.errors.load(std::sync::atomic::Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn terminal_error_stops_failover() {
+        let reverted = json!({ "jsonrpc": "2.0", "id": 1, "error": { "code": 3, "message": "execution reverted" } });
+        let ok = json!({ "jsonrpc": "2.0", "id": 1, "result": "0x2a" });
+        let (state, fake) = relay_over(vec![Ok(reverted), Ok(ok)]);
+        let (status, reply, trace) = call(&state).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(reply["error"]["message"], "execution reverted");
+        assert_eq!(fake.asked.lock().len(), 1);
+        assert_eq!(trace.attempts.len(), 1);
+        let asked = fake.asked.lock()[0].clone();
+        let prov = state.app.snapshot().registry.find(&asked).unwrap();
+        assert_eq!(prov.errors.load(std::sync::atomic::Ordering::Relaxed), 0, "terminal errors aren't held against the provider");
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_provider_tries() {
+        let script = (0..3).map(|_| Err(UpstreamError::Status(503))).collect();
+        let (state, fake) = relay_over(script);
+        let (status, reply, _) = call(&state).await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert_eq!(reply["error"]["data"]["kind"], "all_attempts_failed");
+        assert_eq!(fake.asked.lock().len(), 3);
     }
-    out
 }
@@ -0,0 +1,93 @@
+use axum::{http::StatusCode, Json};
+use crate::jsonrpc::{JsonRpcError, JsonRpcResponse};
+use serde_json::{json, Value};
+
+/// Failures produced by the relay itself, as opposed to error objects a provider
+/// returned (those are passed through untouched). All codes sit in -32010..=-32019,
+/// apart from the standard -32700/-32600/-32602 for calls that are malformed, and
+/// `error.data.source` is always "relay", so clients can tell the two apart.
+#[derive(Debug)]
+pub enum RelayError {
+    ParseError { detail: String }, // body is not JSON
+    InvalidRequest { detail: String },
+    InvalidParams { detail: String },
+    NoHealthyProviders,
+    ResponseTooLarge { detail: String },
+    RateLimited,
+    UpstreamTimeout { attempts: Vec<Value> },
+    AllAttemptsFailed { summary: String, attempts: Vec<Value> },
+    Overloaded, // shed under memory_guard pressure
+    RejectedTransaction { detail: String }, // failed broadcast_preflight
+}
+
+impl RelayError {
+    pub fn code(&self) -> i64 {
+        match self {
+            RelayError::ParseError { .. } => -32700,
+            RelayError::InvalidRequest { .. } => -32600,
+            RelayError::InvalidParams { .. } => -32602,
+            RelayError::NoHealthyProviders => -32010,
+            RelayError::ResponseTooLarge { .. } => -32011,
+            RelayError::RateLimited => -32012,
+            RelayError::UpstreamTimeout { .. } => -32013,
+            RelayError::AllAttemptsFailed { .. } => -32014,
+            RelayError::Overloaded => -32015,
+            RelayError::RejectedTransaction { .. } => -32016,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RelayError::ParseError { .. } => "parse_error",
+            RelayError::InvalidRequest { .. } => "invalid_request",
+            RelayError::InvalidParams { .. } => "invalid_params",
+            RelayError::NoHealthyProviders => "no_healthy_providers",
+            RelayError::ResponseTooLarge { .. } => "response_too_large",
+            RelayError::RateLimited => "rate_limited",
+            RelayError::UpstreamTimeout { .. } => "upstream_timeout",
+            RelayError::AllAttemptsFailed { .. } => "all_attempts_failed",
+            RelayError::Overloaded => "overloaded",
+            RelayError::RejectedTransaction { .. } => "rejected_transaction",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            RelayError::ParseError { .. }
+            | RelayError::InvalidRequest { .. }
+            | RelayError::InvalidParams { .. }
+            | RelayError::RejectedTransaction { .. } => {
+                StatusCode::BAD_REQUEST
+            }
+            RelayError::NoHealthyProviders | RelayError::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+            RelayError::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            RelayError::UpstreamTimeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            RelayError::ResponseTooLarge { .. } | RelayError::AllAttemptsFailed { .. } => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            RelayError::ParseError { detail } => format!("Parse error: {}", detail),
+            RelayError::InvalidRequest { detail } => format!("Invalid request: {}", detail),
+            RelayError::InvalidParams { detail } => format!("Invalid params: {}", detail),
+            RelayError::NoHealthyProviders => "No healthy RPCs available".to_string(),
+            RelayError::ResponseTooLarge { detail } => detail.clone(),
+            RelayError::RateLimited => "Rate limited; try later".to_string(),
+            RelayError::UpstreamTimeout { .. } => "Upstream providers timed out".to_string(),
+            RelayError::AllAttemptsFailed { summary, .. } => summary.clone(),
+            RelayError::Overloaded => "Relay is under memory pressure; retry later".to_string(),
+            RelayError::RejectedTransaction { detail } => format!("Transaction rejected by relay pre-flight check: {}", detail),
+        }
+    }
+
+    /// Full JSON-RPC error reply for request `id`.
+    pub fn into_response(self, id: Value) -> (StatusCode, Json<Value>) {
+        let mut data = json!({ "source": "relay", "kind": self.kind() });
+        if let RelayError::UpstreamTimeout { attempts } | RelayError::AllAttemptsFailed { attempts, .. } = &self {
+            data["attempts"] = Value::Array(attempts.clone());
+        }
+        let error = JsonRpcError { code: self.code(), message: self.message(), data: Some(data) };
+        (self.status(), Json(JsonRpcResponse::error(id, error).to_value()))
+    }
+}
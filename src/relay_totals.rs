@@ -0,0 +1,73 @@
+use serde_json::{json, Map, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// RelayError kinds, plus "upstream_error" for a provider's error reply handed to the client.
+// Anything else (e.g. a provider imitating data.source = "relay") counts as upstream_error.
+const REASONS: [&str; 10] = [
+    "invalid_request",
+    "invalid_params",
+    "no_healthy_providers",
+    "response_too_large",
+    "rate_limited",
+    "upstream_timeout",
+    "all_attempts_failed",
+    "overloaded",
+    "rejected_transaction",
+    "upstream_error",
+];
+
+/// Client-facing outcomes of JSON-RPC calls on POST /, for the /status summary and TUI header.
+#[derive(Default)]
+pub struct RelayTotals {
+    succeeded: AtomicU64,
+    failed: [AtomicU64; REASONS.len()],
+    too_many_requests: AtomicU64, // HTTP 429 answers
+    broadcasts: AtomicU64,        // broadcast fan-outs actually sent (dedup hits excluded)
+}
+
+impl RelayTotals {
+    /// Record one answered call from its HTTP status and reply body.
+    pub fn record(&self, status: u16, reply: &Value) {
+        if status == 429 {
+            self.too_many_requests.fetch_add(1, Ordering::Relaxed);
+        }
+        let Some(err) = reply.get("error") else {
+            self.succeeded.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        let kind = err["data"]["kind"].as_str().filter(|_| err["data"]["source"] == "relay").unwrap_or("upstream_error");
+        let i = REASONS.iter().position(|&r| r == kind).unwrap_or(REASONS.len() - 1);
+        self.failed[i].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn broadcast(&self) {
+        self.broadcasts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// (succeeded, failed, HTTP 429s, broadcasts) so far.
+    pub fn counts(&self) -> (u64, u64, u64, u64) {
+        let failed = self.failed.iter().map(|c| c.load(Ordering::Relaxed)).sum();
+        let load = |a: &AtomicU64| a.load(Ordering::Relaxed);
+        (load(&self.succeeded), failed, load(&self.too_many_requests), load(&self.broadcasts))
+    }
+
+    pub fn to_json(&self) -> Value {
+        let (succeeded, failed, too_many_requests, broadcasts) = self.counts();
+        let by_reason: Map<String, Value> = REASONS
+            .iter()
+            .zip(&self.failed)
+            .map(|(r, c)| (r.to_string(), c.load(Ordering::Relaxed)))
+            .filter(|&(_, n)| n > 0)
+            .map(|(r, n)| (r, json!(n)))
+            .collect();
+        json!({
+            "requests": succeeded + failed,
+            "succeeded": succeeded,
+            "failed": failed,
+            "failure_rate": if succeeded + failed == 0 { 0.0 } else { failed as f64 / (succeeded + failed) as f64 },
+            "failed_by_reason": by_reason,
+            "rate_limited_429": too_many_requests,
+            "broadcasts": broadcasts,
+        })
+    }
+}
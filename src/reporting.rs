@@ -0,0 +1,144 @@
+use crate::config::ErrorReportingConfig;
+use crate::timing::epoch_secs;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use reqwest::{Client, Url};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::{error, warn};
+
+/// Out-of-band error reporting (generic webhook and/or Sentry) for conditions
+/// nobody would otherwise notice: panics, dead background tasks, repeated
+/// reload failures and "all providers down".
+struct Reporter {
+    cfg: ErrorReportingConfig,
+    network: String,
+    client: Client,
+    last_sent: HashMap<String, Instant>,
+}
+
+static REPORTER: Lazy<RwLock<Option<Reporter>>> = Lazy::new(|| RwLock::new(None));
+static EVENT_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// (Re)configure the reporter; called at startup and on every hot reload.
+pub fn configure(cfg: &ErrorReportingConfig, network: &str) {
+    let mut guard = REPORTER.write();
+    if cfg.webhook_url.is_none() && cfg.sentry_dsn.is_none() {
+        *guard = None;
+        return;
+    }
+    let last_sent = guard.take().map(|r| r.last_sent).unwrap_or_default();
+    let client = Client::builder().timeout(Duration::from_secs(5)).build().unwrap_or_else(|_| Client::new());
+    *guard = Some(Reporter { cfg: cfg.clone(), network: network.to_string(), client, last_sent });
+}
+
+/// Report an event. Events of the same `kind` are throttled to one per `min_interval_s`.
+pub fn report(kind: &str, message: impl Into<String>, context: Value) {
+    let message = message.into();
+    let (client, webhook, sentry, network) = {
+        let mut guard = REPORTER.write();
+        let Some(r) = guard.as_mut() else { return };
+        let min_interval = Duration::from_secs(r.cfg.min_interval_s);
+        if let Some(t) = r.last_sent.get(kind) {
+            if t.elapsed() < min_interval { return; }
+        }
+        r.last_sent.insert(kind.to_string(), Instant::now());
+        (r.client.clone(), r.cfg.webhook_url.clone(), r.cfg.sentry_dsn.clone(), r.network.clone())
+    };
+
+    let Ok(handle) = tokio::runtime::Handle::try_current() else { return };
+    let kind = kind.to_string();
+    handle.spawn(async move {
+        if let Some(url) = webhook {
+            let body = json!({
+                "source": "rpc-relay",
+                "network": network,
+                "kind": kind,
+                "message": message,
+                "context": context,
+                "ts": epoch_secs(),
+            });
+            if let Err(e) = client.post(&url).json(&body).send().await {
+                warn!(kind, error = %e, "error webhook delivery failed");
+            }
+        }
+        if let Some(dsn) = sentry {
+            if let Err(e) = send_sentry(&client, &dsn, &network, &kind, &message, context).await {
+                warn!(kind, error = %e, "sentry delivery failed");
+            }
+        }
+    });
+}
+
+/// Report panics from any thread/task, then defer to the default hook.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let location = info.location().map(|l| format!("{}:{}", l.file(), l.line())).unwrap_or_default();
+        let payload = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panic".to_string());
+        report("panic", format!("panic at {}: {}", location, payload), json!({ "location": location }));
+        default_hook(info);
+    }));
+}
+
+/// Spawn a long-running background task; if it ever returns or panics, log and report it.
+pub fn spawn_supervised<F>(name: &'static str, fut: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let handle = tokio::spawn(fut);
+    tokio::spawn(async move {
+        let msg = match handle.await {
+            Ok(()) => format!("background task '{}' exited", name),
+            Err(e) if e.is_panic() => format!("background task '{}' panicked", name),
+            Err(_) => format!("background task '{}' was cancelled", name),
+        };
+        error!(task = name, "{}", msg);
+        report("task_died", msg, json!({ "task": name }));
+    });
+}
+
+// Legacy Sentry store endpoint; enough for message events without pulling in the SDK.
+async fn send_sentry(client: &Client, dsn: &str, network: &str, kind: &str, message: &str, extra: Value) -> anyhow::Result<()> {
+    let dsn = Url::parse(dsn)?;
+    let key = dsn.username();
+    let project = dsn.path().trim_matches('/');
+    let host = dsn.host_str().ok_or_else(|| anyhow::anyhow!("sentry dsn has no host"))?;
+    let port = dsn.port().map(|p| format!(":{}", p)).unwrap_or_default();
+    let store = format!("{}://{}{}/api/{}/store/", dsn.scheme(), host, port, project);
+
+    let event = json!({
+        "event_id": event_id(),
+        "timestamp": epoch_secs(),
+        "level": "error",
+        "platform": "other",
+        "logger": "rpc-relay",
+        "message": { "formatted": message },
+        "tags": { "kind": kind, "network": network },
+        "extra": extra,
+    });
+    client
+        .post(store)
+        .header("X-Sentry-Auth", format!("Sentry sentry_version=7, sentry_client=rpc-relay/0.1, sentry_key={}", key))
+        .json(&event)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+fn event_id() -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+    let seq = EVENT_SEQ.fetch_add(1, Ordering::Relaxed);
+    format!("{:016x}{:016x}", nanos, seq ^ ((std::process::id() as u64) << 32))
+}
@@ -0,0 +1,117 @@
+use crate::jsonrpc::{JsonRpcError, JsonRpcRequest};
+use crate::relay::{self, HttpState};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+// Read-only REST facade over the JSON-RPC relay. Every call goes through
+// `relay::relay_call`, so selection, failover and cache_ttl apply unchanged.
+
+type Reply = (StatusCode, Json<Value>);
+
+#[derive(Deserialize)]
+pub struct BlockQuery {
+    #[serde(default)]
+    pub full: bool, // include full transaction objects
+}
+
+#[derive(Deserialize)]
+pub struct BalanceQuery {
+    #[serde(default)]
+    pub block: Option<String>, // tag, decimal or 0x-hex; default latest
+}
+
+/// GET /v1/block/{number|tag}?full=true
+pub async fn block(State(state): State<HttpState>, Path(block): Path<String>, Query(q): Query<BlockQuery>) -> Reply {
+    let Some(tag) = block_tag(&block) else { return bad_request(format!("bad block {:?}", block)) };
+    match call(&state, "eth_getBlockByNumber", json!([tag, q.full])).await {
+        Ok(v) => found(v, "block"),
+        Err(r) => r,
+    }
+}
+
+/// GET /v1/balance/{address}?block=latest
+pub async fn balance(State(state): State<HttpState>, Path(address): Path<String>, Query(q): Query<BalanceQuery>) -> Reply {
+    if !is_hex(&address, 20) {
+        return bad_request(format!("bad address {:?}", address));
+    }
+    let block = q.block.unwrap_or_else(|| "latest".to_string());
+    let Some(tag) = block_tag(&block) else { return bad_request(format!("bad block {:?}", block)) };
+    match call(&state, "eth_getBalance", json!([address, tag])).await {
+        Ok(Value::String(hex)) => {
+            // u128 covers any realistic balance; fall back to hex only if not
+            let wei = u128::from_str_radix(hex.trim_start_matches("0x"), 16).ok().map(|w| w.to_string());
+            (StatusCode::OK, Json(json!({ "address": address, "block": tag, "wei": wei, "hex": hex })))
+        }
+        Ok(other) => (StatusCode::BAD_GATEWAY, Json(json!({ "error": "unexpected eth_getBalance result", "result": other }))),
+        Err(r) => r,
+    }
+}
+
+/// GET /v1/tx/{hash}
+pub async fn tx(State(state): State<HttpState>, Path(hash): Path<String>) -> Reply {
+    if !is_hex(&hash, 32) {
+        return bad_request(format!("bad transaction hash {:?}", hash));
+    }
+    match call(&state, "eth_getTransactionByHash", json!([hash])).await {
+        Ok(v) => found(v, "transaction"),
+        Err(r) => r,
+    }
+}
+
+/// GET /v1/tx/{hash}/receipt — 404 until the transaction is mined.
+pub async fn receipt(State(state): State<HttpState>, Path(hash): Path<String>) -> Reply {
+    if !is_hex(&hash, 32) {
+        return bad_request(format!("bad transaction hash {:?}", hash));
+    }
+    match call(&state, "eth_getTransactionReceipt", json!([hash])).await {
+        Ok(v) => found(v, "receipt"),
+        Err(r) => r,
+    }
+}
+
+/// Run one JSON-RPC call through the relay and unwrap its `result`.
+async fn call(state: &HttpState, method: &str, params: Value) -> Result<Value, Reply> {
+    let req = JsonRpcRequest::new(method, params);
+    let (status, Json(mut reply)) = relay::relay_call(state, &state.app.snapshot(), req, None, relay::CallLimits::default(), &mut relay::CallTrace::new()).await;
+    if reply.get("error").is_some() {
+        let err = JsonRpcError::of(&reply);
+        let code = err.as_ref().map(|e| e.code);
+        let message = err.as_ref().map_or("upstream error", |e| e.message.as_str());
+        let status = match (status, code) {
+            (StatusCode::OK, Some(-32602)) => StatusCode::BAD_REQUEST,
+            (StatusCode::OK, _) => StatusCode::BAD_GATEWAY,
+            (s, _) => s,
+        };
+        return Err((status, Json(json!({ "error": message, "code": code }))));
+    }
+    Ok(reply.get_mut("result").map(Value::take).unwrap_or(Value::Null))
+}
+
+fn found(v: Value, what: &str) -> Reply {
+    if v.is_null() {
+        return (StatusCode::NOT_FOUND, Json(json!({ "error": format!("{} not found", what) })));
+    }
+    (StatusCode::OK, Json(v))
+}
+
+fn bad_request(msg: String) -> Reply {
+    (StatusCode::BAD_REQUEST, Json(json!({ "error": msg })))
+}
+
+/// Block tag as JSON-RPC expects it: named tags pass through, decimal numbers become hex.
+fn block_tag(s: &str) -> Option<String> {
+    match s {
+        "latest" | "pending" | "earliest" | "safe" | "finalized" => Some(s.to_string()),
+        _ if s.starts_with("0x") => u64::from_str_radix(&s[2..], 16).ok().map(|n| format!("0x{:x}", n)),
+        _ => s.parse::<u64>().ok().map(|n| format!("0x{:x}", n)),
+    }
+}
+
+fn is_hex(s: &str, bytes: usize) -> bool {
+    s.strip_prefix("0x").is_some_and(|h| h.len() == bytes * 2 && h.bytes().all(|b| b.is_ascii_hexdigit()))
+}
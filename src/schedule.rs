@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+
+const DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+const DAY_MIN: u32 = 24 * 60;
+
+/// When a provider takes traffic. Outside `active` (if given) or inside any `inactive`
+/// window it is kept out of selection like a drained one.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleConfig {
+    #[serde(default)]
+    pub utc_offset: UtcOffset, // windows are wall-clock times at this fixed offset
+    #[serde(default)]
+    pub active: Vec<TimeWindow>,   // empty => always, apart from `inactive`
+    #[serde(default)]
+    pub inactive: Vec<TimeWindow>, // e.g. a nightly backup window
+}
+
+impl ScheduleConfig {
+    pub fn allows(&self, unix_s: u64) -> bool {
+        let local = unix_s as i64 + self.utc_offset.0 as i64 * 60;
+        let (weekday, minute) = week_position(local);
+        let inside = |w: &TimeWindow| w.contains(weekday, minute);
+        (self.active.is_empty() || self.active.iter().any(inside)) && !self.inactive.iter().any(inside)
+    }
+}
+
+// (0 = Monday, minute of the day); 1970-01-01 was a Thursday.
+fn week_position(local_s: i64) -> (usize, u32) {
+    let day = local_s.div_euclid(86_400);
+    ((day + 3).rem_euclid(7) as usize, (local_s.rem_euclid(86_400) / 60) as u32)
+}
+
+/// "+HH:MM" / "-HH:MM" from UTC, stored in minutes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct UtcOffset(pub i32);
+
+impl TryFrom<String> for UtcOffset {
+    type Error = String;
+    fn try_from(src: String) -> Result<Self, String> {
+        let bad = || format!("invalid utc_offset {:?} (expected e.g. \"+02:00\")", src);
+        let (sign, rest) = match src.trim().split_at_checked(1) {
+            Some(("+", rest)) => (1, rest),
+            Some(("-", rest)) => (-1, rest),
+            _ => return Err(bad()),
+        };
+        let minutes = parse_hhmm(rest).filter(|&m| m <= 14 * 60).ok_or_else(bad)?;
+        Ok(Self(sign * minutes as i32))
+    }
+}
+
+impl From<UtcOffset> for String {
+    fn from(o: UtcOffset) -> String {
+        let m = o.0.unsigned_abs();
+        format!("{}{:02}:{:02}", if o.0 < 0 { '-' } else { '+' }, m / 60, m % 60)
+    }
+}
+
+/// "[days] [HH:MM-HH:MM]", e.g. "mon-fri 09:00-18:00", "daily 02:00-03:30", "sat,sun".
+/// A range ending before it starts runs past midnight into the next day.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeWindow {
+    src: String,
+    days: [bool; 7],
+    start: u32, // minutes into the day
+    end: u32,   // exclusive; up to 24:00
+}
+
+impl TimeWindow {
+    fn contains(&self, weekday: usize, minute: u32) -> bool {
+        if self.start < self.end {
+            self.days[weekday] && (self.start..self.end).contains(&minute)
+        } else {
+            // the window opened on the previous day if we're before `end`
+            (self.days[weekday] && minute >= self.start) || (self.days[(weekday + 6) % 7] && minute < self.end)
+        }
+    }
+}
+
+impl TryFrom<String> for TimeWindow {
+    type Error = String;
+    fn try_from(src: String) -> Result<Self, String> {
+        let bad = |why: &str| format!("invalid schedule window {:?}: {}", src, why);
+        let mut days = [true; 7];
+        let (mut start, mut end) = (0, DAY_MIN);
+        let parts: Vec<&str> = src.split_whitespace().collect();
+        if parts.is_empty() || parts.len() > 2 {
+            return Err(bad("expected \"[days] [HH:MM-HH:MM]\""));
+        }
+        for part in parts {
+            if let Some((a, b)) = part.split_once('-').filter(|_| part.contains(':')) {
+                start = parse_hhmm(a).filter(|&m| m < DAY_MIN).ok_or_else(|| bad("bad start time"))?;
+                end = parse_hhmm(b).filter(|&m| m <= DAY_MIN).ok_or_else(|| bad("bad end time"))?;
+                if start == end {
+                    return Err(bad("empty time range"));
+                }
+            } else {
+                days = parse_days(part).ok_or_else(|| bad("bad day list (use e.g. mon-fri, sat,sun or daily)"))?;
+            }
+        }
+        Ok(Self { src, days, start, end })
+    }
+}
+
+impl From<TimeWindow> for String {
+    fn from(w: TimeWindow) -> String { w.src }
+}
+
+fn parse_hhmm(s: &str) -> Option<u32> {
+    let (h, m) = s.split_once(':')?;
+    let (h, m): (u32, u32) = (h.parse().ok()?, m.parse().ok()?);
+    (m < 60).then_some(h * 60 + m)
+}
+
+fn parse_days(s: &str) -> Option<[bool; 7]> {
+    let s = s.to_ascii_lowercase();
+    if s == "daily" || s == "*" {
+        return Some([true; 7]);
+    }
+    let day = |d: &str| DAYS.iter().position(|&n| n == d);
+    let mut days = [false; 7];
+    for item in s.split(',') {
+        let (a, b) = match item.split_once('-') {
+            Some((a, b)) => (day(a)?, day(b)?),
+            None => (day(item)?, day(item)?),
+        };
+        // "fri-mon" wraps over the weekend
+        let mut d = a;
+        loop {
+            days[d] = true;
+            if d == b { break }
+            d = (d + 1) % 7;
+        }
+    }
+    Some(days)
+}
@@ -0,0 +1,406 @@
+//! Candidate selection as pure functions of provider snapshots. `Selector` works on
+//! `ProviderView`s: the relay takes them from live `ProviderState` on every call (see
+//! `relay::relay_call` and `relay::healthy_candidates`), `relay simulate` from a config and a
+//! synthetic traffic profile, so both go through the same steps.
+
+use crate::config::{Config, RoutingPolicy, WeightDecayConfig};
+use crate::state::{HistoryDepth, ProviderRegistry, ProviderState};
+
+// Slots per unit of weight when decay or lag weighting is on, so fractional factors still register.
+const DECAY_RESOLUTION: f64 = 4.0;
+
+// Longest round-robin cycle worked out; bigger slot totals are scaled down to fit.
+const MAX_CYCLE: u64 = 4096;
+
+/// What a provider's share of its tier is computed from.
+#[derive(Clone, Copy, Debug)]
+pub struct Share {
+    pub live: bool,         // healthy, not banned, drained or off schedule
+    pub weight: u32,
+    pub lag_factor: f64,    // 1.0 in sync, 0.0 at the lag threshold
+    pub weight_factor: f64, // weight_decay times lag_factor
+}
+
+/// Slots per provider of one tier; 0 for those not live.
+pub fn weighted_slots(tier: &[Share], decay: bool) -> Vec<u32> {
+    let scaled = decay || tier.iter().any(|s| s.live && s.lag_factor < 1.0);
+    let slots: Vec<u32> = tier
+        .iter()
+        .map(|s| match (s.live, scaled) {
+            (false, _) => 0,
+            // at the lag threshold a provider gets no share; decay alone never drops one
+            (true, true) if s.lag_factor <= 0.0 => 0,
+            (true, true) => (s.weight as f64 * s.weight_factor * DECAY_RESOLUTION).round().max(1.0) as u32,
+            (true, false) => s.weight,
+        })
+        .collect();
+    // everyone at the lag threshold: fall back to plain weights rather than nothing
+    if slots.iter().all(|&s| s == 0) {
+        return tier.iter().map(|s| if s.live { s.weight } else { 0 }).collect();
+    }
+    slots
+}
+
+/// One cycle of smooth weighted round-robin (nginx's current/effective weights) over
+/// `slots`: entry k is the index of the slot owner call k of the cycle starts with.
+/// Each owner appears exactly as often as its slots (after dividing by their common
+/// factor), spread out rather than in runs: 5, 1, 1 gives a a b a c a a. Totals over
+/// MAX_CYCLE are scaled down first, keeping shares to within rounding.
+pub fn smooth_cycle(slots: &[u32]) -> Vec<u32> {
+    let common = slots.iter().fold(0u64, |a, &s| gcd(a, s as u64));
+    if common == 0 {
+        return Vec::new();
+    }
+    let total = slots.iter().map(|&s| s as u64).sum::<u64>() / common;
+    let scale = (MAX_CYCLE as f64 / total as f64).min(1.0);
+    let weights: Vec<i64> = slots
+        .iter()
+        .map(|&s| if s == 0 { 0 } else { ((s as u64 / common) as f64 * scale).round().max(1.0) as i64 })
+        .collect();
+    let total: i64 = weights.iter().sum();
+    let mut current = vec![0i64; weights.len()];
+    (0..total)
+        .map(|_| {
+            current.iter_mut().zip(&weights).for_each(|(c, w)| *c += w);
+            // the first of equals wins, as in nginx
+            let best = (0..weights.len()).filter(|&i| weights[i] > 0).max_by_key(|&i| (current[i], std::cmp::Reverse(i))).unwrap_or(0);
+            current[best] -= total;
+            best as u32
+        })
+        .collect()
+}
+
+fn gcd(a: u64, b: u64) -> u64 { if b == 0 { a } else { gcd(b, a % b) } }
+
+/// Slots per view, in the same order; 0 for the ones out of selection. Primaries take all
+/// traffic while any is live. No per-call filters: this is the pool `healthy_candidates` gives.
+pub fn live_slots(views: &[ProviderView], decay: bool) -> Vec<u32> {
+    let primary = views.iter().any(|v| v.primary && v.share.live);
+    let tier: Vec<usize> = (0..views.len()).filter(|&i| views[i].primary == primary).collect();
+    let shares: Vec<Share> = tier.iter().map(|&i| views[i].share).collect();
+    let mut slots = vec![0; views.len()];
+    for (&i, s) in tier.iter().zip(weighted_slots(&shares, decay)) {
+        slots[i] = s;
+    }
+    slots
+}
+
+/// Healthy, not banned, not drained and inside its schedule.
+pub fn selectable(p: &ProviderState) -> bool {
+    p.is_healthy() && !p.breaker_is_banned() && !p.is_drained() && !p.is_off_schedule()
+}
+
+/// Which latencies latency_threshold_ms keeps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LatencyCut {
+    All,
+    Below(u64),   // some are under the threshold: only those
+    Exactly(u64), // none is: only the fastest
+}
+
+impl LatencyCut {
+    pub fn new(latencies: impl Iterator<Item = u64> + Clone, threshold_ms: Option<u64>) -> Self {
+        let Some(th) = threshold_ms else { return Self::All };
+        if latencies.clone().all(|ms| ms < th) {
+            return Self::All;
+        }
+        if latencies.clone().any(|ms| ms < th) {
+            return Self::Below(th);
+        }
+        latencies.min().map_or(Self::All, Self::Exactly)
+    }
+
+    pub fn keeps(self, ms: u64) -> bool {
+        match self {
+            Self::All => true,
+            Self::Below(th) => ms < th,
+            Self::Exactly(min) => ms == min,
+        }
+    }
+}
+
+/// Whether to leave out the providers low on quota: only while some are and some aren't.
+pub fn avoid_near_quota(near: impl Iterator<Item = bool> + Clone) -> bool {
+    near.clone().any(|n| n) && !near.clone().all(|n| n)
+}
+
+/// The lowest cost_per_million when not everyone shares it.
+pub fn cheapest(costs: impl Iterator<Item = f64> + Clone) -> Option<f64> {
+    let min = costs.clone().min_by(f64::total_cmp)?;
+    (!costs.clone().all(|c| c == min)).then_some(min)
+}
+
+/// Whether to leave out the providers whose history doesn't reach back far enough: only
+/// while some do and some don't, so an unprobed or wrong verdict degrades to no filter.
+pub fn avoid_shallow(covers: impl Iterator<Item = bool> + Clone) -> bool {
+    avoid_near_quota(covers.map(|c| !c))
+}
+
+/// One provider as selection sees it at an instant.
+#[derive(Clone, Debug)]
+pub struct ProviderView {
+    pub primary: bool,
+    pub share: Share,
+    pub latency_ms: u64,
+    pub near_quota: bool,
+    pub cost_per_million: f64, // unset counts as free
+    pub history: HistoryDepth,
+}
+
+impl ProviderView {
+    /// The provider's current state.
+    pub fn of(p: &ProviderState, primary: bool, decay: Option<&WeightDecayConfig>) -> Self {
+        let live = selectable(p);
+        Self {
+            primary,
+            share: Share {
+                live,
+                weight: p.get_weight(),
+                lag_factor: p.lag_factor(),
+                weight_factor: if live { p.weight_factor(decay) } else { 0.0 },
+            },
+            latency_ms: p.get_latency(),
+            near_quota: p.near_quota(),
+            cost_per_million: p.cost_per_million().unwrap_or(0.0),
+            history: p.history_depth(),
+        }
+    }
+}
+
+/// Views of every provider, primaries then secondaries: the order `Selector` slots follow.
+pub fn views(reg: &ProviderRegistry, decay: Option<&WeightDecayConfig>) -> Vec<ProviderView> {
+    let tiers = reg.primaries.iter().map(|p| (p, true)).chain(reg.secondaries.iter().map(|p| (p, false)));
+    tiers.map(|(p, primary)| ProviderView::of(p, primary, decay)).collect()
+}
+
+/// Deterministic first-attempt selection over provider views, as a non-broadcast call sees it.
+#[derive(Clone, Debug)]
+pub struct Selector {
+    pub decay: bool,
+    pub latency_threshold_ms: Option<u64>,
+    pub routing: RoutingPolicy,
+    pub behind: Option<u64>, // blocks below the head the call reads, when archive_probe is on and it pins one
+}
+
+impl Selector {
+    /// Settings from `cfg`; `degraded` swaps in health_monitor.degradation's latency threshold.
+    pub fn new(cfg: &Config, degraded: bool) -> Self {
+        let rc = &cfg.relay;
+        let latency_threshold_ms = match (&cfg.health_monitor.degradation, degraded) {
+            (Some(d), true) => d.latency_threshold_ms,
+            _ => rc.latency_threshold_ms,
+        };
+        Self { decay: rc.weight_decay.is_some(), latency_threshold_ms, routing: rc.routing, behind: None }
+    }
+
+    /// `live_slots`, then the per-call cuts of `cut`.
+    pub fn slots(&self, views: &[ProviderView]) -> Vec<u32> {
+        self.cut(views, live_slots(views, self.decay))
+    }
+
+    /// The per-call cuts on `live_slots` output, each within what the one before kept:
+    /// latency_threshold_ms, providers near quota, then history depth.
+    pub fn cut(&self, views: &[ProviderView], mut slots: Vec<u32>) -> Vec<u32> {
+        let picked = |slots: &[u32]| (0..views.len()).filter(|&i| slots[i] > 0).collect::<Vec<_>>();
+
+        let live = picked(&slots);
+        let cut = LatencyCut::new(live.iter().map(|&i| views[i].latency_ms), self.latency_threshold_ms);
+        live.iter().filter(|&&i| !cut.keeps(views[i].latency_ms)).for_each(|&i| slots[i] = 0);
+
+        let live = picked(&slots);
+        if avoid_near_quota(live.iter().map(|&i| views[i].near_quota)) {
+            live.iter().filter(|&&i| views[i].near_quota).for_each(|&i| slots[i] = 0);
+        }
+
+        if let Some(behind) = self.behind {
+            let live = picked(&slots);
+            if avoid_shallow(live.iter().map(|&i| views[i].history.covers(behind))) {
+                live.iter().filter(|&&i| !views[i].history.covers(behind)).for_each(|&i| slots[i] = 0);
+            }
+        }
+        slots
+    }
+
+    /// With routing: cost, the cheapest of `slots`, which a call tries before the rest.
+    pub fn preferred(&self, views: &[ProviderView], slots: &[u32]) -> Option<Vec<u32>> {
+        if self.routing != RoutingPolicy::Cost {
+            return None;
+        }
+        let cost = |i: usize| views[i].cost_per_million;
+        let min = cheapest((0..views.len()).filter(|&i| slots[i] > 0).map(cost))?;
+        Some((0..views.len()).map(|i| if cost(i) == min { slots[i] } else { 0 }).collect())
+    }
+
+    /// View indexes in the order a call with round-robin index `idx` tries them, like
+    /// `Candidates::rotation`: entry `idx` of `cycle` (the smooth_cycle of `slots`) first,
+    /// then the others in order.
+    pub fn rotation(slots: &[u32], cycle: &[u32], idx: usize) -> Vec<usize> {
+        let Some(&start) = cycle.get(idx % cycle.len().max(1)) else { return Vec::new() };
+        let n = slots.len();
+        (0..n).map(|k| (start as usize + k) % n).filter(|&i| slots[i] > 0).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn share(weight: u32) -> Share {
+        Share { live: true, weight, lag_factor: 1.0, weight_factor: 1.0 }
+    }
+
+    fn view(primary: bool, weight: u32, latency_ms: u64) -> ProviderView {
+        ProviderView { primary, share: share(weight), latency_ms, near_quota: false, cost_per_million: 0.0, history: HistoryDepth::Unknown }
+    }
+
+    fn selector(latency_threshold_ms: Option<u64>) -> Selector {
+        Selector { decay: false, latency_threshold_ms, routing: RoutingPolicy::Weighted, behind: None }
+    }
+
+    #[test]
+    fn slots_follow_plain_weights() {
+        assert_eq!(weighted_slots(&[share(3), share(1), share(2)], false), vec![3, 1, 2]);
+        let down = Share { live: false, ..share(5) };
+        assert_eq!(weighted_slots(&[share(3), down, share(2)], false), vec![3, 0, 2]);
+    }
+
+    #[test]
+    fn decay_and_lag_scale_slots() {
+        let decayed = Share { weight_factor: 0.5, ..share(2) };
+        assert_eq!(weighted_slots(&[share(2), decayed], true), vec![8, 4]);
+        // a tiny factor still leaves one slot; decay alone never drops a provider
+        let crushed = Share { weight_factor: 0.001, ..share(1) };
+        assert_eq!(weighted_slots(&[share(1), crushed], true), vec![4, 1]);
+        // lag weighting scales even without decay, and drops a provider at the threshold
+        let lagging = Share { lag_factor: 0.5, weight_factor: 0.5, ..share(2) };
+        let at_limit = Share { lag_factor: 0.0, weight_factor: 0.0, ..share(2) };
+        assert_eq!(weighted_slots(&[share(2), lagging, at_limit], false), vec![8, 4, 0]);
+    }
+
+    #[test]
+    fn everyone_at_the_lag_limit_falls_back_to_weights() {
+        let at_limit = |w| Share { lag_factor: 0.0, weight_factor: 0.0, ..share(w) };
+        assert_eq!(weighted_slots(&[at_limit(2), at_limit(1)], false), vec![2, 1]);
+    }
+
+    #[test]
+    fn rotation_spreads_calls_by_weight() {
+        let slots = [3, 1, 2];
+        let cycle = smooth_cycle(&slots);
+        let mut firsts = [0; 3];
+        for idx in 0..600 {
+            let order = Selector::rotation(&slots, &cycle, idx);
+            assert_eq!(order.len(), 3);
+            firsts[order[0]] += 1;
+        }
+        assert_eq!(firsts, [300, 100, 200]);
+        assert!(Selector::rotation(&[0, 0], &smooth_cycle(&[0, 0]), 5).is_empty());
+        assert_eq!(Selector::rotation(&[0, 2, 0], &smooth_cycle(&[0, 2, 0]), 7), vec![1]);
+        assert_eq!(Selector::rotation(&slots, &cycle, 1), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn smooth_cycle_interleaves() {
+        assert_eq!(smooth_cycle(&[5, 1, 1]), vec![0, 0, 1, 0, 2, 0, 0]);
+        assert_eq!(smooth_cycle(&[3, 1]), vec![0, 0, 1, 0]);
+        // a common factor doesn't lengthen the cycle; zero slots never come up
+        assert_eq!(smooth_cycle(&[40, 0, 20]), vec![0, 2, 0]);
+        assert!(smooth_cycle(&[0, 0]).is_empty());
+        // large totals are scaled to MAX_CYCLE, shares kept
+        let big = smooth_cycle(&[30_001, 10_000]);
+        assert_eq!(big.len(), MAX_CYCLE as usize);
+        assert_eq!(big.iter().filter(|&&i| i == 1).count(), 1024);
+    }
+
+    #[test]
+    fn latency_cut_without_threshold_keeps_all() {
+        assert_eq!(LatencyCut::new([5, 5000].into_iter(), None), LatencyCut::All);
+    }
+
+    #[test]
+    fn latency_cut_all_equal() {
+        // all under the threshold: nothing is cut
+        assert_eq!(LatencyCut::new([40, 40, 40].into_iter(), Some(100)), LatencyCut::All);
+        // all over it: the fastest are kept, which is all of them
+        let cut = LatencyCut::new([400, 400].into_iter(), Some(100));
+        assert_eq!(cut, LatencyCut::Exactly(400));
+        assert!(cut.keeps(400));
+    }
+
+    #[test]
+    fn latency_cut_one_outlier() {
+        let cut = LatencyCut::new([50, 60, 900].into_iter(), Some(200));
+        assert_eq!(cut, LatencyCut::Below(200));
+        assert!(cut.keeps(60) && !cut.keeps(900) && !cut.keeps(200));
+        // an unprobed provider (u64::MAX) is an outlier too
+        assert_eq!(LatencyCut::new([u64::MAX, 10].into_iter(), Some(100)), LatencyCut::Below(100));
+    }
+
+    #[test]
+    fn latency_cut_zero_latency() {
+        assert_eq!(LatencyCut::new([0, 0].into_iter(), Some(1)), LatencyCut::All);
+        // nothing is under a 0 ms threshold, so only the fastest stay
+        let cut = LatencyCut::new([0, 5].into_iter(), Some(0));
+        assert_eq!(cut, LatencyCut::Exactly(0));
+        assert!(cut.keeps(0) && !cut.keeps(5));
+        assert_eq!(LatencyCut::new(std::iter::empty(), Some(100)), LatencyCut::All);
+    }
+
+    #[test]
+    fn primaries_take_all_traffic_while_any_is_live() {
+        let views = [view(true, 1, 10), view(true, 2, 10), view(false, 5, 10)];
+        assert_eq!(selector(None).slots(&views), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn falls_back_to_secondaries_when_no_primary_is_selectable() {
+        let mut views = [view(true, 1, 10), view(true, 2, 10), view(false, 5, 10), view(false, 1, 10)];
+        for v in views.iter_mut().filter(|v| v.primary) {
+            v.share.live = false;
+        }
+        assert_eq!(selector(None).slots(&views), vec![0, 0, 5, 1]);
+    }
+
+    #[test]
+    fn filters_apply_within_the_tier() {
+        let mut views = [view(true, 1, 20), view(true, 1, 900), view(true, 1, 30)];
+        assert_eq!(selector(Some(100)).slots(&views), vec![1, 0, 1]);
+        views[0].near_quota = true;
+        assert_eq!(selector(Some(100)).slots(&views), vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn history_cut_needs_a_pinned_block() {
+        let mut views = [view(true, 1, 10), view(true, 1, 10), view(true, 1, 10)];
+        views[0].history = HistoryDepth::Blocks(128);
+        views[1].history = HistoryDepth::Archive;
+        assert_eq!(selector(None).slots(&views), vec![1, 1, 1]);
+        let old = Selector { behind: Some(5000), ..selector(None) };
+        assert_eq!(old.slots(&views), vec![0, 1, 1]);
+        // within everyone's depth, or nobody's: no cut
+        assert_eq!(Selector { behind: Some(100), ..selector(None) }.slots(&views), vec![1, 1, 1]);
+        views[1].history = HistoryDepth::Blocks(0);
+        views[2].history = HistoryDepth::Blocks(0);
+        assert_eq!(old.slots(&views), vec![1, 1, 1]);
+    }
+
+    #[test]
+    fn cost_routing_prefers_the_cheapest() {
+        let mut views = [view(true, 1, 10), view(true, 1, 10)];
+        views[0].cost_per_million = 0.5;
+        let cost = Selector { routing: RoutingPolicy::Cost, ..selector(None) };
+        let slots = cost.slots(&views);
+        assert_eq!(cost.preferred(&views, &slots), Some(vec![0, 1]));
+        assert_eq!(selector(None).preferred(&views, &slots), None);
+    }
+
+    #[test]
+    fn helpers() {
+        assert!(avoid_near_quota([true, false].into_iter()));
+        assert!(!avoid_near_quota([true, true].into_iter()));
+        assert!(!avoid_near_quota([false, false].into_iter()));
+        assert_eq!(cheapest([1.0, 0.5, 0.5].into_iter()), Some(0.5));
+        assert_eq!(cheapest([0.5, 0.5].into_iter()), None);
+        assert_eq!(cheapest(std::iter::empty()), None);
+    }
+}
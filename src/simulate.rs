@@ -0,0 +1,174 @@
+use crate::config::Config;
+use crate::selector::{self, smooth_cycle, ProviderView, Selector};
+use crate::state::build_registry;
+use serde_json::{json, Value};
+
+const DEFAULT_REQUESTS: usize = 10_000;
+const DEFAULT_RPS: f64 = 100.0;
+
+/// `relay simulate [--requests N] [--rps R] [--latency NAME=MS]... [--down NAME]...
+/// [--near-quota NAME]... [--degraded] [--json]`: replay N calls arriving at R per second
+/// through the relay's selection for the config, with every provider healthy and answering
+/// unless the profile says otherwise, and print where they would go. Providers are named by
+/// their /status label. Returns the process exit code.
+pub fn run(cfg: &Config, args: &[String]) -> i32 {
+    let Some(profile) = Profile::parse(args) else {
+        eprintln!("usage: simulate [--requests N] [--rps R] [--latency NAME=MS]... [--down NAME]... [--near-quota NAME]... [--degraded] [--json]");
+        return 2;
+    };
+    let reg = build_registry(&cfg.rpc_endpoints, &cfg.http_client, &cfg.relay.default_headers);
+    let views = selector::views(&reg, cfg.relay.weight_decay.as_ref());
+    let mut providers: Vec<Sim> = reg
+        .all()
+        .iter()
+        .zip(views)
+        .map(|(p, mut view)| {
+            let label = p.label();
+            profile.apply(&label, &mut view);
+            let max_tps = p.bucket.snapshot().map(|(rate, _, capacity)| (rate, capacity));
+            Sim { tokens: max_tps.map_or(0.0, |(_, capacity)| capacity), max_tps, label, view, served: 0 }
+        })
+        .collect();
+    if providers.is_empty() {
+        eprintln!("simulate: no providers configured");
+        return 1;
+    }
+    if let Some(unknown) = profile.names().find(|n| !providers.iter().any(|s| s.label == **n)) {
+        eprintln!("simulate: no provider labelled {:?}", unknown);
+        return 2;
+    }
+
+    let selector = Selector::new(cfg, profile.degraded);
+    let views: Vec<ProviderView> = providers.iter().map(|s| s.view.clone()).collect();
+    let slots = selector.slots(&views);
+    let preferred = selector.preferred(&views, &slots);
+    let first = preferred.as_ref().unwrap_or(&slots);
+    let total_first: u32 = first.iter().sum();
+
+    let (cycle, preferred_cycle) = (smooth_cycle(&slots), preferred.as_deref().map(smooth_cycle).unwrap_or_default());
+    let mut limited = 0usize;
+    let step = 1.0 / profile.rps;
+    for idx in 0..profile.requests {
+        for s in providers.iter_mut() {
+            if let Some((rate, capacity)) = s.max_tps {
+                s.tokens = (s.tokens + rate * step).min(capacity);
+            }
+        }
+        // the cheapest go first (routing: cost); anyone else only if none of them has a token
+        let mut order = preferred
+            .iter()
+            .flat_map(|p| Selector::rotation(p, &preferred_cycle, idx))
+            .chain(Selector::rotation(&slots, &cycle, idx));
+        let taken = order.find(|&i| providers[i].try_take());
+        match taken {
+            Some(i) => providers[i].served += 1,
+            None => limited += 1,
+        }
+    }
+
+    let rows: Vec<Value> = providers
+        .iter()
+        .zip(first)
+        .map(|(s, &slots)| {
+            json!({
+                "provider": s.label,
+                "tier": if s.view.primary { "primary" } else { "secondary" },
+                "weight": s.view.share.weight,
+                "slots": slots,
+                "expected_share": if total_first == 0 { 0.0 } else { slots as f64 / total_first as f64 },
+                "served": s.served,
+                "share": s.served as f64 / profile.requests.max(1) as f64,
+            })
+        })
+        .collect();
+    if profile.json {
+        let out = json!({ "requests": profile.requests, "rps": profile.rps, "rate_limited": limited, "providers": rows });
+        println!("{}", serde_json::to_string_pretty(&out).unwrap_or_default());
+        return 0;
+    }
+    let width = providers.iter().map(|s| s.label.len()).max().unwrap_or(0).max(8);
+    println!("{} calls at {} rps ({:.1}s)", profile.requests, profile.rps, profile.requests as f64 / profile.rps);
+    println!("{:<width$} {:<9} {:>6} {:>6} {:>9} {:>9} {:>7}", "provider", "tier", "weight", "slots", "expected", "served", "share");
+    for r in &rows {
+        let num = |k: &str| r[k].as_f64().unwrap_or(0.0);
+        println!(
+            "{:<width$} {:<9} {:>6} {:>6} {:>8.1}% {:>9} {:>6.1}%",
+            r["provider"].as_str().unwrap_or_default(),
+            r["tier"].as_str().unwrap_or_default(),
+            num("weight"),
+            num("slots"),
+            num("expected_share") * 100.0,
+            num("served"),
+            num("share") * 100.0,
+        );
+    }
+    println!("rate limited (no provider had a token): {}", limited);
+    0
+}
+
+// One provider during the run; its token bucket follows simulated time, not the clock.
+struct Sim {
+    label: String,
+    view: ProviderView,
+    max_tps: Option<(f64, f64)>, // (refill per second, burst); None => unlimited
+    tokens: f64,
+    served: usize,
+}
+
+impl Sim {
+    fn try_take(&mut self) -> bool {
+        if self.max_tps.is_none() {
+            return true;
+        }
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+struct Profile {
+    requests: usize,
+    rps: f64,
+    latency: Vec<(String, u64)>,
+    down: Vec<String>,
+    near_quota: Vec<String>,
+    degraded: bool, // health_monitor.degradation's latency threshold applies
+    json: bool,
+}
+
+impl Profile {
+    fn parse(args: &[String]) -> Option<Self> {
+        let mut p = Self { requests: DEFAULT_REQUESTS, rps: DEFAULT_RPS, latency: Vec::new(), down: Vec::new(), near_quota: Vec::new(), degraded: false, json: false };
+        let mut it = args.iter();
+        while let Some(a) = it.next() {
+            match a.as_str() {
+                "--requests" => p.requests = it.next()?.parse().ok().filter(|&n| n > 0)?,
+                "--rps" => p.rps = it.next()?.parse().ok().filter(|&r: &f64| r > 0.0)?,
+                "--latency" => {
+                    let (name, ms) = it.next()?.rsplit_once('=')?;
+                    p.latency.push((name.to_string(), ms.parse().ok()?));
+                }
+                "--down" => p.down.push(it.next()?.clone()),
+                "--near-quota" => p.near_quota.push(it.next()?.clone()),
+                "--degraded" => p.degraded = true,
+                "--json" => p.json = true,
+                _ => return None,
+            }
+        }
+        Some(p)
+    }
+
+    fn names(&self) -> impl Iterator<Item = &String> {
+        self.latency.iter().map(|(n, _)| n).chain(&self.down).chain(&self.near_quota)
+    }
+
+    // Unlisted providers answer at once and are within quota; all start in sync.
+    fn apply(&self, label: &str, view: &mut ProviderView) {
+        let named = |list: &[String]| list.iter().any(|n| n == label);
+        view.latency_ms = self.latency.iter().find(|(n, _)| n == label).map_or(0, |&(_, ms)| ms);
+        view.near_quota = named(&self.near_quota);
+        view.share.live &= !named(&self.down);
+    }
+}
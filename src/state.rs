@@ -1,43 +1,319 @@
-use crate::circuit_breaker::{BreakerConfig, CircuitBreaker};
-use crate::config::{Config, Endpoint, RpcEndpoints};
+use crate::adaptive_tps::AdaptiveTps;
+use crate::cache_shadow::CacheShadow;
+use crate::circuit_breaker::{BanSource, BreakerConfig, CircuitBreaker, ManualBan};
+use crate::config::{BanConfig, Config, DegradationConfig, Endpoint, HttpClientConfig, QuotaGuardConfig, RpcEndpoints, WeightDecayConfig};
+use crate::namespace_stats::NamespaceStats;
+use crate::origin_stats::OriginStats;
+use crate::rates::RateWindows;
+use crate::redact::mask_url;
+use crate::schedule::ScheduleConfig;
+use crate::relay_totals::RelayTotals;
+use crate::timing::{ConnectTiming, PhaseStats};
 use crate::token_bucket::TokenBucket;
+use crate::upstream::UpstreamTransport;
+use crate::uptime::UptimeTracker;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::Client;
 use std::sync::{
     atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
     Arc,
 };
-use tokio::sync::RwLock;
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
 
 #[derive(Debug)]
 pub struct ProviderState {
+    pub id: String,           // Endpoint::id(): name, or URL when unnamed
+    pub name: Option<String>,
     pub url: String,
+    graphql_url: parking_lot::RwLock<Option<String>>,
+    display: parking_lot::RwLock<String>, // safe to print: display_name or masked URL
+    transport: Arc<dyn UpstreamTransport>,
+    client: parking_lot::RwLock<Client>, // dedicated pool so a slow provider can't starve the others
+    client_meta: parking_lot::Mutex<ClientMeta>,
     pub weight: AtomicU32,
     pub max_tps: AtomicU32, // 0 => unlimited
     pub healthy: AtomicBool,
     pub latest_block: AtomicU64,
     pub behind: AtomicU64,
+    lag_factor: AtomicU32, // permille of weight kept for lag (health_monitor.lag_weighting)
     pub latency_ms: AtomicU64,
     pub errors: AtomicU64,
     pub call_count: AtomicU64, // attempts
-    pub bucket: parking_lot::Mutex<TokenBucket>,
+    pub success_count: AtomicU64, // replies with a usable result
+    pub failure_count: AtomicU64, // error replies and transport failures (abandoned legs count as neither)
+    pub probe_count: AtomicU64,   // health probes; never mixed into the serving counters above
+    pub probe_failures: AtomicU64,
+    pub probes_skipped: AtomicU64, // rounds skipped for lack of probe budget
+    probe_budget: parking_lot::Mutex<Option<(u32, TokenBucket)>>, // (per minute, bucket) from probe_budget_per_min
+    pub bucket: TokenBucket, // lock-free: taken on every selection
+    pub bucket_rejections: AtomicU64, // skipped at selection for lack of a token
+    adaptive: parking_lot::Mutex<Option<AdaptiveTps>>, // drives the bucket rate when adaptive_tps is set
     pub breaker: parking_lot::Mutex<CircuitBreaker>,
+    pub uptime: parking_lot::Mutex<UptimeTracker>, // health-monitor verdicts over time
+    pub rates: parking_lot::Mutex<RateWindows>,    // completed attempts over the last 1m/5m/15m
+    drained: AtomicBool,     // effective: config value, or the admin API since it last changed
+    drained_cfg: AtomicBool, // last value seen in config
+    ban_cfg: parking_lot::Mutex<Option<BanConfig>>, // last value seen in config
+    schedule: parking_lot::RwLock<Option<ScheduleConfig>>,
+    cost_per_million: parking_lot::RwLock<Option<f64>>,
+    max_blocks_behind: parking_lot::RwLock<Option<u64>>, // endpoint override of the health_monitor limit
+    infrastructure: parking_lot::RwLock<Option<String>>,
+    off_schedule: AtomicBool, // last verdict, to log window changes once
+    error_rate: parking_lot::Mutex<ErrorRate>,       // breaker-relevant outcomes only
+    history: parking_lot::Mutex<(HistoryDepth, Option<Instant>)>, // archive probe verdict, and when
+    captured: parking_lot::Mutex<HeaderCapture>,
+    pub phases: parking_lot::Mutex<PhaseStats>, // connect / TTFB / body averages
+}
+
+// Latest values of the response headers an endpoint asks to keep (capture_headers, quota_guard).
+#[derive(Debug, Default)]
+struct HeaderCapture {
+    names: Vec<String>, // lowercase
+    guard: Option<QuotaGuardConfig>,
+    latest: Vec<(String, String, Instant)>, // (name, value, seen at)
+}
+
+impl HeaderCapture {
+    fn configure(&mut self, ep: &Endpoint) {
+        let mut names: Vec<String> = ep.capture_headers.iter().map(|h| h.to_ascii_lowercase()).collect();
+        if let Some(g) = &ep.quota_guard {
+            names.push(g.header.to_ascii_lowercase());
+        }
+        names.sort();
+        names.dedup();
+        self.latest.retain(|(n, _, _)| names.contains(n));
+        self.names = names;
+        self.guard = ep.quota_guard.clone();
+    }
+}
+
+/// How far behind the head a provider can still serve state, from archive probing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HistoryDepth {
+    #[default]
+    Unknown, // not probed (yet); eligible for everything
+    Archive,
+    Blocks(u64), // deepest probe it answered; 0 if it answered none
+}
+
+impl HistoryDepth {
+    pub fn covers(self, behind: u64) -> bool {
+        match self {
+            HistoryDepth::Unknown | HistoryDepth::Archive => true,
+            HistoryDepth::Blocks(n) => behind <= n,
+        }
+    }
+}
+
+fn probe_bucket(per_min: Option<u32>) -> Option<(u32, TokenBucket)> {
+    per_min.filter(|&n| n > 0).map(|n| {
+        let b = TokenBucket::new(1);
+        b.set_rate(n as f64 / 60.0);
+        (n, b)
+    })
+}
+
+/// Exponentially weighted error rate that also fades with wall time, so an
+/// idle provider recovers even without traffic.
+#[derive(Debug)]
+struct ErrorRate {
+    ewma: f64,
+    at: Instant,
+    half_life: Duration, // refreshed from relay.weight_decay on each selection
+}
+
+const ERROR_RATE_ALPHA: f64 = 0.1;
+
+impl ErrorRate {
+    fn current(&self) -> f64 {
+        let hl = self.half_life.as_secs_f64().max(1.0);
+        self.ewma * 0.5f64.powf(self.at.elapsed().as_secs_f64() / hl)
+    }
+
+    fn record(&mut self, failed: bool) {
+        let x = if failed { 1.0 } else { 0.0 };
+        self.ewma = self.current() * (1.0 - ERROR_RATE_ALPHA) + ERROR_RATE_ALPHA * x;
+        self.at = Instant::now();
+    }
 }
 
 impl ProviderState {
-    pub fn from_endpoint(ep: &Endpoint) -> Arc<Self> {
+    pub fn from_endpoint(ep: &Endpoint, http: &HttpClientConfig, headers: &BTreeMap<String, String>) -> Arc<Self> {
+        Self::with_transport(ep, http, headers, crate::upstream::transport_for(&ep.url))
+    }
+
+    /// As from_endpoint, with calls going through `transport` instead of the one for its URL.
+    pub fn with_transport(
+        ep: &Endpoint,
+        http: &HttpClientConfig,
+        headers: &BTreeMap<String, String>,
+        transport: Arc<dyn UpstreamTransport>,
+    ) -> Arc<Self> {
         let mtps = ep.max_tps.unwrap_or(0);
-        Arc::new(Self {
+        let opts = ClientOptions::new(ep, http, headers);
+        let p = Arc::new(Self {
+            id: ep.id().to_string(),
+            name: ep.name.clone(),
             url: ep.url.clone(),
+            graphql_url: parking_lot::RwLock::new(ep.graphql_endpoint()),
+            display: parking_lot::RwLock::new(display_label(ep)),
+            transport,
+            client: parking_lot::RwLock::new(opts.build().unwrap_or_else(|e| {
+                // Only TLS/resolver setup can fail here; the defaults lack our headers and pool settings.
+                error!(provider = %display_label(ep), error = %e, "HTTP client could not be built; using reqwest defaults");
+                Client::new()
+            })),
+            client_meta: parking_lot::Mutex::new(ClientMeta::new(opts)),
             weight: AtomicU32::new(ep.weight.max(1)),
             max_tps: AtomicU32::new(mtps),
             healthy: AtomicBool::new(true),
             latest_block: AtomicU64::new(0),
             behind: AtomicU64::new(0),
+            lag_factor: AtomicU32::new(1000),
             latency_ms: AtomicU64::new(u64::MAX),
             errors: AtomicU64::new(0),
             call_count: AtomicU64::new(0),
-            bucket: parking_lot::Mutex::new(TokenBucket::new(mtps)),
+            success_count: AtomicU64::new(0),
+            failure_count: AtomicU64::new(0),
+            probe_count: AtomicU64::new(0),
+            probe_failures: AtomicU64::new(0),
+            probes_skipped: AtomicU64::new(0),
+            probe_budget: parking_lot::Mutex::new(probe_bucket(ep.probe_budget_per_min)),
+            bucket: TokenBucket::new(mtps),
+            bucket_rejections: AtomicU64::new(0),
+            adaptive: parking_lot::Mutex::new(ep.adaptive_tps.clone().map(|c| AdaptiveTps::new(c, mtps))),
             breaker: parking_lot::Mutex::new(CircuitBreaker::default()),
-        })
+            uptime: parking_lot::Mutex::new(UptimeTracker::default()),
+            rates: parking_lot::Mutex::new(RateWindows::default()),
+            drained: AtomicBool::new(ep.drained),
+            drained_cfg: AtomicBool::new(ep.drained),
+            ban_cfg: parking_lot::Mutex::new(None),
+            schedule: parking_lot::RwLock::new(ep.schedule.clone()),
+            cost_per_million: parking_lot::RwLock::new(ep.cost_per_million),
+            max_blocks_behind: parking_lot::RwLock::new(ep.max_blocks_behind),
+            infrastructure: parking_lot::RwLock::new(ep.infrastructure.clone()),
+            off_schedule: AtomicBool::new(false),
+            error_rate: parking_lot::Mutex::new(ErrorRate { ewma: 0.0, at: Instant::now(), half_life: Duration::from_secs(60) }),
+            history: parking_lot::Mutex::new((HistoryDepth::Unknown, None)),
+            captured: parking_lot::Mutex::new(HeaderCapture::default()),
+            phases: parking_lot::Mutex::new(PhaseStats::default()),
+        });
+        p.captured.lock().configure(ep);
+        p.apply_ban_config(ep.ban.as_ref());
+        p
+    }
+
+    pub fn client(&self) -> Client { self.client.read().clone() }
+
+    pub fn transport(&self) -> &dyn UpstreamTransport { &*self.transport }
+
+    /// Credential-free identifier for logs, /status and the TUI.
+    pub fn label(&self) -> String { self.display.read().clone() }
+
+    /// Set only for providers flagged `supports_graphql`.
+    pub fn graphql_url(&self) -> Option<String> { self.graphql_url.read().clone() }
+
+    /// Updated endpoint/config values for this provider, prepared (HTTP client included, if
+    /// its settings changed) without touching it; `StagedUpdate::apply` makes them live.
+    pub fn stage_update(self: &Arc<Self>, ep: &Endpoint, http: &HttpClientConfig, headers: &BTreeMap<String, String>) -> StagedUpdate {
+        let opts = ClientOptions::new(ep, http, headers);
+        let client = (self.client_meta.lock().opts != opts).then(|| {
+            let built = opts.build();
+            (opts, built)
+        });
+        StagedUpdate { provider: self.clone(), ep: ep.clone(), client }
+    }
+
+    // Apply updated endpoint/config values, keeping stats; see `stage_update`.
+    fn update_from_endpoint(&self, ep: &Endpoint, client: Option<(ClientOptions, reqwest::Result<Client>)>) {
+        *self.display.write() = display_label(ep);
+        *self.graphql_url.write() = ep.graphql_endpoint();
+        // an admin drain/undrain survives reloads until the config value itself changes
+        if self.drained_cfg.swap(ep.drained, Ordering::Relaxed) != ep.drained {
+            self.set_drained(ep.drained);
+        }
+        self.apply_ban_config(ep.ban.as_ref());
+        *self.schedule.write() = ep.schedule.clone();
+        *self.cost_per_million.write() = ep.cost_per_million;
+        *self.max_blocks_behind.write() = ep.max_blocks_behind;
+        *self.infrastructure.write() = ep.infrastructure.clone();
+        self.captured.lock().configure(ep);
+        self.weight.store(ep.weight.max(1), Ordering::Relaxed);
+        let new_mtps = ep.max_tps.unwrap_or(0);
+        let old_mtps = self.max_tps.load(Ordering::Relaxed);
+        let mut adaptive = self.adaptive.lock();
+        let adaptive_changed = adaptive.as_ref().map(|a| &a.cfg) != ep.adaptive_tps.as_ref();
+        if new_mtps != old_mtps || adaptive_changed {
+            // either change restarts learning from the configured value
+            self.max_tps.store(new_mtps, Ordering::Relaxed);
+            self.bucket.reset(new_mtps);
+            *adaptive = ep.adaptive_tps.clone().map(|c| AdaptiveTps::new(c, new_mtps));
+        }
+        drop(adaptive);
+        let mut budget = self.probe_budget.lock();
+        if budget.as_ref().map(|(n, _)| *n) != ep.probe_budget_per_min.filter(|&n| n > 0) {
+            *budget = probe_bucket(ep.probe_budget_per_min);
+        }
+        drop(budget);
+        if let Some((opts, built)) = client {
+            let mut meta = self.client_meta.lock();
+            meta.opts = opts;
+            self.install_client(&mut meta, built);
+            self.clear_misconfigured("client settings changed on reload");
+        }
+    }
+
+    // In-flight requests keep their clone of the old client; its pool drains with them.
+    fn rebuild_client(&self, meta: &mut ClientMeta) {
+        let built = meta.opts.build();
+        self.install_client(meta, built);
+    }
+
+    fn install_client(&self, meta: &mut ClientMeta, built: reqwest::Result<Client>) {
+        match built {
+            Ok(client) => *self.client.write() = client,
+            Err(e) => error!(provider = %self.label(), error = %e, "HTTP client could not be rebuilt; keeping the previous one"),
+        }
+        meta.built_at = Instant::now();
+    }
+
+    /// Recycle the connection pool when it outlives `connection_max_lifetime_s`.
+    pub fn recycle_client_if_expired(&self) {
+        let mut meta = self.client_meta.lock();
+        let lifetime = meta.opts.connection_max_lifetime_s;
+        if lifetime > 0 && meta.built_at.elapsed() >= Duration::from_secs(lifetime) {
+            self.rebuild_client(&mut meta);
+        }
+    }
+
+    /// Returns true (and starts the next refresh period) when a DNS re-resolution is due.
+    pub fn claim_dns_refresh(&self) -> bool {
+        let mut meta = self.client_meta.lock();
+        let every = meta.opts.dns_refresh_s;
+        let due = every > 0 && meta.resolved_at.map(|t| t.elapsed() >= Duration::from_secs(every)).unwrap_or(true);
+        if due {
+            meta.resolved_at = Some(Instant::now());
+        }
+        due
+    }
+
+    /// Record freshly resolved addresses; rebuilds the pool if they differ from the previous lookup.
+    pub fn apply_resolved_addrs(&self, mut addrs: Vec<IpAddr>) {
+        addrs.sort();
+        addrs.dedup();
+        let mut meta = self.client_meta.lock();
+        if addrs.is_empty() || addrs == meta.resolved {
+            return;
+        }
+        let changed = !meta.resolved.is_empty();
+        meta.resolved = addrs;
+        if changed {
+            info!(provider = %self.label(), "upstream resolved to new addresses {:?}; recycling connections", meta.resolved);
+            self.rebuild_client(&mut meta);
+        }
     }
 
     pub fn is_healthy(&self) -> bool {
@@ -48,11 +324,198 @@ impl ProviderState {
         self.healthy.store(ok, Ordering::Relaxed);
     }
 
+    pub fn is_drained(&self) -> bool { self.drained.load(Ordering::Relaxed) }
+    pub fn set_drained(&self, on: bool) {
+        if self.drained.swap(on, Ordering::Relaxed) != on {
+            info!(provider = %self.label(), drained = on, "provider drain state changed");
+        }
+    }
+
+    /// Outside its schedule windows right now (kept out of selection like a drained provider).
+    pub fn is_off_schedule(&self) -> bool {
+        let now = crate::timing::epoch_secs();
+        let off = self.schedule.read().as_ref().is_some_and(|s| !s.allows(now));
+        if self.off_schedule.swap(off, Ordering::Relaxed) != off {
+            info!(provider = %self.label(), off_schedule = off, "provider schedule window changed");
+        }
+        off
+    }
+
+    // Same rule as drain: config only wins when its own value changes, so an
+    // admin ban/unban isn't undone by unrelated reloads.
+    fn apply_ban_config(&self, ban: Option<&BanConfig>) {
+        let mut last = self.ban_cfg.lock();
+        if last.as_ref() == ban {
+            return;
+        }
+        *last = ban.cloned();
+        drop(last);
+        let current = self.manual_ban().map(|b| b.source);
+        match ban {
+            Some(b) => self.set_manual_ban(Some(ManualBan {
+                reason: b.reason.clone(),
+                until: b.until.and_then(ManualBan::deadline_at_epoch),
+                source: BanSource::Config,
+            })),
+            None if current == Some(BanSource::Config) => self.set_manual_ban(None),
+            None => {}
+        }
+    }
+
+    pub fn set_manual_ban(&self, ban: Option<ManualBan>) {
+        match &ban {
+            Some(b) => warn!(provider = %self.label(), reason = %b.reason, until = ?b.until_epoch(), source = b.source.as_str(), "provider banned manually"),
+            None => info!(provider = %self.label(), "manual ban lifted"),
+        }
+        self.breaker.lock().set_manual_ban(ban);
+    }
+
+    pub fn manual_ban(&self) -> Option<ManualBan> { self.breaker.lock().manual_ban().cloned() }
+
     pub fn breaker_is_banned(&self) -> bool { self.breaker.lock().is_banned() }
-    pub fn breaker_success(&self) { self.breaker.lock().on_success(); }
-    pub fn breaker_failure(&self, cfg: &BreakerConfig) { self.breaker.lock().on_failure(cfg); }
+    pub fn breaker_success(&self) {
+        self.breaker.lock().on_success();
+        self.error_rate.lock().record(false);
+    }
+    /// Upstream rejected our credentials; enough in a row takes the provider out until fixed.
+    pub fn on_auth_failure(&self, cfg: &BreakerConfig, detail: &str) {
+        if self.breaker.lock().on_auth_failure(cfg) {
+            warn!(provider = %self.label(), detail, "provider marked misconfigured after repeated auth failures");
+            crate::reporting::report(
+                "provider_misconfigured",
+                format!("{} rejects our credentials: {}", self.label(), detail),
+                serde_json::json!({ "provider": self.label() }),
+            );
+        }
+    }
+
+    pub fn misconfigured_since(&self) -> Option<u64> { self.breaker.lock().misconfigured_since() }
+
+    pub fn auth_reprobe_due(&self, cfg: &BreakerConfig) -> bool { self.breaker.lock().reprobe_due(cfg) }
+
+    pub fn clear_misconfigured(&self, why: &str) {
+        if self.breaker.lock().clear_misconfigured() {
+            info!(provider = %self.label(), why, "misconfigured state cleared");
+        }
+    }
+
+    pub fn breaker_failure(&self, cfg: &BreakerConfig) {
+        self.error_rate.lock().record(true);
+        if self.breaker.lock().on_failure(cfg) {
+            warn!(provider = %self.label(), ban_seconds = cfg.ban_seconds, "provider banned by circuit breaker");
+        }
+    }
+
+    /// Upstream answered with one of relay.ban_statuses.
+    pub fn ban_on_status(&self, cfg: &BreakerConfig, status: u16) {
+        if self.breaker.lock().ban_now(cfg) {
+            warn!(provider = %self.label(), status, ban_seconds = cfg.ban_seconds, "provider banned on HTTP status");
+        }
+    }
+
+    /// Whether this round may probe the provider under its probe_budget_per_min.
+    pub fn try_probe_budget(&self) -> bool {
+        let ok = self.probe_budget.lock().as_mut().is_none_or(|(_, b)| b.try_take(1.0));
+        if !ok {
+            self.probes_skipped.fetch_add(1, Ordering::Relaxed);
+        }
+        ok
+    }
+
+    /// Keep the configured headers of an upstream response (relay traffic and probes alike).
+    pub fn capture_headers(&self, headers: &HeaderMap) {
+        let mut cap = self.captured.lock();
+        let HeaderCapture { names, latest, .. } = &mut *cap;
+        for name in names.iter() {
+            let Some(value) = headers.get(name.as_str()).and_then(|v| v.to_str().ok()) else { continue };
+            let entry = (name.clone(), value.to_string(), Instant::now());
+            match latest.iter_mut().find(|(n, _, _)| n == name) {
+                Some(slot) => *slot = entry,
+                None => latest.push(entry),
+            }
+        }
+    }
+
+    /// (header, latest value, seconds since seen).
+    pub fn captured_headers(&self) -> Vec<(String, String, u64)> {
+        self.captured.lock().latest.iter().map(|(n, v, at)| (n.clone(), v.clone(), at.elapsed().as_secs())).collect()
+    }
+
+    pub fn cost_per_million(&self) -> Option<f64> { *self.cost_per_million.read() }
+
+    pub fn infrastructure(&self) -> Option<String> { self.infrastructure.read().clone() }
+
+    /// This provider's lag limit: its endpoint override, else `default` (health_monitor's),
+    /// loosened to the degradation limit while degraded.
+    pub fn max_blocks_behind(&self, default: u64, degraded: Option<&DegradationConfig>) -> u64 {
+        let own = self.max_blocks_behind.read().unwrap_or(default);
+        degraded.map_or(own, |d| d.max_blocks_behind.max(own))
+    }
+
+    /// Requests sent so far (relay traffic and probes) priced at cost_per_million.
+    pub fn estimated_spend(&self) -> Option<f64> {
+        let sent = self.call_count.load(Ordering::Relaxed) + self.probe_count.load(Ordering::Relaxed);
+        self.cost_per_million().map(|c| c * sent as f64 / 1_000_000.0)
+    }
+
+    /// The quota_guard header was last seen below its min_remaining.
+    pub fn near_quota(&self) -> bool {
+        let cap = self.captured.lock();
+        let Some(g) = &cap.guard else { return false };
+        let header = g.header.to_ascii_lowercase();
+        cap.latest
+            .iter()
+            .find(|(n, _, _)| *n == header)
+            .and_then(|(_, v, _)| v.trim().parse::<f64>().ok())
+            .is_some_and(|remaining| remaining < g.min_remaining)
+    }
+
+    pub fn history_depth(&self) -> HistoryDepth { self.history.lock().0 }
+
+    /// Whether the last archive probe is older than `interval` (or there was none).
+    pub fn history_probe_due(&self, interval: Duration) -> bool {
+        self.history.lock().1.is_none_or(|at| at.elapsed() >= interval)
+    }
+
+    pub fn set_history_depth(&self, depth: HistoryDepth) {
+        let prev = std::mem::replace(&mut *self.history.lock(), (depth, Some(Instant::now()))).0;
+        if prev != depth {
+            info!(provider = %self.label(), ?depth, "history depth probed");
+        }
+    }
+
+    /// Bill a call that must happen regardless of the budget (an accounted health probe).
+    pub fn charge_token(&self) {
+        self.bucket.charge(1.0);
+    }
+
+    pub fn try_consume_token(&self) -> bool {
+        if !self.bucket.try_take(1.0) {
+            self.bucket_rejections.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        let raised = self.adaptive.lock().as_mut().and_then(|a| a.on_call());
+        if let Some(tps) = raised {
+            debug!(provider = %self.label(), tps, "learned rate limit raised");
+            self.bucket.set_rate(tps);
+        }
+        true
+    }
 
-    pub fn try_consume_token(&self) -> bool { self.bucket.lock().try_take(1.0) }
+    pub fn bucket_fill(&self) -> Option<f64> { self.bucket.fill_ratio() }
+    pub fn bucket_snapshot(&self) -> Option<(f64, f64, f64)> { self.bucket.snapshot() }
+
+    /// Upstream answered 429: lower the learned limit (no-op unless adaptive_tps is set).
+    pub fn on_rate_limited(&self) {
+        let cut = self.adaptive.lock().as_mut().and_then(|a| a.on_rate_limited());
+        if let Some(tps) = cut {
+            info!(provider = %self.label(), tps, "learned rate limit lowered after 429");
+            self.bucket.set_rate(tps);
+        }
+    }
+
+    /// Current learned limit; None when adaptive_tps is off or nothing has been learned yet.
+    pub fn learned_tps(&self) -> Option<f64> { self.adaptive.lock().as_ref().and_then(|a| a.limit()) }
 
     pub fn set_latency(&self, ms: u64) { self.latency_ms.store(ms, Ordering::Relaxed) }
     pub fn get_latency(&self) -> u64 { self.latency_ms.load(Ordering::Relaxed) }
@@ -62,17 +525,181 @@ impl ProviderState {
 
     pub fn set_behind(&self, d: u64) { self.behind.store(d, Ordering::Relaxed) }
     pub fn get_behind(&self) -> u64 { self.behind.load(Ordering::Relaxed) }
+    pub fn set_lag_factor(&self, f: f64) { self.lag_factor.store((f.clamp(0.0, 1.0) * 1000.0).round() as u32, Ordering::Relaxed) }
+    pub fn lag_factor(&self) -> f64 { self.lag_factor.load(Ordering::Relaxed) as f64 / 1000.0 }
+
+    pub fn record_outcome(&self, ok: bool) {
+        let c = if ok { &self.success_count } else { &self.failure_count };
+        c.fetch_add(1, Ordering::Relaxed);
+        self.rates.lock().record(ok);
+    }
+
+    pub fn record_probe(&self, ok: bool) {
+        self.probe_count.fetch_add(1, Ordering::Relaxed);
+        if !ok { self.probe_failures.fetch_add(1, Ordering::Relaxed); }
+    }
+
+    /// Fraction of completed attempts that succeeded; None before the first one.
+    pub fn success_rate(&self) -> Option<f64> {
+        let ok = self.success_count.load(Ordering::Relaxed);
+        let total = ok + self.failure_count.load(Ordering::Relaxed);
+        (total > 0).then(|| ok as f64 / total as f64)
+    }
 
     pub fn get_weight(&self) -> u32 { self.weight.load(Ordering::Relaxed).max(1) }
+
+    /// Recent breaker-relevant error rate (0..1).
+    pub fn recent_error_rate(&self) -> f64 { self.error_rate.lock().current() }
+
+    /// Share of the configured weight in effect: error-rate decay times lag weighting.
+    pub fn weight_factor(&self, decay: Option<&WeightDecayConfig>) -> f64 {
+        let Some(d) = decay else { return self.lag_factor() };
+        let mut er = self.error_rate.lock();
+        er.half_life = Duration::from_secs(d.half_life_s.max(1));
+        d.factor(er.current()) * self.lag_factor()
+    }
+}
+
+fn display_label(ep: &Endpoint) -> String {
+    ep.display_name.clone().or_else(|| ep.name.clone()).unwrap_or_else(|| mask_url(&ep.url))
+}
+
+/// Effective client settings for one provider; compared on reload to decide whether to rebuild.
+#[derive(Debug, Clone, PartialEq)]
+struct ClientOptions {
+    pool_max_idle: usize,
+    pool_idle_timeout_s: u64,
+    tcp_keepalive_s: u64,
+    tcp_nodelay: bool,
+    http1_only: bool,
+    connect_timeout_ms: Option<u64>,
+    connection_max_lifetime_s: u64,
+    dns_refresh_s: u64,
+    auth_token: Option<String>,
+    default_headers: BTreeMap<String, String>, // relay.default_headers
+}
+
+#[derive(Debug)]
+struct ClientMeta {
+    opts: ClientOptions,
+    built_at: Instant,
+    resolved: Vec<IpAddr>,
+    resolved_at: Option<Instant>,
+}
+
+impl ClientMeta {
+    fn new(opts: ClientOptions) -> Self {
+        Self { opts, built_at: Instant::now(), resolved: Vec::new(), resolved_at: None }
+    }
+}
+
+/// A surviving provider's settings from a reloaded config, held by the new `Snapshot` until
+/// `AppState::swap` publishes it, so they never serve under the old config.
+pub struct StagedUpdate {
+    provider: Arc<ProviderState>,
+    ep: Endpoint,
+    client: Option<(ClientOptions, reqwest::Result<Client>)>, // only when client settings changed
+}
+
+impl StagedUpdate {
+    fn apply(self) {
+        self.provider.update_from_endpoint(&self.ep, self.client);
+    }
+}
+
+impl ClientOptions {
+    fn new(ep: &Endpoint, http: &HttpClientConfig, headers: &BTreeMap<String, String>) -> Self {
+        Self {
+            pool_max_idle: ep.pool_max_idle.unwrap_or(http.pool_max_idle_per_host),
+            pool_idle_timeout_s: http.pool_idle_timeout_s,
+            tcp_keepalive_s: http.tcp_keepalive_s,
+            tcp_nodelay: http.tcp_nodelay,
+            http1_only: http.http1_only,
+            connect_timeout_ms: http.connect_timeout_ms,
+            connection_max_lifetime_s: ep.connection_max_lifetime_s.unwrap_or(http.connection_max_lifetime_s),
+            dns_refresh_s: ep.dns_refresh_s.unwrap_or(http.dns_refresh_s),
+            auth_token: ep.auth_token.clone(),
+            default_headers: headers.clone(),
+        }
+    }
+
+    fn build(&self) -> reqwest::Result<Client> {
+        let mut b = Client::builder()
+            .pool_max_idle_per_host(self.pool_max_idle)
+            .pool_idle_timeout(Duration::from_secs(self.pool_idle_timeout_s))
+            .tcp_nodelay(self.tcp_nodelay)
+            .connector_layer(ConnectTiming);
+        if self.tcp_keepalive_s > 0 {
+            b = b.tcp_keepalive(Duration::from_secs(self.tcp_keepalive_s));
+        }
+        if self.http1_only {
+            b = b.http1_only();
+        }
+        if let Some(ms) = self.connect_timeout_ms {
+            b = b.connect_timeout(Duration::from_millis(ms));
+        }
+        // validate() reports anything that doesn't parse; it is left out here
+        let mut headers: HeaderMap = self
+            .default_headers
+            .iter()
+            .filter_map(|(name, value)| Some((HeaderName::from_bytes(name.as_bytes()).ok()?, HeaderValue::from_str(value).ok()?)))
+            .collect();
+        // an endpoint's own credentials win over a default Authorization
+        if let Some(token) = &self.auth_token {
+            if let Ok(mut v) = HeaderValue::from_str(&format!("Bearer {}", token)) {
+                v.set_sensitive(true);
+                headers.insert(AUTHORIZATION, v);
+            }
+        }
+        if !headers.is_empty() {
+            b = b.default_headers(headers);
+        }
+        b.build()
+    }
 }
 
 #[derive(Default)]
 pub struct ProviderRegistry {
     pub primaries: Vec<Arc<ProviderState>>,
     pub secondaries: Vec<Arc<ProviderState>>,
+    selection: parking_lot::Mutex<HashMap<SelectionKey, Arc<Candidates>>>, // recent sets, at most SELECTION_CACHE
 }
 
+// Distinct keys kept; a call's pool, its filtered and cost-preferred sets and the undecayed
+// set used by /status and /readyz alternate, so a single slot would be rebuilt on every call.
+const SELECTION_CACHE: usize = 16;
+
+/// What a candidate set is built from: slots per provider, primaries then secondaries, as
+/// `Selector` gives them after every cut. A provider a filter dropped has 0, so each
+/// filtered set is cached under its own key like the unfiltered pool.
+pub type SelectionKey = Vec<u32>;
+
 impl ProviderRegistry {
+    pub fn new(primaries: Vec<Arc<ProviderState>>, secondaries: Vec<Arc<ProviderState>>) -> Self {
+        Self { primaries, secondaries, selection: Default::default() }
+    }
+
+    /// Candidate set for `key`, reused while health and weights keep producing it. A miss
+    /// is built outside the lock; the cache starts over once it holds SELECTION_CACHE keys.
+    pub fn candidates(&self, key: SelectionKey) -> Arc<Candidates> {
+        if let Some(c) = self.selection.lock().get(&key) {
+            return c.clone();
+        }
+        let all = self.primaries.iter().chain(self.secondaries.iter());
+        let c = Arc::new(Candidates::new(all.cloned().zip(key.iter().copied())));
+        let mut cached = self.selection.lock();
+        if cached.len() >= SELECTION_CACHE {
+            cached.clear();
+        }
+        cached.insert(key, c.clone());
+        c
+    }
+
+    /// Look a provider up by id or name.
+    pub fn find(&self, key: &str) -> Option<Arc<ProviderState>> {
+        self.primaries.iter().chain(self.secondaries.iter()).find(|p| p.id == key || p.name.as_deref() == Some(key)).cloned()
+    }
+
     pub fn all(&self) -> Vec<Arc<ProviderState>> {
         let mut v = Vec::with_capacity(self.primaries.len() + self.secondaries.len());
         v.extend(self.primaries.iter().cloned());
@@ -81,80 +708,217 @@ impl ProviderRegistry {
     }
 }
 
+/// Selectable providers and their share of traffic in slots. Calls walk a cycle of
+/// smooth weighted round-robin (see selector::smooth_cycle), so shares interleave.
+#[derive(Default)]
+pub struct Candidates {
+    providers: Vec<Arc<ProviderState>>,
+    cycle: Vec<u32>, // index into providers of each call's first pick
+}
+
+impl Candidates {
+    /// Providers with zero slots are left out.
+    pub fn new(weighted: impl IntoIterator<Item = (Arc<ProviderState>, u32)>) -> Self {
+        let (providers, slots): (Vec<_>, Vec<_>) = weighted.into_iter().filter(|(_, s)| *s > 0).unzip();
+        let cycle = crate::selector::smooth_cycle(&slots);
+        Self { providers, cycle }
+    }
+
+    pub fn providers(&self) -> &[Arc<ProviderState>] { &self.providers }
+
+    pub fn is_empty(&self) -> bool { self.providers.is_empty() }
+
+    /// Every provider once, starting with entry `idx` (modulo its length) of the cycle.
+    pub fn rotation(&self, idx: usize) -> impl Iterator<Item = &Arc<ProviderState>> {
+        let n = self.providers.len();
+        let start = self.cycle.get(idx % self.cycle.len().max(1)).map_or(0, |&i| i as usize);
+        (0..n).map(move |i| &self.providers[(start + i) % n])
+    }
+}
+
+/// Config and the providers built from it. A reload builds a whole new snapshot and
+/// swaps it in at once; a request holds one snapshot from start to finish.
+pub struct Snapshot {
+    pub cfg: Config,
+    pub registry: ProviderRegistry,
+    pub breaker: BreakerConfig,
+    staged: parking_lot::Mutex<Vec<StagedUpdate>>, // applied by AppState::swap
+}
+
+impl Snapshot {
+    pub fn new(cfg: Config, registry: ProviderRegistry) -> Self {
+        let breaker = BreakerConfig::from(&cfg.relay);
+        Self { cfg, registry, breaker, staged: Default::default() }
+    }
+
+    /// Provider updates (from `reconcile_registry`) that go live when this snapshot does.
+    pub fn with_staged(self, staged: Vec<StagedUpdate>) -> Self {
+        *self.staged.lock() = staged;
+        self
+    }
+}
+
 pub struct AppState {
-    pub cfg: Arc<RwLock<Config>>,
-    pub registry: Arc<RwLock<ProviderRegistry>>,
-    pub breaker_cfg: Arc<RwLock<BreakerConfig>>,
+    snapshot: parking_lot::RwLock<Arc<Snapshot>>,
+    generation: AtomicU64,
     pub rr_main: AtomicU64,
 
     // Global counters for the live dashboard
     pub total_calls: AtomicU64,   // incoming POST /
     pub cache_hits: AtomicU64,    // cache served
+    pub namespaces: NamespaceStats,
+    pub origins: OriginStats,     // by X-RLY-Origin, under origin_stats
+    pub totals: RelayTotals,      // outcomes of POST / calls as the client saw them
+    pub cache_shadow: CacheShadow,
+    retired: parking_lot::Mutex<Vec<(Arc<ProviderState>, Instant)>>, // dropped by a reload, and when
 }
 
 impl AppState {
     pub fn new(cfg: Config) -> Self {
-        let breaker_cfg = BreakerConfig {
-            ban_error_threshold: cfg.relay.ban_error_threshold,
-            ban_seconds: cfg.relay.ban_seconds,
-        };
-        let registry = build_registry(&cfg.rpc_endpoints);
+        let registry = build_registry(&cfg.rpc_endpoints, &cfg.http_client, &cfg.relay.default_headers);
+        Self::with_registry(cfg, registry)
+    }
+
+    /// State over providers built elsewhere, e.g. on a transport of the caller's choosing.
+    pub fn with_registry(cfg: Config, registry: ProviderRegistry) -> Self {
         Self {
-            cfg: Arc::new(RwLock::new(cfg)),
-            registry: Arc::new(RwLock::new(registry)),
-            breaker_cfg: Arc::new(RwLock::new(breaker_cfg)),
+            snapshot: parking_lot::RwLock::new(Arc::new(Snapshot::new(cfg, registry))),
+            generation: AtomicU64::new(0),
             rr_main: AtomicU64::new(0),
             total_calls: AtomicU64::new(0),
             cache_hits: AtomicU64::new(0),
+            namespaces: NamespaceStats::default(),
+            origins: OriginStats::default(),
+            totals: RelayTotals::default(),
+            cache_shadow: CacheShadow::default(),
+            retired: parking_lot::Mutex::new(Vec::new()),
         }
     }
+
+    /// Current snapshot: a shared read lock held just long enough to clone the Arc.
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.snapshot.read().clone()
+    }
+
+    /// Changes with every swap; equal values mean the same snapshot.
+    pub fn generation(&self) -> u64 { self.generation.load(Ordering::Acquire) }
+
+    /// Publish `next`, applying its staged provider updates under the same write lock, so
+    /// no request takes the new config with the old provider settings or the reverse. The
+    /// lock is only held for that and the pointer exchange (clients are built already); the
+    /// old snapshot is dropped after it is released, in case this was its last reference.
+    pub fn swap(&self, next: Snapshot) {
+        let staged = std::mem::take(&mut *next.staged.lock());
+        let next = Arc::new(next);
+        let old = {
+            let mut slot = self.snapshot.write();
+            staged.into_iter().for_each(StagedUpdate::apply);
+            let old = std::mem::replace(&mut *slot, next);
+            self.generation.fetch_add(1, Ordering::Release);
+            old
+        };
+        drop(old);
+    }
+
+    /// Providers dropped by a reload and not yet expired, with when they were dropped.
+    pub fn retired(&self) -> Vec<(Arc<ProviderState>, Instant)> { self.retired.lock().clone() }
+
+    /// Keep `removed` (stats and error history) around after a reload; retired providers
+    /// the current registry took back are no longer retired.
+    pub fn retire(&self, removed: Vec<Arc<ProviderState>>) {
+        let live = self.snapshot().registry.all();
+        let mut retired = self.retired.lock();
+        retired.retain(|(p, _)| !live.iter().any(|l| Arc::ptr_eq(l, p)));
+        retired.extend(removed.into_iter().map(|p| (p, Instant::now())));
+    }
+
+    /// Forget providers retired longer than `keep`, including their error history unless
+    /// a live provider now uses the same id.
+    pub fn expire_retired(&self, keep: Duration) {
+        let expired: Vec<_> = {
+            let mut retired = self.retired.lock();
+            let (gone, kept) = retired.drain(..).partition(|(_, at)| at.elapsed() >= keep);
+            *retired = kept;
+            gone
+        };
+        let snap = self.snapshot();
+        for (p, _) in expired {
+            if snap.registry.find(&p.id).is_none() {
+                crate::error_reason::forget(&p.id);
+            }
+            debug!(provider = %p.label(), "retired provider expired");
+        }
+    }
+}
+
+pub fn build_registry(eps: &RpcEndpoints, http: &HttpClientConfig, headers: &BTreeMap<String, String>) -> ProviderRegistry {
+    let (primary, secondary) = distinct_endpoints(eps);
+    ProviderRegistry::new(
+        primary.into_iter().map(|ep| ProviderState::from_endpoint(ep, http, headers)).collect(),
+        secondary.into_iter().map(|ep| ProviderState::from_endpoint(ep, http, headers)).collect(),
+    )
 }
 
-pub fn build_registry(eps: &RpcEndpoints) -> ProviderRegistry {
-    ProviderRegistry {
-        primaries: eps.primary.iter().map(ProviderState::from_endpoint).collect(),
-        secondaries: eps.secondary.iter().map(ProviderState::from_endpoint).collect(),
+// One provider per url, warning about each entry folded into an earlier one.
+fn distinct_endpoints(eps: &RpcEndpoints) -> (Vec<&Endpoint>, Vec<&Endpoint>) {
+    let (primary, secondary, folded) = eps.distinct();
+    for (endpoint, shares) in folded {
+        warn!(endpoint, shares, "duplicate endpoint url; both entries share one provider (first entry's settings)");
     }
+    (primary, secondary)
+}
+
+/// What `reconcile_registry` built for a reloaded endpoint list.
+pub struct Reconciled {
+    pub registry: ProviderRegistry,
+    pub staged: Vec<StagedUpdate>,          // new settings of providers kept or brought back; see Snapshot::with_staged
+    pub added: Vec<Arc<ProviderState>>,     // (re)joined; out of rotation until their first health probe
+    pub removed: Vec<Arc<ProviderState>>,   // of the old registry, dropped by the new list
 }
 
-/// Reconcile existing registry with a new config
-pub fn reconcile_registry(reg: &mut ProviderRegistry, new_eps: &RpcEndpoints) {
+/// Registry for a reloaded endpoint list, keeping state for providers that stayed. A
+/// `retired` provider listed again comes back with its stats. Changes nothing a live
+/// request can see: kept providers get their new settings staged, for the caller to
+/// publish with the new snapshot.
+pub fn reconcile_registry(
+    reg: &ProviderRegistry,
+    retired: &[Arc<ProviderState>],
+    new_eps: &RpcEndpoints,
+    http: &HttpClientConfig,
+    headers: &BTreeMap<String, String>,
+) -> Reconciled {
     use std::collections::HashMap;
     let mut existing: HashMap<String, Arc<ProviderState>> =
-        reg.all().into_iter().map(|p| (p.url.clone(), p)).collect();
-
-    let mut new_prim = Vec::new();
-    for ep in &new_eps.primary {
-        if let Some(p) = existing.remove(&ep.url) {
-            p.weight.store(ep.weight.max(1), Ordering::Relaxed);
-            let new_mtps = ep.max_tps.unwrap_or(0);
-            let old_mtps = p.max_tps.load(Ordering::Relaxed);
-            if new_mtps != old_mtps {
-                p.max_tps.store(new_mtps, Ordering::Relaxed);
-                *p.bucket.lock() = TokenBucket::new(new_mtps);
+        reg.all().into_iter().map(|p| (p.id.clone(), p)).collect();
+    let mut dormant: HashMap<String, Arc<ProviderState>> =
+        retired.iter().map(|p| (p.id.clone(), p.clone())).collect();
+    let (mut added, mut removed, mut staged) = (Vec::new(), Vec::new(), Vec::new());
+    let mut place = |ep: &Endpoint| {
+        match existing.remove(ep.id()) {
+            Some(p) if p.url == ep.url => {
+                staged.push(p.stage_update(ep, http, headers));
+                return p;
             }
-            new_prim.push(p);
-        } else {
-            new_prim.push(ProviderState::from_endpoint(ep));
+            // A named provider whose URL changed is a different upstream; start it fresh.
+            Some(p) => removed.push(p),
+            None => {}
         }
-    }
-
-    let mut new_sec = Vec::new();
-    for ep in &new_eps.secondary {
-        if let Some(p) = existing.remove(&ep.url) {
-            p.weight.store(ep.weight.max(1), Ordering::Relaxed);
-            let new_mtps = ep.max_tps.unwrap_or(0);
-            let old_mtps = p.max_tps.load(Ordering::Relaxed);
-            if new_mtps != old_mtps {
-                p.max_tps.store(new_mtps, Ordering::Relaxed);
-                *p.bucket.lock() = TokenBucket::new(new_mtps);
+        let p = match dormant.remove(ep.id()).filter(|p| p.url == ep.url) {
+            Some(p) => {
+                staged.push(p.stage_update(ep, http, headers));
+                p
             }
-            new_sec.push(p);
-        } else {
-            new_sec.push(ProviderState::from_endpoint(ep));
-        }
-    }
+            None => ProviderState::from_endpoint(ep, http, headers),
+        };
+        p.mark_healthy(false);
+        added.push(p.clone());
+        p
+    };
+
+    let (primary, secondary) = distinct_endpoints(new_eps);
+    let new_prim: Vec<_> = primary.into_iter().map(&mut place).collect();
+    let new_sec: Vec<_> = secondary.into_iter().map(&mut place).collect();
 
-    reg.primaries = new_prim;
-    reg.secondaries = new_sec;
+    removed.extend(existing.into_values());
+    Reconciled { registry: ProviderRegistry::new(new_prim, new_sec), staged, added, removed }
 }
@@ -0,0 +1,291 @@
+use crate::state::AppState;
+use axum::{
+    body::Body,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Write as _;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, Duration, Instant};
+
+const FILTERS: [&str; 7] = ["healthy", "unhealthy", "banned", "drained", "off_schedule", "near_quota", "selectable"];
+// How often a watcher looks for changes, and the longest it stays quiet without one.
+const WATCH_POLL: Duration = Duration::from_millis(250);
+const WATCH_KEEPALIVE: Duration = Duration::from_secs(30);
+
+const TABLE_FIELDS: [&str; 8] = ["label", "healthy", "latency_ms", "behind", "call_count", "errors", "success_rate", "last_error"];
+
+/// GET /status query: which providers, which of their fields, in what order and format.
+/// Filter, sort and fields apply to the provider lists (rpcs, retired); the network
+/// subtotals and everything else still cover all providers.
+#[derive(Deserialize)]
+pub struct StatusQuery {
+    #[serde(default)]
+    include: Option<String>, // comma-separated extras; "retired" adds providers dropped by a reload
+    #[serde(default)]
+    filter: Option<String>,  // comma-separated; a provider must match all of them
+    #[serde(default)]
+    fields: Option<String>,  // comma-separated provider fields to keep
+    #[serde(default)]
+    sort: Option<String>,    // provider field or alias; "-" in front for descending
+    #[serde(default)]
+    format: Option<String>,  // json (default), table or prometheus
+    #[serde(default)]
+    watch: bool,             // stream a document per change as NDJSON instead of answering once
+}
+
+impl StatusQuery {
+    pub fn include_retired(&self) -> bool { list(&self.include).any(|i| i == "retired") }
+
+    pub fn watch(&self) -> bool { self.watch }
+
+    /// Shape `doc` (a status_json document) as asked; 400 with the reason for a bad query.
+    pub fn respond(&self, mut doc: Value) -> Response {
+        let format = self.format.as_deref().unwrap_or("json");
+        if !["json", "table", "prometheus"].contains(&format) {
+            return bad_request(format!("unknown format {:?} (json, table or prometheus)", format));
+        }
+        if let Err(msg) = self.shape(&mut doc) {
+            return bad_request(msg);
+        }
+        match format {
+            "table" => {
+                let fields: Vec<&str> = list(&self.fields).collect();
+                text(table(&doc, (!fields.is_empty()).then_some(fields)), "text/plain; charset=utf-8")
+            }
+            "prometheus" => text(prometheus(&doc), "text/plain; version=0.0.4; charset=utf-8"),
+            _ => (StatusCode::OK, Json(doc)).into_response(),
+        }
+    }
+
+    fn shape(&self, doc: &mut Value) -> Result<(), String> {
+        let filters: Vec<&str> = list(&self.filter).collect();
+        if let Some(f) = filters.iter().find(|f| !FILTERS.contains(f)) {
+            return Err(format!("unknown filter {:?} (one of {})", f, FILTERS.join(", ")));
+        }
+        let fields: Vec<&str> = list(&self.fields).collect();
+        let sort = self.sort.as_deref().map(str::trim).filter(|s| !s.is_empty());
+        let (descending, sort_key) = match sort {
+            Some(s) => match s.strip_prefix('-') {
+                Some(key) => (true, Some(alias(key))),
+                None => (false, Some(alias(s))),
+            },
+            None => (false, None),
+        };
+        // Field names are checked against what providers actually carry (nothing to check without any).
+        let known: Vec<String> = lists(doc).flat_map(|l| l.iter()).filter_map(Value::as_object).flat_map(|o| o.keys().cloned()).collect();
+        if !known.is_empty() {
+            if let Some(f) = fields.iter().chain(&sort_key).find(|f| !known.iter().any(|k| k == *f)) {
+                return Err(format!("unknown provider field {:?}", f));
+            }
+        }
+        for key in ["rpcs", "retired"] {
+            let Some(Value::Array(providers)) = doc.get_mut(key) else { continue };
+            providers.retain(|p| filters.iter().all(|f| matches(p, f)));
+            if let Some(k) = sort_key {
+                providers.sort_by(|a, b| {
+                    let ord = compare(&a[k], &b[k]);
+                    if descending { ord.reverse() } else { ord }
+                });
+            }
+            if !fields.is_empty() {
+                for p in providers.iter_mut() {
+                    if let Value::Object(obj) = p {
+                        obj.retain(|k, _| fields.contains(&k.as_str()));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// ?watch=true: hold the connection and send the (shaped) status document as one NDJSON
+/// line now, after every change to provider selectability or a reload, and at least every
+/// WATCH_KEEPALIVE. Ends when the client goes away.
+pub fn watch(app: Arc<AppState>, q: StatusQuery) -> Response {
+    if q.format.as_deref().is_some_and(|f| f != "json") {
+        return bad_request("watch streams JSON lines; it can't be combined with format".to_string());
+    }
+    let mut doc = crate::relay::status_json(&app, q.include_retired());
+    if let Err(msg) = q.shape(&mut doc) {
+        return bad_request(msg);
+    }
+    let (tx, mut rx) = mpsc::channel::<String>(4);
+    tokio::spawn(async move {
+        let mut seen = fingerprint(&app);
+        loop {
+            if tx.send(format!("{}\n", doc)).await.is_err() {
+                return;
+            }
+            let sent = Instant::now();
+            loop {
+                sleep(WATCH_POLL).await;
+                if tx.is_closed() {
+                    return;
+                }
+                let now = fingerprint(&app);
+                if now != seen || sent.elapsed() >= WATCH_KEEPALIVE {
+                    seen = now;
+                    break;
+                }
+            }
+            doc = crate::relay::status_json(&app, q.include_retired());
+            let _ = q.shape(&mut doc); // checked above; the field names don't change
+        }
+    });
+    let lines = futures::stream::poll_fn(move |cx| rx.poll_recv(cx).map(|l| l.map(Ok::<_, Infallible>)));
+    ([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(lines)).into_response()
+}
+
+// What wakes a watcher: a reload, degraded mode, memory pressure, or a provider moving in
+// or out of selection. Counters and latencies alone don't.
+fn fingerprint(app: &AppState) -> (u64, bool, bool, Vec<[bool; 7]>) {
+    let providers = app
+        .snapshot()
+        .registry
+        .all()
+        .iter()
+        .map(|p| {
+            [
+                p.is_healthy(),
+                p.breaker_is_banned(),
+                p.manual_ban().is_some(),
+                p.misconfigured_since().is_some(),
+                p.is_drained(),
+                p.is_off_schedule(),
+                p.near_quota(),
+            ]
+        })
+        .collect();
+    (app.generation(), crate::health::is_degraded(), crate::memory_guard::under_pressure(), providers)
+}
+
+fn list(param: &Option<String>) -> impl Iterator<Item = &str> {
+    param.as_deref().unwrap_or("").split(',').map(str::trim).filter(|s| !s.is_empty())
+}
+
+fn lists(doc: &Value) -> impl Iterator<Item = &Vec<Value>> {
+    ["rpcs", "retired"].into_iter().filter_map(|k| doc.get(k)?.as_array())
+}
+
+// Short names for the usual sort keys.
+fn alias(key: &str) -> &str {
+    match key {
+        "latency" => "latency_ms",
+        "calls" => "call_count",
+        "spend" => "estimated_spend",
+        "name" => "label",
+        other => other,
+    }
+}
+
+fn matches(p: &Value, filter: &str) -> bool {
+    let set = |k: &str| p[k] == true;
+    let banned = p["banned_until"].as_u64().unwrap_or(0) > 0 || !p["manual_ban"].is_null() || !p["misconfigured_since"].is_null();
+    match filter {
+        "healthy" => set("healthy"),
+        "unhealthy" => !set("healthy"),
+        "banned" => banned,
+        "drained" => set("drained"),
+        "off_schedule" => set("off_schedule"),
+        "near_quota" => set("near_quota"),
+        "selectable" => set("healthy") && !banned && !set("drained") && !set("off_schedule"),
+        _ => false,
+    }
+}
+
+// Numbers by value, then strings, then anything else; nulls last either way.
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Greater,
+        (_, Value::Null) => Ordering::Less,
+        (Value::Number(x), Value::Number(y)) => x.as_f64().unwrap_or(0.0).total_cmp(&y.as_f64().unwrap_or(0.0)),
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+        _ => a.to_string().cmp(&b.to_string()),
+    }
+}
+
+fn bad_request(msg: String) -> Response { (StatusCode::BAD_REQUEST, Json(json!({ "error": msg }))).into_response() }
+
+fn text(body: String, content_type: &'static str) -> Response {
+    (StatusCode::OK, [(header::CONTENT_TYPE, content_type)], body).into_response()
+}
+
+// One row per provider, columns aligned; nested values are printed as compact JSON.
+fn table(doc: &Value, fields: Option<Vec<&str>>) -> String {
+    let columns = fields.unwrap_or_else(|| TABLE_FIELDS.to_vec());
+    let cell = |v: &Value| match v {
+        Value::Null => "-".to_string(),
+        Value::String(s) => s.clone(),
+        Value::Number(n) if n.is_f64() => format!("{:.3}", n.as_f64().unwrap_or(0.0)),
+        other => other.to_string(),
+    };
+    let rows: Vec<Vec<String>> = lists(doc).flatten().map(|p| columns.iter().map(|c| cell(&p[*c])).collect()).collect();
+    let widths: Vec<usize> =
+        columns.iter().enumerate().map(|(i, c)| rows.iter().map(|r| r[i].len()).chain([c.len()]).max().unwrap_or(0)).collect();
+    let mut out = String::new();
+    let mut line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells.iter().zip(&widths).map(|(c, w)| format!("{:<w$}", c, w = *w)).collect();
+        let _ = writeln!(out, "{}", padded.join("  ").trim_end());
+    };
+    line(columns.clone());
+    for r in &rows {
+        line(r.iter().map(String::as_str).collect());
+    }
+    out
+}
+
+// Prometheus text exposition: every numeric or boolean provider field (and those one level
+// down, e.g. uptime_24h) as a gauge labelled by network and provider, plus the summary counters.
+fn prometheus(doc: &Value) -> String {
+    let network = doc["network"].as_str().unwrap_or_default();
+    let mut families: BTreeMap<String, Vec<(String, f64)>> = BTreeMap::new();
+    for p in lists(doc).flatten() {
+        let labels = format!("network=\"{}\",provider=\"{}\"", escape(network), escape(p["label"].as_str().unwrap_or_default()));
+        let Some(obj) = p.as_object() else { continue };
+        for (k, v) in obj {
+            let nested = v.as_object().into_iter().flatten().map(|(sub, v)| (format!("{}_{}", k, sub), v));
+            for (name, v) in std::iter::once((k.clone(), v)).chain(nested) {
+                if let Some(x) = sample(v) {
+                    families.entry(format!("rly_provider_{}", metric_name(&name))).or_default().push((labels.clone(), x));
+                }
+            }
+        }
+    }
+    let global = format!("network=\"{}\"", escape(network));
+    let summary = doc["summary"].as_object().into_iter().flatten().map(|(k, v)| (format!("rly_summary_{}", metric_name(k)), v));
+    for (name, v) in summary.chain([("rly_degraded".to_string(), &doc["degraded"])]) {
+        if let Some(x) = sample(v) {
+            families.entry(name).or_default().push((global.clone(), x));
+        }
+    }
+    let mut out = String::new();
+    for (name, samples) in families {
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for (labels, x) in samples {
+            let _ = writeln!(out, "{}{{{}}} {}", name, labels, x);
+        }
+    }
+    out
+}
+
+fn sample(v: &Value) -> Option<f64> {
+    match v {
+        Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+        other => other.as_f64(),
+    }
+}
+
+fn metric_name(s: &str) -> String { s.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' }).collect() }
+
+fn escape(s: &str) -> String { s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n") }
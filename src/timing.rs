@@ -0,0 +1,132 @@
+use serde_json::{json, Value};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Where one upstream exchange spent its time. `connect_ms` (DNS + TCP + TLS) is None
+/// when a pooled connection was reused; `ttfb_ms` runs from the request (after any
+/// connect) to the response headers; `body_ms` is the body read. A phase the exchange
+/// never reached stays None.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Phases {
+    pub connect_ms: Option<u64>,
+    pub ttfb_ms: Option<u64>,
+    pub body_ms: Option<u64>,
+}
+
+tokio::task_local! {
+    static CURRENT: Arc<parking_lot::Mutex<Phases>>;
+}
+
+/// Run `fut`, collecting the phases of the upstream exchange made inside it.
+pub async fn timed<F: Future>(fut: F) -> (F::Output, Phases) {
+    let slot = Arc::new(parking_lot::Mutex::new(Phases::default()));
+    let out = CURRENT.scope(slot.clone(), fut).await;
+    let phases = *slot.lock();
+    (out, phases)
+}
+
+/// Update the phases of the enclosing `timed` call, if any.
+pub fn note(f: impl FnOnce(&mut Phases)) {
+    let _ = CURRENT.try_with(|slot| f(&mut slot.lock()));
+}
+
+pub fn ms(d: Duration) -> u64 {
+    d.as_millis() as u64
+}
+
+/// Connector layer for provider clients: times each new connection into the `timed`
+/// scope of the request that opened it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConnectTiming;
+
+impl<S> Layer<S> for ConnectTiming {
+    type Service = TimedConnect<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TimedConnect { inner }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TimedConnect<S> {
+    inner: S,
+}
+
+impl<S, R> Service<R> for TimedConnect<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        // Captured here: the connect itself may finish on a pool task outside the scope.
+        let slot = CURRENT.try_with(Arc::clone).ok();
+        let started = Instant::now();
+        let connecting = self.inner.call(req);
+        Box::pin(async move {
+            let conn = connecting.await?;
+            if let Some(slot) = slot {
+                slot.lock().connect_ms = Some(ms(started.elapsed()));
+            }
+            Ok(conn)
+        })
+    }
+}
+
+const ALPHA: f64 = 0.2;
+
+/// Per-provider moving averages of the phases, for /status.
+#[derive(Debug, Default)]
+pub struct PhaseStats {
+    connect_ms: Option<f64>,
+    ttfb_ms: Option<f64>,
+    body_ms: Option<f64>,
+    connects: u64,
+    exchanges: u64, // reached the response headers
+}
+
+impl PhaseStats {
+    pub fn record(&mut self, p: &Phases) {
+        fn ewma(avg: &mut Option<f64>, sample: Option<u64>) {
+            if let Some(s) = sample {
+                let s = s as f64;
+                *avg = Some(avg.map_or(s, |a| a + ALPHA * (s - a)));
+            }
+        }
+        ewma(&mut self.connect_ms, p.connect_ms);
+        ewma(&mut self.ttfb_ms, p.ttfb_ms);
+        ewma(&mut self.body_ms, p.body_ms);
+        self.connects += p.connect_ms.is_some() as u64;
+        self.exchanges += p.ttfb_ms.is_some() as u64;
+    }
+
+    pub fn to_json(&self) -> Value {
+        let round = |v: Option<f64>| v.map(|v| (v * 10.0).round() / 10.0);
+        json!({
+            "connect_ms": round(self.connect_ms),
+            "ttfb_ms": round(self.ttfb_ms),
+            "body_ms": round(self.body_ms),
+            "connects": self.connects,
+            "exchanges": self.exchanges,
+        })
+    }
+}
+
+/// Wall-clock now as unix seconds (0 if the clock reads before 1970).
+pub fn epoch_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
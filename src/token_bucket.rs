@@ -1,40 +1,96 @@
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Instant;
 
-/// Simple token bucket (tokens per second) with fractional tokens.
+/// Token bucket (tokens per second) with fractional tokens, lock-free: the balance is kept
+/// as a "theoretical arrival time" (when the bucket would be full again) that takes and
+/// charges move forward with a CAS, so concurrent requests never queue on a lock.
+/// Rate changes are rare (reloads, adaptive_tps) and only approximately ordered
+/// against concurrent takes.
 #[derive(Debug)]
 pub struct TokenBucket {
-    capacity: f64,
-    tokens: f64,
-    refill_per_sec: f64,
-    last: Instant,
+    base: Instant,
+    full_at: AtomicU64,  // ns since `base`; at or before now => full
+    interval: AtomicU64, // f64 bits: ns to refill one token; 0 => unlimited
+    capacity: AtomicU64, // f64 bits: burst size in tokens
 }
 
 impl TokenBucket {
     pub fn new(max_tps: u32) -> Self {
-        let cap = if max_tps == 0 { f64::INFINITY } else { max_tps as f64 };
-        let rps = if max_tps == 0 { f64::INFINITY } else { max_tps as f64 };
-        Self { capacity: cap, tokens: cap, refill_per_sec: rps, last: Instant::now() }
+        let b = Self { base: Instant::now(), full_at: AtomicU64::new(0), interval: AtomicU64::new(0), capacity: AtomicU64::new(0) };
+        b.reset(max_tps);
+        b
     }
 
-    fn refill(&mut self) {
-        if self.capacity.is_infinite() { return; }
-        let now = Instant::now();
-        let dt = now.duration_since(self.last).as_secs_f64();
-        if dt > 0.0 {
-            self.tokens = (self.tokens + dt * self.refill_per_sec).min(self.capacity);
-            self.last = now;
-        }
+    /// Back to a full bucket at `max_tps` (0 => unlimited).
+    pub fn reset(&self, max_tps: u32) {
+        let tps = max_tps as f64;
+        self.interval.store(if max_tps == 0 { 0f64 } else { 1e9 / tps }.to_bits(), Ordering::Relaxed);
+        self.capacity.store(tps.to_bits(), Ordering::Relaxed);
+        self.full_at.store(self.now(), Ordering::Release);
+    }
+
+    /// Change the rate in place; the burst capacity follows it and existing tokens are kept up to it.
+    pub fn set_rate(&self, tps: f64) {
+        let tps = tps.max(0.001);
+        let now = self.now();
+        let capacity = tps.max(1.0); // below 1 tps a whole token must still fit
+        let tokens = match self.tokens(now) {
+            None => tps, // was unlimited: start full at the new rate
+            Some(t) => t.min(capacity),
+        };
+        let interval = 1e9 / tps;
+        self.interval.store(interval.to_bits(), Ordering::Relaxed);
+        self.capacity.store(capacity.to_bits(), Ordering::Relaxed);
+        self.full_at.store(now + ((capacity - tokens) * interval) as u64, Ordering::Release);
+    }
+
+    fn now(&self) -> u64 { self.base.elapsed().as_nanos() as u64 }
+
+    fn interval(&self) -> f64 { f64::from_bits(self.interval.load(Ordering::Relaxed)) }
+
+    fn capacity(&self) -> f64 { f64::from_bits(self.capacity.load(Ordering::Relaxed)) }
+
+    // None when unlimited; negative while in debt from `charge`.
+    fn tokens(&self, now: u64) -> Option<f64> {
+        let interval = self.interval();
+        if interval == 0.0 { return None; }
+        let owed = self.full_at.load(Ordering::Acquire).saturating_sub(now) as f64 / interval;
+        Some(self.capacity() - owed)
+    }
+
+    /// Share of the burst capacity currently available; None when unlimited.
+    pub fn fill_ratio(&self) -> Option<f64> {
+        let tokens = self.tokens(self.now())?;
+        Some((tokens / self.capacity()).max(0.0))
+    }
+
+    /// (refill rate, tokens available now, burst capacity); None when unlimited.
+    pub fn snapshot(&self) -> Option<(f64, f64, f64)> {
+        let tokens = self.tokens(self.now())?;
+        Some((1e9 / self.interval(), tokens, self.capacity()))
+    }
+
+    /// Take tokens unconditionally; the balance may go negative and is repaid by refill.
+    pub fn charge(&self, n: f64) {
+        let interval = self.interval();
+        if interval == 0.0 { return; }
+        let cost = (n * interval) as u64;
+        let now = self.now();
+        let _ = self.full_at.fetch_update(Ordering::AcqRel, Ordering::Acquire, |at| Some(at.max(now) + cost));
     }
 
     /// Attempt to take tokens. Returns true if successful.
-    pub fn try_take(&mut self, n: f64) -> bool {
-        if self.capacity.is_infinite() { return true; }
-        self.refill();
-        if self.tokens >= n {
-            self.tokens -= n;
-            true
-        } else {
-            false
-        }
+    pub fn try_take(&self, n: f64) -> bool {
+        let interval = self.interval();
+        if interval == 0.0 { return true; }
+        let cost = (n * interval) as u64;
+        let limit = (self.capacity() * interval).round() as u64; // furthest full_at may run ahead of now
+        let now = self.now();
+        self.full_at
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |at| {
+                let next = at.max(now) + cost;
+                (next - now <= limit).then_some(next)
+            })
+            .is_ok()
     }
 }
@@ -0,0 +1,167 @@
+use crate::config::TxTrackingConfig;
+use crate::jsonrpc::JsonRpcRequest;
+use crate::raw_tx::RawTx;
+use crate::relay::HttpState;
+use crate::state::{ProviderState, Snapshot};
+use crate::timing::epoch_secs;
+use crate::upstream;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::time::{sleep, Duration};
+use tracing::{debug, info};
+
+const GWEI: f64 = 1e9;
+// A just-accepted transaction can take a moment to be queryable on the node that took it.
+const SENDER_TRIES: u32 = 3;
+const SENDER_RETRY: Duration = Duration::from_secs(1);
+
+/// Broadcast transactions by hash, linked to the same-nonce transactions that replaced them.
+/// The sender comes from the provider that accepted the transaction (the relay doesn't
+/// recover signatures), so links appear a moment after the replacement is broadcast.
+#[derive(Default)]
+pub struct TxTracker {
+    entries: HashMap<String, Tracked>,
+    order: VecDeque<String>, // hashes, oldest first
+}
+
+struct Tracked {
+    tx: RawTx,
+    sender: Option<String>,
+    accepted_by: String,
+    at: Instant,
+    at_unix: u64,
+    replaced_by: Option<String>,
+    replaces: Option<String>,
+}
+
+impl Tracked {
+    // Same-nonce self-send of nothing: the usual way wallets cancel a pending transaction.
+    fn is_cancel(&self) -> bool {
+        self.tx.value == 0 && self.tx.input_len == 0 && self.tx.to.is_some() && self.tx.to == self.sender
+    }
+}
+
+impl TxTracker {
+    fn insert(&mut self, hash: String, tx: RawTx, accepted_by: String, cfg: &TxTrackingConfig) {
+        let retention = Duration::from_secs(cfg.retention_s);
+        while let Some(oldest) = self.order.front() {
+            let expired = self.entries.get(oldest).is_none_or(|t| t.at.elapsed() >= retention);
+            if !expired && self.order.len() < cfg.max_entries.max(1) {
+                break;
+            }
+            if let Some(h) = self.order.pop_front() {
+                self.entries.remove(&h);
+            }
+        }
+        if self.entries.contains_key(&hash) {
+            return; // a re-broadcast; keep the first sighting
+        }
+        let entry = Tracked { tx, sender: None, accepted_by, at: Instant::now(), at_unix: epoch_secs(), replaced_by: None, replaces: None };
+        self.entries.insert(hash.clone(), entry);
+        self.order.push_back(hash);
+    }
+
+    /// Record who sent `hash` and link it with any other tracked transaction from the same
+    /// sender and nonce: the later one with the higher fee cap replaces the earlier one.
+    fn set_sender(&mut self, hash: &str, sender: String) {
+        let Some(me) = self.entries.get_mut(hash) else { return };
+        me.sender = Some(sender.clone());
+        let nonce = me.tx.nonce;
+        let mut same: Vec<(Instant, String)> = self
+            .entries
+            .iter()
+            .filter(|(_, t)| t.sender.as_deref() == Some(sender.as_str()) && t.tx.nonce == nonce)
+            .map(|(h, t)| (t.at, h.clone()))
+            .collect();
+        same.sort();
+        // relink this nonce's chain in broadcast order; a lower fee cap can't have replaced anything
+        for pair in same.windows(2) {
+            let (older, newer) = (&pair[0].1, &pair[1].1);
+            if older != hash && newer != hash {
+                continue;
+            }
+            let bumped = self.entries[newer].tx.max_fee > self.entries[older].tx.max_fee;
+            if bumped && self.entries[older].replaced_by.is_none() {
+                if let Some(o) = self.entries.get_mut(older) {
+                    o.replaced_by = Some(newer.clone());
+                }
+                if let Some(n) = self.entries.get_mut(newer) {
+                    n.replaces = Some(older.clone());
+                }
+                info!(sender = %sender, nonce, replaced = %older, by = %newer, "broadcast transaction replaced");
+            }
+        }
+    }
+
+    fn to_json(&self, hash: &str) -> Option<Value> {
+        let t = self.entries.get(hash)?;
+        let status = match t.replaced_by.as_ref().and_then(|h| self.entries.get(h)) {
+            Some(r) if r.is_cancel() => "cancelled",
+            Some(_) => "replaced",
+            None => "broadcast",
+        };
+        let gwei = |wei: u128| wei as f64 / GWEI;
+        Some(json!({
+            "hash": hash,
+            "sender": t.sender,
+            "nonce": t.tx.nonce,
+            "to": t.tx.to,
+            "max_fee_gwei": gwei(t.tx.max_fee),
+            "max_priority_fee_gwei": t.tx.max_priority_fee.map(gwei),
+            "broadcast_at": t.at_unix,
+            "accepted_by": t.accepted_by,
+            "status": status,
+            "replaced_by": t.replaced_by,
+            "replaces": t.replaces,
+        }))
+    }
+}
+
+/// After a successful broadcast: track `raw_hex` under `hash` and look its sender up on the
+/// provider that accepted it.
+pub fn on_broadcast(state: &HttpState, snap: &Arc<Snapshot>, raw_hex: &str, hash: String, accepted_by: Option<String>) {
+    let Some(cfg) = &snap.cfg.tx_tracking else { return };
+    let Some(tx) = crate::keccak::decode_hex(raw_hex).and_then(|raw| crate::raw_tx::decode(&raw).ok().flatten()) else { return };
+    let accepted_by = accepted_by.unwrap_or_default();
+    state.relay.tx_tracker.lock().insert(hash.clone(), tx, accepted_by.clone(), cfg);
+
+    let all = snap.registry.all();
+    let Some(prov) = all.iter().find(|p| p.label() == accepted_by).or(all.first()).cloned() else { return };
+    let (tracker, snap) = (state.relay.tx_tracker.clone(), snap.clone());
+    tokio::spawn(async move {
+        for _ in 0..SENDER_TRIES {
+            sleep(SENDER_RETRY).await;
+            if let Some(sender) = lookup_sender(&prov, &snap, &hash).await {
+                tracker.lock().set_sender(&hash, sender.to_ascii_lowercase());
+                return;
+            }
+        }
+        debug!(hash = %hash, provider = %prov.label(), "sender of broadcast transaction not found; replacements can't be linked");
+    });
+}
+
+async fn lookup_sender(prov: &ProviderState, snap: &Snapshot, hash: &str) -> Option<String> {
+    let payload = JsonRpcRequest::new("eth_getTransactionByHash", json!([hash])).to_value();
+    prov.charge_token();
+    let rc = &snap.cfg.relay;
+    let reply = upstream::call(prov, &payload, rc.upstream_timeout(), rc.response_limit()).await.ok()?;
+    reply.get("result")?.get("from")?.as_str().map(str::to_string)
+}
+
+/// GET /tx/{hash}: what the relay knows about a transaction it broadcast.
+pub async fn tx_status(State(state): State<HttpState>, Path(hash): Path<String>) -> (StatusCode, Json<Value>) {
+    if state.app.snapshot().cfg.tx_tracking.is_none() {
+        return (StatusCode::NOT_FOUND, Json(json!({ "error": "tx_tracking is not enabled" })));
+    }
+    match state.relay.tx_tracker.lock().to_json(&hash.to_ascii_lowercase()) {
+        Some(v) => (StatusCode::OK, Json(v)),
+        None => (StatusCode::NOT_FOUND, Json(json!({ "error": "not a transaction this relay broadcast recently" }))),
+    }
+}
@@ -1,232 +1,670 @@
-use crate::state::{AppState, ProviderState};
-use crate::error_reason;
-use std::{collections::HashMap, sync::Arc, time::{Duration, Instant}};
-use tokio::time::sleep;
-
-/// Run the live terminal dashboard.
-/// - Default: ASCII status labels to avoid column drift.
-/// - Set RLY_TUI_EMOJI=1 to use emoji status (may misalign on some terminals).
-/// - Tick interval: RLY_TUI_INTERVAL_MS (default 2000 ms).
-pub async fn run_terminal_dashboard(app: Arc<AppState>) {
-    // Per-provider rolling counters to compute TPS/TPM
-    let mut last_counts: HashMap<String, (u64, Instant)> = HashMap::new();
-    let mut last_total_calls: (u64, Instant) = (0, Instant::now());
-
-    let interval = std::env::var("RLY_TUI_INTERVAL_MS")
-        .ok()
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(2000);
-
-    let use_emoji = std::env::var("RLY_TUI_EMOJI").ok().map(|v| v == "1" || v.eq_ignore_ascii_case("true")).unwrap_or(false);
-
-    loop {
-        let start = Instant::now();
-
-        // Snapshot providers
-        let reg = app.registry.read().await;
-        let providers: Vec<Arc<ProviderState>> =
-            reg.primaries.iter().chain(reg.secondaries.iter()).cloned().collect();
-        drop(reg);
-
-        // Build rows
-        let mut rows = Vec::new();
-        let mut total_tps = 0.0f64;
-        let mut total_tpm = 0.0f64;
-
-        for p in providers.iter() {
-            // TPS/TPM from call_count delta
-            let now = Instant::now();
-            let calls_now = p.call_count.load(std::sync::atomic::Ordering::Relaxed);
-            let (tps, tpm) = match last_counts.get(&p.url) {
-                Some((last, last_t)) => {
-                    let dt = now.duration_since(*last_t).as_secs_f64().max(0.001);
-                    let dc = calls_now.saturating_sub(*last) as f64;
-                    (dc / dt, dc * (60.0 / dt))
-                }
-                None => (0.0, 0.0),
-            };
-            last_counts.insert(p.url.clone(), (calls_now, now));
-            total_tps += tps;
-            total_tpm += tpm;
-
-            let status = if p.breaker.lock().is_banned() {
-                if use_emoji { "⛔ BANNED".to_string() } else { "BANNED".to_string() }
-            } else if p.is_healthy() {
-                if use_emoji { "🟢 OK".to_string() } else { "OK".to_string() }
-            } else {
-                if use_emoji { "🔴 DOWN".to_string() } else { "DOWN".to_string() }
-            };
-
-            let url = truncate(&p.url, 45);
-            let weight = p.get_weight();
-            let block = p.get_latest_block();
-            let behind = p.get_behind();
-            let latency_ms = p.get_latency();
-            let err = p.errors.load(std::sync::atomic::Ordering::Relaxed);
-            let calls = calls_now;
-            let last_err = error_reason::get_last_error(&p.url).as_str().to_string();
-
-            rows.push(Row {
-                url,
-                status,
-                weight,
-                block,
-                behind,
-                latency_ms: latency_ms as f64,
-                tps,
-                tpm,
-                err,
-                last_err,
-                calls,
-            });
-        }
-
-        // Header line with totals + cache
-        let total_calls = app.total_calls.load(std::sync::atomic::Ordering::Relaxed);
-        let cache_hits = app.cache_hits.load(std::sync::atomic::Ordering::Relaxed);
-        let hit_rate = if total_calls == 0 { 0.0 } else { (cache_hits as f64) * 100.0 / (total_calls as f64) };
-
-        // Optional global TPS/TPM from total calls delta (incoming)
-        let now = Instant::now();
-        let (glob_tps, glob_tpm) = {
-            let dt = now.duration_since(last_total_calls.1).as_secs_f64().max(0.001);
-            let dc = total_calls.saturating_sub(last_total_calls.0) as f64;
-            last_total_calls = (total_calls, now);
-            (dc / dt, dc * (60.0 / dt))
-        };
-
-        print_frame(rows, total_calls, cache_hits, hit_rate, total_tps, total_tpm, glob_tps, glob_tpm);
-
-        // Pace the loop
-        let elapsed = start.elapsed();
-        if elapsed < Duration::from_millis(interval) {
-            sleep(Duration::from_millis(interval) - elapsed).await;
-        }
-    }
-}
-
-struct Row {
-    url: String,
-    status: String,
-    weight: u32,
-    block: u64,
-    behind: u64,
-    latency_ms: f64,
-    tps: f64,
-    tpm: f64,
-    err: u64,
-    last_err: String, // NEW
-    calls: u64,
-}
-
-// --- formatting helpers ---
-
-fn truncate(s: &str, width: usize) -> String {
-    if s.chars().count() <= width { return s.to_string(); }
-    let mut out = String::with_capacity(width);
-    for (i, ch) in s.chars().enumerate() {
-        if i + 1 >= width { break; }
-        out.push(ch);
-    }
-    out.push('…');
-    out
-}
-
-fn pad(s: &str, width: usize) -> String {
-    let len = s.chars().count();
-    if len >= width { s.to_string() } else { format!("{}{}", s, " ".repeat(width - len)) }
-}
-
-fn make_summary_line(total_width: usize, content: &str) -> String {
-    let inner = total_width.saturating_sub(2);
-    let clipped = {
-        let mut out = String::new();
-        for ch in content.chars() {
-            if out.chars().count() >= inner { break; }
-            out.push(ch);
-        }
-        out
-    };
-    format!("│{}│", pad(&clipped, inner))
-}
-
-fn print_frame(rows: Vec<Row>, total_calls: u64, cache_hits: u64, hit_rate: f64,
-               total_tps: f64, total_tpm: f64, glob_tps: f64, glob_tpm: f64) {
-    // Column widths
-    let w_url   = 45usize;
-    let w_stat  = 8usize;   // "OK/DOWN/." fits
-    let w_wt    = 8usize;   // (weight)
-    let w_block = 13usize;  // latest susize;   // behind
-    let w_bhin  = 7usize;   // behind
-    let w_lat   = 12usize;  // latency (ms)
-    let w_tps   = 8usize;
-    let w_tpm   = 8usize;
-    let w_err   = 8usize;
-    let w_lerr  = 12usize;  // NEW: last error reason (rpc_error/timeout/...)
-    let w_calls = 12usize;
-
-    let total_w =
-        1 + w_url + 1 + w_stat + 1 + w_wt + 1 + w_block + 1 + w_bhin + 1 + w_lat + 1 + w_tps + 1 + w_tpm + 1 + w_err + 1 + w_lerr + 1 + w_calls + 1;
-
-    // Summary header (exact widths, ASCII only to avoid drift)
-    println!("╭{}╮", "─".repeat(total_w.saturating_sub(2)));
-    let line1 = format!("  Total calls: {} | Cache hits: {} | Hit rate: {:.1}%",
-                        total_calls, cache_hits, hit_rate);
-    println!("{}", make_summary_line(total_w, &line1));
-    let line2 = format!("  Ingress: {:.1} TPS | {:.0} TPM   Providers (sum): {:.1} TPS | {:.0} TPM",
-                        glob_tps, glob_tpm, total_tps, total_tpm);
-    println!("{}", make_summary_line(total_w, &line2));
-    println!("╰{}╯", "─".repeat(total_w.saturating_sub(2)));
-
-    // Table header
-    println!(
-        "┏{}┳{}┳{}┳{}┳{}┳{}┳{}┳{}┳{}┳{}┳{}┓",
-        pad(" URL", w_url),
-        pad(" Status", w_stat),
-        pad(" Weight", w_wt),
-        pad(" Block", w_block),
-        pad(" >>>", w_bhin),
-        pad(" Latency ms", w_lat),
-        pad(" TPS", w_tps),
-        pad(" TPM", w_tpm),
-        pad(" Err", w_err),
-        pad(" Last_err", w_lerr),
-       pad(" Calls", w_calls),
-   );
-
-    println!(
-       "┡{}┿{}┿{}┿{}┿{}┿{}┿{}┿{}┿{}┿{}┿{}┩",
-        "━".repeat(w_url),
-        "━".repeat(w_stat),
-        "━".repeat(w_wt),
-        "━".repeat(w_block),
-        "━".repeat(w_bhin),
-        "━".repeat(w_lat),
-        "━".repeat(w_tps),
-        "━".repeat(w_tpm),
-        "━".repeat(w_err),
-        "━".repeat(w_lerr),
-        "━".repeat(w_calls),
-    );
-
-    for r in rows {
-        let lat_display = if r.latency_ms > 1.0e9 { "∞".to_string() } else { format!("{:.1}", r.latency_ms) };
-        let block_display = if r.block == 0 { "–".to_string() } else { format!("{}", r.block) };
-        println!(
-            "│{}│{}│{}│{}│{}│{}│{}│{}│{}│{}│{}│",
-            pad(&r.url, w_url),
-            pad(&r.status, w_stat),
-            pad(&format!("{}", r.weight), w_wt),
-            pad(&block_display, w_block),
-            pad(&format!("{}", r.behind), w_bhin),
-            pad(&lat_display, w_lat),
-            pad(&format!("{:.1}", r.tps), w_tps),
-            pad(&format!("{:.0}", r.tpm), w_tpm),
-            pad(&format!("{}", r.err), w_err),
-            pad(&r.last_err, w_lerr),
-            pad(&format!("{}", r.calls), w_calls),
-        );
-    }
-
-    println!("└{}┘", "─".repeat(total_w.saturating_sub(2)));
-}
+use crate::config::{UiCharset, UiColumn, UiConfig};
+use crate::relay::{CacheStats, TtlCache};
+use crate::state::{AppState, ProviderState};
+use crate::error_reason;
+use crate::rates;
+use once_cell::sync::Lazy;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write as _,
+    io::{BufRead, IsTerminal, Write as _},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+
+// Latest frame drawn, without colors, for snapshot exports.
+static LAST_FRAME: Lazy<parking_lot::Mutex<String>> = Lazy::new(Default::default);
+
+// How long the header shows where the last snapshot went.
+const NOTICE_FOR: Duration = Duration::from_secs(10);
+
+/// Run the live terminal dashboard.
+/// - Default: ASCII status labels to avoid column drift.
+/// - Set RLY_TUI_EMOJI=1 to use emoji status (may misalign on some terminals).
+/// - Tick interval: RLY_TUI_INTERVAL_MS (default 2000 ms).
+/// - On a TTY the frame is redrawn in place; otherwise frames are appended.
+///   Either way a frame identical to the previous one is not written again.
+/// - On an interactive terminal, typing `s` + Enter exports a snapshot (see `export_snapshot`).
+pub async fn run_terminal_dashboard(app: Arc<AppState>, cache: TtlCache) {
+    // Per-provider rolling counters to compute TPS/TPM
+    let mut last_counts: HashMap<String, (u64, Instant)> = HashMap::new();
+    let mut last_total_calls: (u64, Instant) = (0, Instant::now());
+    // Per-provider trend history for the sparkline columns
+    let mut history: HashMap<String, Trend> = HashMap::new();
+
+    let interval = std::env::var("RLY_TUI_INTERVAL_MS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(2000);
+
+    let in_place = std::io::stdout().is_terminal();
+    let color = in_place && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty());
+    let mut last_frame = String::new();
+
+    let use_emoji = std::env::var("RLY_TUI_EMOJI").ok().map(|v| v == "1" || v.eq_ignore_ascii_case("true")).unwrap_or(false);
+
+    // Line-based keys (no raw mode): stdin is read on its own thread so the loop never blocks.
+    let (key_tx, mut key_rx) = tokio::sync::mpsc::unbounded_channel::<()>();
+    if in_place && std::io::stdin().is_terminal() {
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+                if line.trim().eq_ignore_ascii_case("s") && key_tx.send(()).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    let mut notice: Option<(String, Instant)> = None;
+
+    loop {
+        let start = Instant::now();
+
+        // Snapshot providers
+        let snap = app.snapshot();
+        let providers: Vec<Arc<ProviderState>> = snap.registry.all();
+        let decay = snap.cfg.relay.weight_decay.clone();
+        let ui_cfg = snap.cfg.ui.clone();
+        let network = snap.cfg.network.clone();
+        drop(snap);
+
+        // Build rows
+        let mut rows = Vec::new();
+        let mut total_tps = 0.0f64;
+        let mut total_tpm = 0.0f64;
+
+        for p in providers.iter() {
+            // TPS/TPM from call_count delta
+            let now = Instant::now();
+            let calls_now = p.call_count.load(std::sync::atomic::Ordering::Relaxed);
+            let (tps, tpm) = match last_counts.get(&p.id) {
+                Some((last, last_t)) => {
+                    let dt = now.duration_since(*last_t).as_secs_f64().max(0.001);
+                    let dc = calls_now.saturating_sub(*last) as f64;
+                    (dc / dt, dc * (60.0 / dt))
+                }
+                None => (0.0, 0.0),
+            };
+            last_counts.insert(p.id.clone(), (calls_now, now));
+            total_tps += tps;
+            total_tpm += tpm;
+
+            let banned = p.breaker.lock().is_banned();
+            let status = if p.misconfigured_since().is_some() {
+                if use_emoji { "🔑 AUTH".to_string() } else { "AUTH-ERR".to_string() }
+            } else if p.manual_ban().is_some() {
+                if use_emoji { "✋ OP-BAN".to_string() } else { "OP-BAN".to_string() }
+            } else if banned {
+                if use_emoji { "⛔ BANNED".to_string() } else { "BANNED".to_string() }
+            } else if p.is_drained() {
+                if use_emoji { "🔧 DRAIN".to_string() } else { "DRAINED".to_string() }
+            } else if p.is_off_schedule() {
+                if use_emoji { "⏰ OFF-HRS".to_string() } else { "OFF-HRS".to_string() }
+            } else if p.is_healthy() {
+                if use_emoji { "🟢 OK".to_string() } else { "OK".to_string() }
+            } else {
+                if use_emoji { "🔴 DOWN".to_string() } else { "DOWN".to_string() }
+            };
+
+            let url = p.label();
+            let weight = p.get_weight();
+            let weight_factor = p.weight_factor(decay.as_ref());
+            let block = p.get_latest_block();
+            let behind = p.get_behind();
+            let latency_ms = p.get_latency();
+            let err = p.errors.load(std::sync::atomic::Ordering::Relaxed);
+            let calls = calls_now;
+            let last_err = error_reason::last_error_label(&p.id);
+            let ok_pct = p.success_rate().map(|r| r * 100.0);
+            let trend = history.entry(p.id.clone()).or_default();
+            trend.push(tps, (latency_ms != u64::MAX).then_some(latency_ms as f64));
+
+            rows.push(Row {
+                url,
+                status,
+                weight,
+                weight_factor,
+                block,
+                behind,
+                latency_ms: latency_ms as f64,
+                tps,
+                tpm,
+                err,
+                ok_pct,
+                last_err,
+                calls,
+                trend: trend.clone(),
+                banned,
+                drained: p.is_drained() || p.is_off_schedule(),
+                healthy: p.is_healthy(),
+                uptime_24h: p.uptime.lock().uptime_pct(Duration::from_secs(86_400)),
+                bucket_fill: p.bucket_fill(),
+                bucket_rejections: p.bucket_rejections.load(std::sync::atomic::Ordering::Relaxed),
+                rates: {
+                    let r = p.rates.lock();
+                    rates::WINDOWS.map(|(_, secs)| r.per_second(secs))
+                },
+            });
+        }
+        history.retain(|id, _| providers.iter().any(|p| &p.id == id));
+
+        // Header line with totals + cache
+        let total_calls = app.total_calls.load(std::sync::atomic::Ordering::Relaxed);
+        let cache_hits = app.cache_hits.load(std::sync::atomic::Ordering::Relaxed);
+        let hit_rate = if total_calls == 0 { 0.0 } else { (cache_hits as f64) * 100.0 / (total_calls as f64) };
+
+        // Optional global TPS/TPM from total calls delta (incoming)
+        let now = Instant::now();
+        let (glob_tps, glob_tpm) = {
+            let dt = now.duration_since(last_total_calls.1).as_secs_f64().max(0.001);
+            let dc = total_calls.saturating_sub(last_total_calls.0) as f64;
+            last_total_calls = (total_calls, now);
+            (dc / dt, dc * (60.0 / dt))
+        };
+
+        let cache_stats = cache.stats().await;
+        let outcomes = app.totals.counts();
+        let notice_line = notice.as_ref().filter(|(_, at)| at.elapsed() < NOTICE_FOR).map(|(n, _)| n.clone());
+        let summary = Summary { network, notice: notice_line, total_calls, cache_hits, hit_rate, outcomes, total_tps, total_tpm, glob_tps, glob_tpm, cache: cache_stats, rss: crate::memory_guard::rss_bytes() };
+        let frame = render_frame(rows, &summary, &ui_cfg, color);
+        if frame != last_frame {
+            write_frame(&frame, in_place, last_frame.is_empty());
+            *LAST_FRAME.lock() = strip_colors(&frame);
+            last_frame = frame;
+        }
+
+        // Pace the loop; a snapshot key redraws right away with the notice
+        let pause = Duration::from_millis(interval).saturating_sub(start.elapsed());
+        tokio::select! {
+            _ = sleep(pause) => {}
+            Some(()) = key_rx.recv() => {
+                let dir = app.snapshot().cfg.ui.snapshot_dir.clone();
+                let text = match export_snapshot(&app, &dir) {
+                    Ok(files) => format!("Snapshot written: {}", files.iter().map(|f| f.display().to_string()).collect::<Vec<_>>().join(", ")),
+                    Err(e) => format!("Snapshot failed ({}): {}", dir.display(), e),
+                };
+                notice = Some((text, Instant::now()));
+            }
+        }
+    }
+}
+
+struct Row {
+    url: String,
+    status: String,
+    weight: u32,
+    weight_factor: f64, // < 1.0 while decayed by recent errors or lag
+    block: u64,
+    behind: u64,
+    latency_ms: f64,
+    tps: f64,
+    tpm: f64,
+    err: u64,
+    ok_pct: Option<f64>,
+    last_err: String, // NEW
+    calls: u64,
+    trend: Trend,
+    banned: bool,
+    drained: bool, // or off schedule
+    healthy: bool,
+    uptime_24h: Option<f64>,
+    bucket_fill: Option<f64>, // None => unlimited
+    bucket_rejections: u64,
+    rates: [(f64, f64); 3], // (requests/s, errors/s) over rates::WINDOWS
+}
+
+const TREND_SAMPLES: usize = 60;
+
+#[derive(Clone, Default)]
+struct Trend {
+    tps: VecDeque<Option<f64>>,
+    latency: VecDeque<Option<f64>>, // None while unmeasured
+}
+
+impl Trend {
+    fn push(&mut self, tps: f64, latency: Option<f64>) {
+        for (buf, v) in [(&mut self.tps, Some(tps)), (&mut self.latency, latency)] {
+            if buf.len() == TREND_SAMPLES { buf.pop_front(); }
+            buf.push_back(v);
+        }
+    }
+}
+
+struct Summary {
+    network: String, // one relay serves one network; the table is that network's group
+    notice: Option<String>, // result of the last snapshot export, for a few seconds
+    total_calls: u64,
+    cache_hits: u64,
+    hit_rate: f64,
+    outcomes: (u64, u64, u64, u64), // (succeeded, failed, HTTP 429s, broadcasts)
+    total_tps: f64,
+    total_tpm: f64,
+    glob_tps: f64,
+    glob_tpm: f64,
+    cache: CacheStats,
+    rss: Option<u64>,
+}
+
+const CACHE_PANEL_METHODS: usize = 8;
+
+fn human_bytes(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    }
+}
+
+// --- glyphs ---
+
+/// Frame and symbol glyphs. Every char is one column wide, so both sets lay out identically.
+struct Glyphs {
+    panel: [char; 4], // summary panel corners: top-left, top-right, bottom-left, bottom-right
+    line: char,
+    vert: char,
+    head: [char; 6],  // table header: top-left, top-right, top joint, rule-left, rule-right, rule joint
+    head_line: char,
+    foot: [char; 2],  // table bottom corners
+    ellipsis: char,
+    none: &'static str,     // no value yet
+    infinite: &'static str, // unmeasured latency, unlimited bucket
+    times: char,
+    bars: [char; 8],
+}
+
+const UNICODE: Glyphs = Glyphs {
+    panel: ['╭', '╮', '╰', '╯'],
+    line: '─',
+    vert: '│',
+    head: ['┏', '┓', '┳', '┡', '┩', '┿'],
+    head_line: '━',
+    foot: ['└', '┘'],
+    ellipsis: '…',
+    none: "–",
+    infinite: "∞",
+    times: '×',
+    bars: ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'],
+};
+
+const ASCII: Glyphs = Glyphs {
+    panel: ['+', '+', '+', '+'],
+    line: '-',
+    vert: '|',
+    head: ['+', '+', '+', '+', '+', '+'],
+    head_line: '=',
+    foot: ['+', '+'],
+    ellipsis: '~',
+    none: "-",
+    infinite: "inf",
+    times: 'x',
+    bars: ['_', '.', ',', '-', '~', '=', '+', '#'],
+};
+
+fn glyphs(set: UiCharset) -> &'static Glyphs {
+    match set {
+        UiCharset::Unicode => &UNICODE,
+        UiCharset::Ascii => &ASCII,
+        UiCharset::Auto if unicode_terminal() => &UNICODE,
+        UiCharset::Auto => &ASCII,
+    }
+}
+
+/// Best guess whether the terminal shows box drawing and symbols: on Windows only Windows
+/// Terminal and VS Code reliably do; elsewhere anything but TERM=dumb or a non-UTF-8 locale.
+/// An unset or C/POSIX locale (common in containers) keeps unicode, as before.
+fn unicode_terminal() -> bool {
+    let env = |k: &str| std::env::var(k).unwrap_or_default();
+    if env("TERM") == "dumb" {
+        return false;
+    }
+    if cfg!(windows) {
+        return !env("WT_SESSION").is_empty() || env("TERM_PROGRAM") == "vscode";
+    }
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"].iter().map(|k| env(k)).find(|v| !v.is_empty()).unwrap_or_default();
+    let locale = locale.to_ascii_lowercase();
+    locale.is_empty() || locale == "c" || locale == "posix" || locale.contains("utf-8") || locale.contains("utf8")
+}
+
+// --- formatting helpers ---
+
+// "1.2/0.8/0.5", like a load average
+fn load_average(rates: [f64; 3]) -> String {
+    rates.map(|r| format!("{:.1}", r)).join("/")
+}
+
+/// Render samples as block characters scaled to the window's own min..max.
+/// The newest sample is rightmost; gaps (no data yet) render as spaces.
+fn sparkline(samples: &VecDeque<Option<f64>>, width: usize, bars: &[char; 8]) -> String {
+    let per_cell = TREND_SAMPLES.div_ceil(width).max(1);
+    let cells: Vec<Option<f64>> = samples
+        .iter()
+        .copied()
+        .collect::<Vec<_>>()
+        .rchunks(per_cell)
+        .rev()
+        .map(|chunk| {
+            let vals: Vec<f64> = chunk.iter().flatten().copied().collect();
+            (!vals.is_empty()).then(|| vals.iter().sum::<f64>() / vals.len() as f64)
+        })
+        .collect();
+    let (lo, hi) = cells.iter().flatten().fold((f64::MAX, f64::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+    let line: String = cells
+        .iter()
+        .map(|c| match c {
+            Some(v) if hi > lo => bars[(((v - lo) / (hi - lo)) * 7.0).round() as usize],
+            Some(_) => bars[0],
+            None => ' ',
+        })
+        .collect();
+    format!("{:>width$}", line, width = width)
+}
+
+fn truncate(s: &str, width: usize, ellipsis: char) -> String {
+    if s.chars().count() <= width { return s.to_string(); }
+    let mut out = String::with_capacity(width);
+    for (i, ch) in s.chars().enumerate() {
+        if i + 1 >= width { break; }
+        out.push(ch);
+    }
+    out.push(ellipsis);
+    out
+}
+
+fn pad(s: &str, width: usize) -> String {
+    let len = s.chars().count();
+    if len >= width { s.to_string() } else { format!("{}{}", s, " ".repeat(width - len)) }
+}
+
+fn make_summary_line(total_width: usize, content: &str, vert: char) -> String {
+    let inner = total_width.saturating_sub(2);
+    let clipped = {
+        let mut out = String::new();
+        for ch in content.chars() {
+            if out.chars().count() >= inner { break; }
+            out.push(ch);
+        }
+        out
+    };
+    format!("{v}{}{v}", pad(&clipped, inner), v = vert)
+}
+
+// --- column layout ---
+
+const MIN_URL: usize = 16;
+
+fn title(c: UiColumn) -> &'static str {
+    match c {
+        UiColumn::Url => " URL",
+        UiColumn::Status => " Status",
+        UiColumn::Weight => " Weight",
+        UiColumn::Block => " Block",
+        UiColumn::Behind => " >>>",
+        UiColumn::Latency => " Latency ms",
+        UiColumn::Tps => " TPS",
+        UiColumn::Tpm => " TPM",
+        UiColumn::Rates => " Req/s 1/5/15m",
+        UiColumn::ErrorRates => " Err/s 1/5/15m",
+        UiColumn::Errors => " Err",
+        UiColumn::OkPct => " OK %",
+        UiColumn::LastError => " Last_err", // rpc_error/timeout/...
+        UiColumn::Calls => " Calls",
+        UiColumn::TpsTrend => " TPS trend",
+        UiColumn::LatencyTrend => " Latency trend",
+        UiColumn::Uptime => " Up 24h",
+        UiColumn::Bucket => " Bucket/Rej",
+    }
+}
+
+fn default_width(c: UiColumn) -> usize {
+    match c {
+        UiColumn::Url => 45,
+        UiColumn::Status => 8, // "OK/DOWN/." fits
+        UiColumn::Weight => 8,
+        UiColumn::Block => 13,
+        UiColumn::Behind => 7,
+        UiColumn::Latency => 12,
+        UiColumn::Tps | UiColumn::Tpm | UiColumn::Errors => 8,
+        UiColumn::Rates | UiColumn::ErrorRates => 17,
+        UiColumn::OkPct => 7,
+        UiColumn::LastError => 15,
+        UiColumn::Calls => 12,
+        UiColumn::TpsTrend | UiColumn::LatencyTrend => 15, // ~4 ticks per cell
+        UiColumn::Uptime => 8,
+        UiColumn::Bucket => 12,
+    }
+}
+
+fn cell(c: UiColumn, r: &Row, width: usize, g: &Glyphs) -> String {
+    match c {
+        UiColumn::Url => truncate(&r.url, width, g.ellipsis),
+        UiColumn::Status => r.status.clone(),
+        UiColumn::Weight if r.weight_factor < 0.995 => format!("{}{}{:.2}", r.weight, g.times, r.weight_factor),
+        UiColumn::Weight => r.weight.to_string(),
+        UiColumn::Block => if r.block == 0 { g.none.to_string() } else { r.block.to_string() },
+        UiColumn::Behind => r.behind.to_string(),
+        UiColumn::Latency => if r.latency_ms > 1.0e9 { g.infinite.to_string() } else { format!("{:.1}", r.latency_ms) },
+        UiColumn::Tps => format!("{:.1}", r.tps),
+        UiColumn::Tpm => format!("{:.0}", r.tpm),
+        UiColumn::Rates => load_average(r.rates.map(|(rps, _)| rps)),
+        UiColumn::ErrorRates => load_average(r.rates.map(|(_, eps)| eps)),
+        UiColumn::Errors => r.err.to_string(),
+        UiColumn::OkPct => r.ok_pct.map(|p| format!("{:.1}", p)).unwrap_or_else(|| g.none.to_string()),
+        UiColumn::LastError => r.last_err.clone(),
+        UiColumn::Calls => r.calls.to_string(),
+        UiColumn::TpsTrend => sparkline(&r.trend.tps, width, &g.bars),
+        UiColumn::LatencyTrend => sparkline(&r.trend.latency, width, &g.bars),
+        UiColumn::Uptime => r.uptime_24h.map(|p| format!("{:.2}", p)).unwrap_or_else(|| g.none.to_string()),
+        UiColumn::Bucket => match r.bucket_fill {
+            Some(f) => format!("{:.0}%/{}", f * 100.0, r.bucket_rejections),
+            None => format!("{}/{}", g.infinite, r.bucket_rejections),
+        },
+    }
+}
+
+/// Resolve the configured columns to (column, width), fitting `term_width` when auto-fit is on:
+/// trailing columns are dropped until the table fits, then a URL column without an explicit
+/// width takes the spare room (up to the longest label).
+fn layout(cfg: &UiConfig, term_width: Option<usize>, longest_url: usize) -> Vec<(UiColumn, usize)> {
+    let specs: Vec<(UiColumn, Option<usize>)> = if cfg.columns.is_empty() {
+        UiColumn::ALL.iter().map(|&c| (c, None)).collect()
+    } else {
+        cfg.columns.iter().map(|s| (s.column, s.width)).collect()
+    };
+    let flex_url = specs.iter().any(|&(c, w)| c == UiColumn::Url && w.is_none());
+    let mut cols: Vec<(UiColumn, usize)> =
+        specs.iter().map(|&(c, w)| (c, w.unwrap_or_else(|| default_width(c)).max(1))).collect();
+
+    let Some(tw) = term_width.filter(|_| cfg.auto_fit) else { return cols };
+    if flex_url {
+        for (c, w) in cols.iter_mut() {
+            if *c == UiColumn::Url { *w = MIN_URL; }
+        }
+    }
+    while table_width(&cols) > tw && cols.len() > 1 {
+        match cols.iter().rposition(|&(c, _)| c != UiColumn::Url) {
+            Some(i) => { cols.remove(i); }
+            None => break,
+        }
+    }
+    if flex_url {
+        let spare = tw.saturating_sub(table_width(&cols));
+        for (c, w) in cols.iter_mut() {
+            if *c == UiColumn::Url { *w += spare.min(longest_url.saturating_sub(*w)); }
+        }
+    }
+    cols
+}
+
+fn table_width(cols: &[(UiColumn, usize)]) -> usize {
+    1 + cols.iter().map(|(_, w)| w + 1).sum::<usize>()
+}
+
+/// Terminal width of stdout, falling back to $COLUMNS; None when neither is known.
+#[cfg(unix)]
+fn terminal_width() -> Option<usize> {
+    // SAFETY: TIOCGWINSZ only writes into the winsize struct we pass.
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) } == 0 && ws.ws_col > 0 {
+        return Some(ws.ws_col as usize);
+    }
+    std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok())
+}
+
+/// $COLUMNS, else a classic 120-column console; there is no TTY query here.
+#[cfg(not(unix))]
+fn terminal_width() -> Option<usize> {
+    const FALLBACK_WIDTH: usize = 120;
+    Some(std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()).unwrap_or(FALLBACK_WIDTH))
+}
+
+// Cursor home, then overwrite line by line (clearing each tail) and clear whatever is left below;
+// cheaper and flicker-free compared to clearing the whole screen every frame.
+fn write_frame(frame: &str, in_place: bool, first: bool) {
+    let mut out = std::io::stdout().lock();
+    if !in_place {
+        let _ = out.write_all(frame.as_bytes());
+        let _ = out.flush();
+        return;
+    }
+    let mut buf = String::with_capacity(frame.len() + 256);
+    buf.push_str(if first { "\x1b[2J\x1b[H" } else { "\x1b[H" });
+    for line in frame.lines() {
+        buf.push_str(line);
+        buf.push_str("\x1b[K\n");
+    }
+    buf.push_str("\x1b[J");
+    let _ = out.write_all(buf.as_bytes());
+    let _ = out.flush();
+}
+
+/// Write the /status document (retired providers included) and, if the dashboard has drawn
+/// one, its latest frame as plain text, to `dir` as rly-snapshot-<unix seconds>.json / .txt.
+/// Returns the files written.
+pub fn export_snapshot(app: &AppState, dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+    let stamp = crate::timing::epoch_secs();
+    let base = dir.join(format!("rly-snapshot-{}", stamp));
+    let status = crate::relay::status_json(app, true);
+    let json_path = base.with_extension("json");
+    std::fs::write(&json_path, serde_json::to_vec_pretty(&status).unwrap_or_default())?;
+    let mut written = vec![json_path];
+    let frame = LAST_FRAME.lock().clone();
+    if !frame.is_empty() {
+        let txt_path = base.with_extension("txt");
+        std::fs::write(&txt_path, frame)?;
+        written.push(txt_path);
+    }
+    Ok(written)
+}
+
+// --- colors ---
+
+/// `frame` without the SGR color sequences `render_frame` adds.
+fn strip_colors(frame: &str) -> String {
+    let mut out = String::with_capacity(frame.len());
+    let mut rest = frame;
+    while let Some(i) = rest.find("\x1b[") {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        rest = rest.find('m').map_or("", |end| &rest[end + 1..]);
+    }
+    out.push_str(rest);
+    out
+}
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// Banned rows are red and lagging rows yellow throughout; otherwise only
+/// status and latency cells are colored.
+fn cell_color(c: UiColumn, r: &Row, ui: &UiConfig) -> Option<&'static str> {
+    if r.banned { return Some(RED); }
+    match c {
+        UiColumn::Status if r.drained => Some(YELLOW),
+        UiColumn::Status => Some(if r.healthy { GREEN } else { RED }),
+        UiColumn::Weight if r.weight_factor < 0.995 => Some(YELLOW),
+        UiColumn::Bucket if r.bucket_fill.is_some_and(|f| f < 0.1) => Some(YELLOW),
+        UiColumn::Latency if r.latency_ms <= 1.0e9 => {
+            let ms = r.latency_ms as u64;
+            Some(if ms >= ui.latency_crit_ms { RED } else if ms >= ui.latency_warn_ms { YELLOW } else { GREEN })
+        }
+        _ if r.behind > 0 => Some(YELLOW),
+        _ => None,
+    }
+}
+
+fn render_frame(rows: Vec<Row>, s: &Summary, ui: &UiConfig, color: bool) -> String {
+    let longest_url = rows.iter().map(|r| r.url.chars().count()).max().unwrap_or(0);
+    let cols = layout(ui, terminal_width(), longest_url);
+    let total_w = table_width(&cols);
+    let g = glyphs(ui.charset);
+    let mut f = String::new();
+
+    // Summary header (exact widths, ASCII only to avoid drift)
+    let rule = |n: usize| g.line.to_string().repeat(n);
+    let _ = writeln!(f, "{}{}{}", g.panel[0], rule(total_w.saturating_sub(2)), g.panel[1]);
+    let line1 = format!("  Total calls: {} | Cache hits: {} | Hit rate: {:.1}%",
+                        s.total_calls, s.cache_hits, s.hit_rate);
+    let _ = writeln!(f, "{}", make_summary_line(total_w, &line1, g.vert));
+    if let Some(notice) = &s.notice {
+        let _ = writeln!(f, "{}", make_summary_line(total_w, &format!("  {}", notice), g.vert));
+    }
+    let (ok, failed, too_many, broadcasts) = s.outcomes;
+    let fail_pct = failed as f64 * 100.0 / (ok + failed).max(1) as f64;
+    let line_outcomes = format!("  Requests: {} ok | {} failed ({:.2}%) | 429s: {} | Broadcasts: {}",
+                                ok, failed, fail_pct, too_many, broadcasts);
+    let _ = writeln!(f, "{}", make_summary_line(total_w, &line_outcomes, g.vert));
+    let line2 = format!("  Ingress: {:.1} TPS | {:.0} TPM   Providers (sum): {:.1} TPS | {:.0} TPM{}",
+                        s.glob_tps, s.glob_tpm, s.total_tps, s.total_tpm,
+                        if crate::health::is_degraded() { "   DEGRADED (loosened thresholds)" } else { "" });
+    let _ = writeln!(f, "{}", make_summary_line(total_w, &line2, g.vert));
+    let rss = s.rss.map(human_bytes).unwrap_or_else(|| "n/a".to_string());
+    let line3 = format!("  Cache: {} entries | ~{} | Pinned: {} entries | ~{} | Process RSS: {}",
+                        s.cache.entries, human_bytes(s.cache.approx_bytes as u64),
+                        s.cache.pinned_entries, human_bytes(s.cache.pinned_bytes as u64), rss);
+    let _ = writeln!(f, "{}", make_summary_line(total_w, &line3, g.vert));
+    for (method, hits, misses) in s.cache.per_method.iter().take(CACHE_PANEL_METHODS) {
+        let rate = *hits as f64 * 100.0 / (*hits + *misses).max(1) as f64;
+        let line = format!("    {:<28} hits {:>8} | misses {:>8} | hit rate {:>5.1}%", truncate(method, 28, g.ellipsis), hits, misses, rate);
+        let _ = writeln!(f, "{}", make_summary_line(total_w, &line, g.vert));
+    }
+    let _ = writeln!(f, "{}{}{}", g.panel[2], rule(total_w.saturating_sub(2)), g.panel[3]);
+
+    // Table header
+    let header: Vec<String> = cols.iter().map(|&(c, w)| pad(&truncate(title(c), w, g.ellipsis), w)).collect();
+    let _ = writeln!(f, "{}{}{}", g.head[0], header.join(&g.head[2].to_string()), g.head[1]);
+    let head_rule: Vec<String> = cols.iter().map(|&(_, w)| g.head_line.to_string().repeat(w)).collect();
+    let _ = writeln!(f, "{}{}{}", g.head[3], head_rule.join(&g.head[5].to_string()), g.head[4]);
+
+    let _ = writeln!(f, "{}", make_summary_line(total_w, &format!(" {}", s.network), g.vert));
+    let subtotal = format!(
+        "  {} subtotal: {}/{} healthy | calls {} | errors {} | {:.1} TPS",
+        s.network,
+        rows.iter().filter(|r| r.healthy).count(),
+        rows.len(),
+        rows.iter().map(|r| r.calls).sum::<u64>(),
+        rows.iter().map(|r| r.err).sum::<u64>(),
+        s.total_tps
+    );
+    for r in rows {
+        let cells: Vec<String> = cols
+            .iter()
+            .map(|&(c, w)| {
+                // pad before coloring so escape codes don't count toward the width
+                let text = pad(&truncate(&cell(c, &r, w, g), w, g.ellipsis), w);
+                match cell_color(c, &r, ui).filter(|_| color) {
+                    Some(code) => format!("{}{}{}", code, text, RESET),
+                    None => text,
+                }
+            })
+            .collect();
+        let _ = writeln!(f, "{v}{}{v}", cells.join(&g.vert.to_string()), v = g.vert);
+    }
+    let _ = writeln!(f, "{}", make_summary_line(total_w, &subtotal, g.vert));
+
+    let _ = writeln!(f, "{}{}{}", g.foot[0], rule(total_w.saturating_sub(2)), g.foot[1]);
+    f
+}
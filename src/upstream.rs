@@ -0,0 +1,180 @@
+use crate::error_reason::ErrorReason;
+use crate::state::ProviderState;
+use crate::timing;
+use reqwest::{header::CONTENT_LENGTH, StatusCode};
+use serde_json::Value;
+use std::{fmt, future::Future, pin::Pin, sync::Arc, time::Duration};
+use tokio::time::Instant;
+
+/// Why a single upstream attempt produced no JSON body.
+#[derive(Debug)]
+pub enum UpstreamError {
+    Timeout,
+    Http(reqwest::Error), // URL stripped: provider URLs may embed API keys
+    BadJson(String),
+    TooLarge(usize),
+    RateLimited, // HTTP 429
+    Status(u16), // auth failures, 5xx, or any non-2xx without a JSON body
+}
+
+impl UpstreamError {
+    pub fn reason(&self) -> ErrorReason {
+        match self {
+            UpstreamError::Timeout => ErrorReason::Timeout,
+            UpstreamError::Status(code) => status_reason(*code),
+            UpstreamError::Http(e) => transport_reason(e),
+            UpstreamError::BadJson(_) => ErrorReason::BadJson,
+            UpstreamError::TooLarge(_) => ErrorReason::TooLarge,
+            UpstreamError::RateLimited => ErrorReason::RateLimited,
+        }
+    }
+
+    /// HTTP status the provider answered with, when the failure came from one.
+    pub fn http_status(&self) -> Option<u16> {
+        match self {
+            UpstreamError::Status(code) => Some(*code),
+            UpstreamError::RateLimited => Some(429),
+            UpstreamError::Http(e) => e.status().map(|s| s.as_u16()),
+            _ => None,
+        }
+    }
+}
+
+fn status_reason(code: u16) -> ErrorReason {
+    match code {
+        401 | 403 => ErrorReason::AuthFailed,
+        429 => ErrorReason::RateLimited,
+        400..=499 => ErrorReason::Http4xx,
+        500..=599 => ErrorReason::Http5xx,
+        _ => ErrorReason::HttpError,
+    }
+}
+
+// reqwest only says "error sending request"; the cause is further down the source chain
+// (hyper-util's connect error, rustls via io::Error, hyper's own errors).
+fn transport_reason(e: &reqwest::Error) -> ErrorReason {
+    if let Some(status) = e.status() {
+        return status_reason(status.as_u16());
+    }
+    let mut reset = false;
+    let mut tls = false;
+    let mut cause: Option<&dyn std::error::Error> = Some(e);
+    while let Some(err) = cause {
+        let msg = err.to_string().to_ascii_lowercase();
+        if msg.contains("dns error") || msg.contains("failed to lookup address") {
+            return ErrorReason::DnsFailure;
+        }
+        tls |= msg.contains("certificate") || msg.contains("tls") || msg.contains("handshake");
+        reset |= msg.contains("connection reset") || msg.contains("connection closed") || msg.contains("broken pipe");
+        if let Some(io) = err.downcast_ref::<std::io::Error>() {
+            use std::io::ErrorKind::*;
+            reset |= matches!(io.kind(), ConnectionReset | ConnectionAborted | BrokenPipe | UnexpectedEof);
+        }
+        cause = err.source();
+    }
+    if tls {
+        ErrorReason::TlsError
+    } else if e.is_connect() && e.is_timeout() {
+        ErrorReason::ConnectTimeout
+    } else if reset {
+        ErrorReason::ConnectionReset
+    } else {
+        ErrorReason::HttpError
+    }
+}
+
+impl fmt::Display for UpstreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpstreamError::Timeout => write!(f, "upstream timeout"),
+            UpstreamError::Http(e) => write!(f, "upstream error: {}", e),
+            UpstreamError::BadJson(e) => write!(f, "bad json: {}", e),
+            UpstreamError::TooLarge(limit) => write!(f, "response too large (limit {} bytes)", limit),
+            UpstreamError::RateLimited => write!(f, "rate limited by upstream (HTTP 429)"),
+            UpstreamError::Status(code) => write!(f, "upstream HTTP {}", code),
+        }
+    }
+}
+
+pub type SendFuture<'a> = Pin<Box<dyn Future<Output = Result<Value, UpstreamError>> + Send + 'a>>;
+
+/// How JSON-RPC payloads reach a provider. `deadline` bounds the whole exchange,
+/// body included; replies over `max_bytes` are abandoned rather than buffered.
+/// Everything above this (selection, failover, accounting) is transport-agnostic.
+pub trait UpstreamTransport: fmt::Debug + Send + Sync {
+    fn send<'a>(&'a self, prov: &'a ProviderState, payload: &'a Value, deadline: Instant, max_bytes: usize) -> SendFuture<'a>;
+}
+
+/// JSON over HTTP POST to the provider URL, on the provider's own client pool.
+#[derive(Debug)]
+pub struct HttpTransport;
+
+impl UpstreamTransport for HttpTransport {
+    fn send<'a>(&'a self, prov: &'a ProviderState, payload: &'a Value, deadline: Instant, max_bytes: usize) -> SendFuture<'a> {
+        Box::pin(post_json(prov, &prov.url, payload, deadline.saturating_duration_since(Instant::now()), max_bytes))
+    }
+}
+
+/// Transport for a provider URL. Only http(s) exists so far; other schemes go
+/// through HTTP too and fail there with a clear error.
+pub fn transport_for(_url: &str) -> Arc<dyn UpstreamTransport> {
+    Arc::new(HttpTransport)
+}
+
+/// Send a JSON-RPC payload to one provider through its transport and parse the reply.
+/// `timeout` covers the whole exchange including the body.
+pub async fn call(prov: &ProviderState, payload: &Value, timeout: Duration, max_bytes: usize) -> Result<Value, UpstreamError> {
+    prov.transport().send(prov, payload, Instant::now() + timeout, max_bytes).await
+}
+
+/// POST JSON to a URL of the provider (its RPC URL, or e.g. its GraphQL endpoint).
+/// The connect / TTFB / body split goes into the provider's averages and to an
+/// enclosing `timing::timed` call.
+pub async fn post_json(prov: &ProviderState, url: &str, payload: &Value, timeout: Duration, max_bytes: usize) -> Result<Value, UpstreamError> {
+    let exchange = async {
+        let started = Instant::now();
+        let sent = prov.client().post(url).json(payload).send().await;
+        let mut resp = sent.map_err(http_err)?;
+        let to_headers = timing::ms(started.elapsed());
+        timing::note(|p| p.ttfb_ms = Some(to_headers.saturating_sub(p.connect_ms.unwrap_or(0))));
+        prov.capture_headers(resp.headers());
+        let status = resp.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(UpstreamError::RateLimited);
+        }
+        // Bad credentials and outages never carry a usable reply, whatever the body says.
+        if matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) || status.is_server_error() {
+            return Err(UpstreamError::Status(status.as_u16()));
+        }
+
+        let declared = resp
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<usize>().ok());
+        if declared.is_some_and(|n| n > max_bytes) {
+            return Err(UpstreamError::TooLarge(max_bytes));
+        }
+
+        let reading = Instant::now();
+        let mut body = Vec::with_capacity(declared.unwrap_or(0).min(max_bytes));
+        while let Some(chunk) = resp.chunk().await.map_err(http_err)? {
+            if body.len() + chunk.len() > max_bytes {
+                return Err(UpstreamError::TooLarge(max_bytes));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        timing::note(|p| p.body_ms = Some(timing::ms(reading.elapsed())));
+        match serde_json::from_slice::<Value>(&body) {
+            Ok(v) => Ok(v),
+            Err(_) if !status.is_success() => Err(UpstreamError::Status(status.as_u16())),
+            Err(e) => Err(UpstreamError::BadJson(e.to_string())),
+        }
+    };
+    let (res, phases) = timing::timed(tokio::time::timeout(timeout, exchange)).await;
+    prov.phases.lock().record(&phases);
+    timing::note(|p| *p = phases);
+    res.unwrap_or(Err(UpstreamError::Timeout))
+}
+
+fn http_err(e: reqwest::Error) -> UpstreamError { UpstreamError::Http(e.without_url()) }
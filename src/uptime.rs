@@ -0,0 +1,59 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Longest window we report; older transitions are pruned.
+pub const HORIZON: Duration = Duration::from_secs(7 * 86_400);
+
+#[derive(Clone, Copy, Debug)]
+pub struct Transition {
+    pub at: Instant,
+    pub unix_s: u64,
+    pub healthy: bool,
+}
+
+/// Healthy/unhealthy history of one provider, fed once per health-check round.
+#[derive(Debug, Default)]
+pub struct UptimeTracker {
+    transitions: VecDeque<Transition>,
+}
+
+impl UptimeTracker {
+    pub fn observe(&mut self, healthy: bool) {
+        if self.transitions.back().map(|t| t.healthy) == Some(healthy) {
+            return;
+        }
+        let unix_s = crate::timing::epoch_secs();
+        self.transitions.push_back(Transition { at: Instant::now(), unix_s, healthy });
+        // keep one transition at/before the horizon so the state at the window start is known
+        while self.transitions.len() > 1 && self.transitions[1].at.elapsed() >= HORIZON {
+            self.transitions.pop_front();
+        }
+    }
+
+    /// Percentage of the last `window` spent healthy, counting only time since
+    /// the first observation. None until something has been observed.
+    pub fn uptime_pct(&self, window: Duration) -> Option<f64> {
+        let first = self.transitions.front()?;
+        let now = Instant::now();
+        let start = now.checked_sub(window).map_or(first.at, |s| s.max(first.at));
+        let total = now.duration_since(start);
+        if total.is_zero() {
+            return Some(if self.transitions.back()?.healthy { 100.0 } else { 0.0 });
+        }
+
+        let mut up = Duration::ZERO;
+        for (i, t) in self.transitions.iter().enumerate() {
+            let end = self.transitions.get(i + 1).map_or(now, |n| n.at);
+            if end <= start || !t.healthy {
+                continue;
+            }
+            up += end.duration_since(t.at.max(start));
+        }
+        Some(up.as_secs_f64() * 100.0 / total.as_secs_f64())
+    }
+
+    /// Most recent transitions, newest first.
+    pub fn recent(&self, n: usize) -> Vec<Transition> {
+        self.transitions.iter().rev().take(n).copied().collect()
+    }
+}